@@ -14,6 +14,7 @@ use std::env;
 
 use futures::executor::block_on;
 use ruba::{Ruba, TableStats};
+use ruba::extractor::Extractor;
 use time::precise_time_ns;
 use failure::Fail;
 
@@ -28,12 +29,12 @@ fn main() {
     let _ = block_on(ruba.load_csv(
         filename, "default", LOAD_CHUNK_SIZE,
         vec![
-            ("Tolls_Amt".to_owned(), ruba::extractor::multiply_by_100),
-            ("Tip_Amt".to_owned(), ruba::extractor::multiply_by_100),
-            ("Fare_Amt".to_owned(), ruba::extractor::multiply_by_100),
-            ("Total_Amt".to_owned(), ruba::extractor::multiply_by_100),
-            ("Trip_Pickup_DateTime".to_owned(), ruba::extractor::date_time),
-            ("Trip_Dropoff_DateTime".to_owned(), ruba::extractor::date_time),
+            ("Tolls_Amt".to_owned(), Extractor::Decimal(2)),
+            ("Tip_Amt".to_owned(), Extractor::Decimal(2)),
+            ("Fare_Amt".to_owned(), Extractor::Decimal(2)),
+            ("Total_Amt".to_owned(), Extractor::Decimal(2)),
+            ("Trip_Pickup_DateTime".to_owned(), Extractor::Timestamp),
+            ("Trip_Dropoff_DateTime".to_owned(), Extractor::Timestamp),
         ]));
     let table_stats = block_on(ruba.table_stats()).expect("!?!");
     print_table_stats(&table_stats, start_time);
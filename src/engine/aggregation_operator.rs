@@ -1,11 +1,21 @@
+use std::cmp;
 use std::collections::HashMap;
-use std::hash::BuildHasherDefault;
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::{f64, i64};
 use std::marker::PhantomData;
 
+use bit_vec::BitVec;
+use engine::hyperloglog;
+use engine::moments::{self, Moments};
+use engine::tdigest::{self, TDigest};
 use engine::typed_vec::TypedVec;
+use engine::types::EncodingType;
 use engine::vector_op::*;
 use engine::*;
 use ingest::raw_val::RawVal;
+use mem_store::column::ColumnCodec;
+use ordered_float::OrderedFloat;
 use seahash::SeaHasher;
 
 type HashMapSea<K, V> = HashMap<K, V, BuildHasherDefault<SeaHasher>>;
@@ -73,20 +83,20 @@ impl<T: IntVecType<T> + IntoUsize> HashMapGrouping<T> {
 impl<'a, T: IntVecType<T> + IntoUsize> VecOperator<'a> for HashMapGrouping<T> {
     fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
         let (unique, grouping_key, cardinality) = {
-            let mut count = T::zero();
-            let grouping_key = scratchpad.get::<T>(self.input);
-            let mut grouping = Vec::with_capacity(grouping_key.len());
+            let raw_grouping_key = scratchpad.get::<T>(self.input);
+            let mut grouping = Vec::with_capacity(raw_grouping_key.len());
             let mut groups = Vec::new();
             let mut map = HashMapSea::default();
-            for i in grouping_key.iter() {
-                grouping.push(*map.entry(i).or_insert_with(|| {
+            for i in raw_grouping_key.iter() {
+                grouping.push(*map.entry(*i).or_insert_with(|| {
                     groups.push(*i);
-                    let old = count;
-                    count = count + T::one();
-                    old
+                    (groups.len() - 1) as u32
                 }));
             }
-            (grouping, groups, count.to_usize().unwrap())
+            let order = sort_groups(&mut grouping, &groups);
+            let unique = order.into_iter().map(|g| groups[g]).collect::<Vec<_>>();
+            let cardinality = unique.len();
+            (unique, grouping, cardinality)
         };
         scratchpad.set(self.unique_out, TypedVec::owned(unique));
         scratchpad.set(self.grouping_key_out, TypedVec::owned(grouping_key));
@@ -94,9 +104,161 @@ impl<'a, T: IntVecType<T> + IntoUsize> VecOperator<'a> for HashMapGrouping<T> {
     }
 }
 
+// Groups rows by the values of multiple columns of any type, which are serialized into a byte string per row.
+#[derive(Debug)]
+pub struct HashMapGroupingByteSlices {
+    inputs: Vec<BufferRef>,
+    grouping_key_out: BufferRef,
+    first_rows_out: BufferRef,
+}
+
+impl HashMapGroupingByteSlices {
+    pub fn boxed<'a>(inputs: Vec<BufferRef>, grouping_key_out: BufferRef, first_rows_out: BufferRef) -> BoxedOperator<'a> {
+        Box::new(HashMapGroupingByteSlices { inputs, grouping_key_out, first_rows_out })
+    }
+}
+
+impl<'a> VecOperator<'a> for HashMapGroupingByteSlices {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let (grouping_key, first_rows) = {
+            let columns = self.inputs.iter().map(|&input| scratchpad.get_any(input)).collect::<Vec<_>>();
+            let len = columns.get(0).map_or(0, |c| c.len());
+            let mut grouping = Vec::with_capacity(len);
+            let mut first_rows = Vec::new();
+            let mut map = HashMapSea::<Vec<u8>, u32>::default();
+            let mut key = Vec::new();
+            for i in 0..len {
+                key.clear();
+                for column in &columns {
+                    append_key_bytes(&**column, i, &mut key);
+                }
+                // Only allocate key when encountering new group
+                let group = match map.get(&key[..]) {
+                    Some(&group) => group,
+                    None => {
+                        let group = first_rows.len() as u32;
+                        map.insert(key.clone(), group);
+                        first_rows.push(i);
+                        group
+                    }
+                };
+                grouping.push(group);
+            }
+            let mut keys = vec![Vec::new(); first_rows.len()];
+            for (k, group) in map {
+                keys[group as usize] = k;
+            }
+            let order = sort_groups(&mut grouping, &keys);
+            let first_rows = order.into_iter().map(|g| first_rows[g]).collect::<Vec<_>>();
+            (grouping, first_rows)
+        };
+        scratchpad.set(self.grouping_key_out, TypedVec::owned(grouping_key));
+        scratchpad.set(self.first_rows_out, TypedVec::owned(first_rows));
+    }
+}
+
+// Byte strings compare in the same order as the values they were created from
+fn append_key_bytes<'a>(column: &TypedVec<'a>, i: usize, key: &mut Vec<u8>) {
+    match column.get_type() {
+        EncodingType::U8 => key.push(column.cast_ref_u8()[i]),
+        EncodingType::U16 => append_be_bytes(column.cast_ref_u16()[i] as u64, 2, key),
+        EncodingType::U32 => append_be_bytes(column.cast_ref_u32()[i] as u64, 4, key),
+        EncodingType::I64 => append_be_bytes((column.cast_ref_i64()[i] as u64) ^ (1 << 63), 8, key),
+        EncodingType::F64 => {
+            // Flip all bits of negative numbers and only the sign bit of positive numbers
+            let bits = column.cast_ref_f64()[i].to_bits();
+            let ordered = if bits >> 63 == 1 { !bits } else { bits ^ (1 << 63) };
+            append_be_bytes(ordered, 8, key)
+        }
+        EncodingType::Str => {
+            // Zero bytes are escaped as [0, 0xFF] to keep them distinct from the terminator [0, 0]
+            for &byte in column.cast_ref_str()[i].as_bytes() {
+                key.push(byte);
+                if byte == 0 {
+                    key.push(0xFF);
+                }
+            }
+            key.extend_from_slice(&[0, 0]);
+        }
+        t => unreachable!("unsupported type {:?} for grouping key", t),
+    }
+}
+
+// Types supported by `append_key_bytes`
+pub fn is_byte_slice_groupable(t: EncodingType) -> bool {
+    match t {
+        EncodingType::U8 | EncodingType::U16 | EncodingType::U32 |
+        EncodingType::I64 | EncodingType::F64 | EncodingType::Str => true,
+        _ => false,
+    }
+}
+
+fn append_be_bytes(value: u64, width: usize, key: &mut Vec<u8>) {
+    for b in (0..width).rev() {
+        key.push((value >> (8 * b)) as u8);
+    }
+}
+
+// Renumbers groups in ascending order of their keys, which allows results from different batches to be merged.
+// Returns the original index of each group in sorted order.
+fn sort_groups<K: Ord>(grouping: &mut [u32], keys: &[K]) -> Vec<usize> {
+    let mut order = (0..keys.len()).collect::<Vec<_>>();
+    order.sort_unstable_by(|&g1, &g2| keys[g1].cmp(&keys[g2]));
+    let mut rank = vec![0u32; keys.len()];
+    for (r, &g) in order.iter().enumerate() {
+        rank[g] = r as u32;
+    }
+    for g in grouping.iter_mut() {
+        *g = rank[*g as usize];
+    }
+    order
+}
+
+// Calls `aggregate` with the group and index of every row that is not null. Null values are skipped but still mark
+// their group as present.
+fn for_each_present<U, F>(grouping: &[U], present: Option<&BitVec>, modified: &mut [bool], mut aggregate: F)
+    where U: IntVecType<U>, F: FnMut(usize, usize) {
+    match present {
+        Some(present) => for (row, (i, p)) in grouping.iter().zip(present.iter()).enumerate() {
+            let index = i.cast_usize();
+            if p {
+                aggregate(index, row);
+            }
+            modified[index] = true;
+        },
+        None => for (row, i) in grouping.iter().enumerate() {
+            let index = i.cast_usize();
+            aggregate(index, row);
+            modified[index] = true;
+        },
+    }
+}
+
+// Removes entries of all groups that weren't present in grouping
+fn remove_unmodified<T>(values: &mut Vec<T>, modified: &[bool]) {
+    let mut j = 0;
+    for (i, &m) in modified.iter().enumerate() {
+        if m {
+            values.swap(j, i);
+            j += 1;
+        }
+    }
+    values.truncate(j);
+}
+
+// Aggregates of groups without any non-null values are null
+fn with_nulls<'a, T: VecType<T> + 'a>(values: Vec<T>, found: Vec<bool>, nullable: bool) -> BoxedVec<'a> {
+    if nullable {
+        TypedVec::nullable(TypedVec::owned(values), found.into_iter().collect())
+    } else {
+        TypedVec::owned(values)
+    }
+}
+
 #[derive(Debug)]
 pub struct VecCount<T> {
     grouping: BufferRef,
+    present: Option<BufferRef>,
     output: BufferRef,
     max_index: usize,
     dense_grouping: bool,
@@ -104,9 +266,10 @@ pub struct VecCount<T> {
 }
 
 impl<T> VecCount<T> {
-    pub fn new(grouping: BufferRef, output: BufferRef, max_index: usize, dense_grouping: bool) -> VecCount<T> {
+    pub fn new(grouping: BufferRef, present: Option<BufferRef>, output: BufferRef, max_index: usize, dense_grouping: bool) -> VecCount<T> {
         VecCount {
             grouping,
+            present,
             output,
             max_index,
             dense_grouping,
@@ -119,20 +282,12 @@ impl<'a, T: IntVecType<T> + IntoUsize> VecOperator<'a> for VecCount<T> {
     fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
         let result = {
             let mut result = vec![0u32; self.max_index + 1];
+            let mut modified = vec![false; self.max_index + 1];
             let grouping = scratchpad.get::<T>(self.grouping);
-            for i in grouping.iter() {
-                result[i.cast_usize()] += 1;
-            }
+            let present = self.present.map(|present| scratchpad.get_bit_vec(present));
+            for_each_present(&grouping, present.as_ref().map(|p| &**p), &mut modified, |index, _| result[index] += 1);
             if !self.dense_grouping {
-                // Remove 0 counts for all entries that weren't present in grouping
-                let mut j = 0;
-                for i in 0..result.len() {
-                    if result[i] > 0 {
-                        result[j] = result[i];
-                        j += 1;
-                    }
-                }
-                result.truncate(j);
+                remove_unmodified(&mut result, &modified);
             }
             TypedVec::owned(result)
         };
@@ -144,6 +299,7 @@ impl<'a, T: IntVecType<T> + IntoUsize> VecOperator<'a> for VecCount<T> {
 pub struct VecSum<T, U> {
     input: BufferRef,
     grouping: BufferRef,
+    present: Option<BufferRef>,
     output: BufferRef,
     max_index: usize,
     dense_grouping: bool,
@@ -152,11 +308,12 @@ pub struct VecSum<T, U> {
 }
 
 impl<T, U> VecSum<T, U> where
-    T: IntVecType<T>, U: IntVecType<U> + IntoUsize {
-    pub fn boxed<'a>(input: BufferRef, grouping: BufferRef, output: BufferRef, max_index: usize, dense_grouping: bool) -> BoxedOperator<'a> {
+    T: AggregationType<T>, U: IntVecType<U> + IntoUsize {
+    pub fn boxed<'a>(input: BufferRef, grouping: BufferRef, present: Option<BufferRef>, output: BufferRef, max_index: usize, dense_grouping: bool) -> BoxedOperator<'a> {
         Box::new(VecSum::<T, U> {
             input,
             grouping,
+            present,
             output,
             max_index,
             dense_grouping,
@@ -167,17 +324,69 @@ impl<T, U> VecSum<T, U> where
 }
 
 impl<'a, T, U> VecOperator<'a> for VecSum<T, U> where
-    T: IntVecType<T>, U: IntVecType<U> {
+    T: AggregationType<T>, U: IntVecType<U> {
     fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
         let result = {
             // TODO(clemens): this is already computed in unique function, we should just reuse
             let mut modified = vec![false; self.max_index + 1];
+            let mut found = vec![false; self.max_index + 1];
             let nums = scratchpad.get::<T>(self.input);
             let grouping = scratchpad.get::<U>(self.grouping);
-            let mut result = vec![0; self.max_index + 1];
-            for (i, n) in grouping.iter().zip(nums.iter()) {
-                result[i.cast_usize()] += Into::<i64>::into(*n);
-                modified[i.cast_usize()] = true;
+            let present = self.present.map(|present| scratchpad.get_bit_vec(present));
+            let mut result = vec![T::Accumulator::zero(); self.max_index + 1];
+            for_each_present(&grouping, present.as_ref().map(|p| &**p), &mut modified, |index, row| {
+                result[index] = result[index].add(nums[row].widen());
+                found[index] = true;
+            });
+            if !self.dense_grouping {
+                remove_unmodified(&mut result, &modified);
+                remove_unmodified(&mut found, &modified);
+            }
+
+            with_nulls(result, found, self.present.is_some())
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+// Sums a run-length encoded column by adding the value of each run to the groups of all rows it spans
+#[derive(Debug)]
+pub struct VecSumRuns<'a, U> {
+    codec: &'a ColumnCodec,
+    grouping: BufferRef,
+    output: BufferRef,
+    max_index: usize,
+    dense_grouping: bool,
+    u: PhantomData<U>,
+}
+
+impl<'a, U> VecSumRuns<'a, U> where U: IntVecType<U> + IntoUsize {
+    pub fn boxed(codec: &'a ColumnCodec, grouping: BufferRef, output: BufferRef, max_index: usize, dense_grouping: bool) -> BoxedOperator<'a> {
+        Box::new(VecSumRuns::<U> {
+            codec,
+            grouping,
+            output,
+            max_index,
+            dense_grouping,
+            u: PhantomData,
+        })
+    }
+}
+
+impl<'a, U> VecOperator<'a> for VecSumRuns<'a, U> where U: IntVecType<U> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let mut modified = vec![false; self.max_index + 1];
+            let (values, ends) = self.codec.runs().unwrap();
+            let grouping = scratchpad.get::<U>(self.grouping);
+            let mut result = vec![0i64; self.max_index + 1];
+            let mut start = 0;
+            for (&value, &end) in values.iter().zip(ends.iter()) {
+                for i in &grouping[start as usize..end as usize] {
+                    result[i.cast_usize()] += value;
+                    modified[i.cast_usize()] = true;
+                }
+                start = end;
             }
             if !self.dense_grouping {
                 // Remove 0 counts for all entries that weren't present in grouping
@@ -196,3 +405,399 @@ impl<'a, T, U> VecOperator<'a> for VecSum<T, U> where
         scratchpad.set(self.output, result);
     }
 }
+
+#[derive(Debug)]
+pub struct VecMinMax<T, U, Op> {
+    input: BufferRef,
+    grouping: BufferRef,
+    present: Option<BufferRef>,
+    output: BufferRef,
+    max_index: usize,
+    dense_grouping: bool,
+    t: PhantomData<T>,
+    u: PhantomData<U>,
+    op: PhantomData<Op>,
+}
+
+impl<T, U, Op> VecMinMax<T, U, Op> where
+    T: AggregationType<T>, U: IntVecType<U> + IntoUsize, Op: MinMaxOperation + fmt::Debug + 'static {
+    pub fn boxed<'a>(input: BufferRef, grouping: BufferRef, present: Option<BufferRef>, output: BufferRef, max_index: usize, dense_grouping: bool) -> BoxedOperator<'a> {
+        Box::new(VecMinMax::<T, U, Op> {
+            input,
+            grouping,
+            present,
+            output,
+            max_index,
+            dense_grouping,
+            t: PhantomData,
+            u: PhantomData,
+            op: PhantomData,
+        })
+    }
+}
+
+impl<'a, T, U, Op> VecOperator<'a> for VecMinMax<T, U, Op> where
+    T: AggregationType<T>, U: IntVecType<U>, Op: MinMaxOperation + fmt::Debug {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let mut modified = vec![false; self.max_index + 1];
+            let mut found = vec![false; self.max_index + 1];
+            let nums = scratchpad.get::<T>(self.input);
+            let grouping = scratchpad.get::<U>(self.grouping);
+            let present = self.present.map(|present| scratchpad.get_bit_vec(present));
+            let mut result = vec![Op::initial(); self.max_index + 1];
+            for_each_present(&grouping, present.as_ref().map(|p| &**p), &mut modified, |index, row| {
+                result[index] = Op::combine(result[index], nums[row].widen());
+                found[index] = true;
+            });
+            if !self.dense_grouping {
+                remove_unmodified(&mut result, &modified);
+                remove_unmodified(&mut found, &modified);
+            }
+
+            with_nulls(result, found, self.present.is_some())
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+// Aggregate that is computed by building up a state for each group from the non-null rows of the group. The grouping
+// and null handling are implemented once by `VecGroupedAggregate`.
+pub trait GroupAccumulator<'a>: fmt::Debug + 'a {
+    type Input: VecType<Self::Input> + 'a;
+    type State: Clone;
+
+    fn initial(&self) -> Self::State;
+    // `inputs` holds the values of each input buffer of the operator
+    fn update(&self, state: &mut Self::State, inputs: &[&[Self::Input]], row: usize);
+    // Receives the states of all output groups in order, groups without any non-null values have the initial state
+    fn serialize(&self, states: Vec<Self::State>, outputs: &[BufferRef], scratchpad: &mut Scratchpad<'a>);
+}
+
+#[derive(Debug)]
+pub struct VecGroupedAggregate<A, U> {
+    accumulator: A,
+    inputs: Vec<BufferRef>,
+    grouping: BufferRef,
+    present: Option<BufferRef>,
+    outputs: Vec<BufferRef>,
+    max_index: usize,
+    dense_grouping: bool,
+    u: PhantomData<U>,
+}
+
+impl<'a, A, U> VecGroupedAggregate<A, U> where
+    A: GroupAccumulator<'a>, U: IntVecType<U> + IntoUsize {
+    pub fn boxed(accumulator: A,
+                 inputs: Vec<BufferRef>,
+                 grouping: BufferRef,
+                 present: Option<BufferRef>,
+                 outputs: Vec<BufferRef>,
+                 max_index: usize,
+                 dense_grouping: bool) -> BoxedOperator<'a> {
+        Box::new(VecGroupedAggregate::<A, U> {
+            accumulator,
+            inputs,
+            grouping,
+            present,
+            outputs,
+            max_index,
+            dense_grouping,
+            u: PhantomData,
+        })
+    }
+}
+
+impl<'a, A, U> VecOperator<'a> for VecGroupedAggregate<A, U> where
+    A: GroupAccumulator<'a>, U: IntVecType<U> + IntoUsize {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let states = {
+            let mut modified = vec![false; self.max_index + 1];
+            let columns = self.inputs.iter().map(|&input| scratchpad.get::<A::Input>(input)).collect::<Vec<_>>();
+            let inputs = columns.iter().map(|column| &**column).collect::<Vec<_>>();
+            let grouping = scratchpad.get::<U>(self.grouping);
+            let present = self.present.map(|present| scratchpad.get_bit_vec(present));
+            let accumulator = &self.accumulator;
+            let mut states = vec![accumulator.initial(); self.max_index + 1];
+            for_each_present(&grouping, present.as_ref().map(|p| &**p), &mut modified, |index, row| {
+                accumulator.update(&mut states[index], &inputs, row);
+            });
+            if !self.dense_grouping {
+                remove_unmodified(&mut states, &modified);
+            }
+            states
+        };
+        self.accumulator.serialize(states, &self.outputs, scratchpad);
+    }
+}
+
+// Marks the dictionary codes that occur in each group in a bitset of `(dictionary_len + 7) / 8` bytes per group
+#[derive(Debug)]
+pub struct DistinctCodes<T> {
+    stride: usize,
+    t: PhantomData<T>,
+}
+
+impl<T> DistinctCodes<T> {
+    pub fn new(dictionary_len: usize) -> DistinctCodes<T> {
+        DistinctCodes { stride: cmp::max((dictionary_len + 7) / 8, 1), t: PhantomData }
+    }
+}
+
+impl<'a, T: IntVecType<T> + IntoUsize + 'a> GroupAccumulator<'a> for DistinctCodes<T> {
+    type Input = T;
+    type State = Vec<u8>;
+
+    fn initial(&self) -> Vec<u8> { Vec::new() }
+
+    fn update(&self, bitset: &mut Vec<u8>, inputs: &[&[T]], row: usize) {
+        let code = inputs[0][row].cast_usize();
+        if bitset.is_empty() {
+            bitset.resize(self.stride, 0);
+        }
+        bitset[code / 8] |= 1 << (code % 8);
+    }
+
+    fn serialize(&self, bitsets: Vec<Vec<u8>>, outputs: &[BufferRef], scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(outputs[0], TypedVec::owned(concat_sketches(bitsets, self.stride)));
+    }
+}
+
+// Builds a HyperLogLog sketch of `hyperloglog::REGISTERS` bytes for each group
+#[derive(Debug)]
+pub struct HyperLogLog<T> {
+    t: PhantomData<T>,
+}
+
+impl<T> HyperLogLog<T> {
+    pub fn new() -> HyperLogLog<T> {
+        HyperLogLog { t: PhantomData }
+    }
+}
+
+impl<'a, T: VecType<T> + Hash + 'a> GroupAccumulator<'a> for HyperLogLog<T> {
+    type Input = T;
+    type State = Vec<u8>;
+
+    fn initial(&self) -> Vec<u8> { Vec::new() }
+
+    fn update(&self, registers: &mut Vec<u8>, inputs: &[&[T]], row: usize) {
+        if registers.is_empty() {
+            registers.resize(hyperloglog::REGISTERS, 0);
+        }
+        hyperloglog::insert(registers, hash(&inputs[0][row]));
+    }
+
+    fn serialize(&self, registers: Vec<Vec<u8>>, outputs: &[BufferRef], scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(outputs[0], TypedVec::owned(concat_sketches(registers, hyperloglog::REGISTERS)));
+    }
+}
+
+// Collects the distinct integer values in each group, for count(distinct) on columns without a shared dictionary.
+// Exact, but keeps every distinct value. Outputs the number of values of each group followed by the sorted values.
+#[derive(Debug)]
+pub struct DistinctValues;
+
+impl<'a> GroupAccumulator<'a> for DistinctValues {
+    type Input = i64;
+    type State = Vec<i64>;
+
+    fn initial(&self) -> Vec<i64> { Vec::new() }
+
+    fn update(&self, values: &mut Vec<i64>, inputs: &[&[i64]], row: usize) {
+        values.push(inputs[0][row]);
+    }
+
+    fn serialize(&self, groups: Vec<Vec<i64>>, outputs: &[BufferRef], scratchpad: &mut Scratchpad<'a>) {
+        let mut result = Vec::new();
+        for mut values in groups {
+            values.sort_unstable();
+            values.dedup();
+            result.push(values.len() as i64);
+            result.extend_from_slice(&values);
+        }
+        scratchpad.set(outputs[0], TypedVec::owned(result));
+    }
+}
+
+// Builds a t-digest of `tdigest::SKETCH_BYTES` bytes for each group
+#[derive(Debug)]
+pub struct TDigestSketch;
+
+impl<'a> GroupAccumulator<'a> for TDigestSketch {
+    type Input = OrderedFloat<f64>;
+    type State = Vec<f64>;
+
+    fn initial(&self) -> Vec<f64> { Vec::new() }
+
+    fn update(&self, values: &mut Vec<f64>, inputs: &[&[OrderedFloat<f64>]], row: usize) {
+        values.push(inputs[0][row].0);
+    }
+
+    fn serialize(&self, groups: Vec<Vec<f64>>, outputs: &[BufferRef], scratchpad: &mut Scratchpad<'a>) {
+        let stride = tdigest::SKETCH_BYTES;
+        let mut result = vec![0u8; groups.len() * stride];
+        for (mut values, sketch) in groups.into_iter().zip(result.chunks_mut(stride)) {
+            if !values.is_empty() {
+                TDigest::from_values(&mut values).write(sketch);
+            }
+        }
+        scratchpad.set(outputs[0], TypedVec::owned(result));
+    }
+}
+
+// Computes count, means and (co)moments of pairs of values for each group, `moments::SKETCH_BYTES` bytes per group.
+// Takes two inputs, rows where either argument is null are not present.
+#[derive(Debug)]
+pub struct MomentsSketch;
+
+impl<'a> GroupAccumulator<'a> for MomentsSketch {
+    type Input = OrderedFloat<f64>;
+    type State = Moments;
+
+    fn initial(&self) -> Moments { Moments::default() }
+
+    fn update(&self, moments: &mut Moments, inputs: &[&[OrderedFloat<f64>]], row: usize) {
+        moments.push(inputs[0][row].0, inputs[1][row].0);
+    }
+
+    fn serialize(&self, groups: Vec<Moments>, outputs: &[BufferRef], scratchpad: &mut Scratchpad<'a>) {
+        let stride = moments::SKETCH_BYTES;
+        let mut result = vec![0u8; groups.len() * stride];
+        for (moments, sketch) in groups.iter().zip(result.chunks_mut(stride)) {
+            moments.write(sketch);
+        }
+        scratchpad.set(outputs[0], TypedVec::owned(result));
+    }
+}
+
+// Finds the row with the minimum or maximum key in each group. Outputs the key and index of that row, and whether the
+// group contains any row with non-null values. Ties resolve to the first row.
+#[derive(Debug)]
+pub struct ArgMinMax<T, Op> {
+    t: PhantomData<T>,
+    op: PhantomData<Op>,
+}
+
+impl<T, Op> ArgMinMax<T, Op> {
+    pub fn new() -> ArgMinMax<T, Op> {
+        ArgMinMax { t: PhantomData, op: PhantomData }
+    }
+}
+
+impl<'a, T, Op> GroupAccumulator<'a> for ArgMinMax<T, Op> where
+    T: AggregationType<T>, Op: MinMaxOperation + fmt::Debug + 'static {
+    type Input = T;
+    type State = Option<(T::Accumulator, usize)>;
+
+    fn initial(&self) -> Self::State { None }
+
+    fn update(&self, selected: &mut Self::State, inputs: &[&[T]], row: usize) {
+        let key = inputs[0][row].widen();
+        let replace = match *selected {
+            Some((current, _)) => Op::combine(current, key) != current,
+            None => true,
+        };
+        if replace {
+            *selected = Some((key, row));
+        }
+    }
+
+    fn serialize(&self, groups: Vec<Self::State>, outputs: &[BufferRef], scratchpad: &mut Scratchpad<'a>) {
+        let mut keys = Vec::with_capacity(groups.len());
+        let mut indices = Vec::with_capacity(groups.len());
+        let mut found = BitVec::with_capacity(groups.len());
+        for selected in groups {
+            let (key, index) = selected.unwrap_or_else(|| (Op::initial(), 0));
+            keys.push(key);
+            indices.push(index);
+            found.push(selected.is_some());
+        }
+        scratchpad.set(outputs[0], TypedVec::owned(keys));
+        scratchpad.set(outputs[1], TypedVec::owned(indices));
+        scratchpad.set(outputs[2], TypedVec::bit_vec(found));
+    }
+}
+
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = SeaHasher::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Concatenates sketches of `stride` bytes, groups without any non-null values get an empty sketch
+fn concat_sketches(sketches: Vec<Vec<u8>>, stride: usize) -> Vec<u8> {
+    let mut result = Vec::with_capacity(sketches.len() * stride);
+    for sketch in sketches {
+        if sketch.is_empty() {
+            result.resize(result.len() + stride, 0);
+        } else {
+            result.extend_from_slice(&sketch);
+        }
+    }
+    result
+}
+
+pub trait MinMaxOperation {
+    fn initial<A: Accumulator>() -> A;
+    fn combine<A: Accumulator>(accumulator: A, elem: A) -> A;
+}
+
+#[derive(Debug)]
+pub struct Minimum;
+
+impl MinMaxOperation for Minimum {
+    fn initial<A: Accumulator>() -> A { A::max_value() }
+    #[inline]
+    fn combine<A: Accumulator>(accumulator: A, elem: A) -> A { cmp::min(accumulator, elem) }
+}
+
+#[derive(Debug)]
+pub struct Maximum;
+
+impl MinMaxOperation for Maximum {
+    fn initial<A: Accumulator>() -> A { A::min_value() }
+    #[inline]
+    fn combine<A: Accumulator>(accumulator: A, elem: A) -> A { cmp::max(accumulator, elem) }
+}
+
+// Values that can be summed and compared, integers of all widths are aggregated as i64 and floats as f64
+pub trait AggregationType<T>: VecType<T> + 'static {
+    type Accumulator: Accumulator;
+    fn widen(self) -> Self::Accumulator;
+}
+
+impl<T: IntVecType<T>> AggregationType<T> for T {
+    type Accumulator = i64;
+    #[inline]
+    fn widen(self) -> i64 { self.into() }
+}
+
+impl AggregationType<OrderedFloat<f64>> for OrderedFloat<f64> {
+    type Accumulator = OrderedFloat<f64>;
+    #[inline]
+    fn widen(self) -> OrderedFloat<f64> { self }
+}
+
+pub trait Accumulator: VecType<Self> + 'static {
+    fn zero() -> Self;
+    fn add(self, other: Self) -> Self;
+    fn min_value() -> Self;
+    fn max_value() -> Self;
+}
+
+impl Accumulator for i64 {
+    fn zero() -> i64 { 0 }
+    #[inline]
+    fn add(self, other: i64) -> i64 { self + other }
+    fn min_value() -> i64 { i64::MIN }
+    fn max_value() -> i64 { i64::MAX }
+}
+
+impl Accumulator for OrderedFloat<f64> {
+    fn zero() -> OrderedFloat<f64> { OrderedFloat(0.0) }
+    #[inline]
+    fn add(self, other: OrderedFloat<f64>) -> OrderedFloat<f64> { OrderedFloat(self.0 + other.0) }
+    fn min_value() -> OrderedFloat<f64> { OrderedFloat(f64::NEG_INFINITY) }
+    fn max_value() -> OrderedFloat<f64> { OrderedFloat(f64::INFINITY) }
+}
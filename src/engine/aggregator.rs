@@ -1,11 +1,112 @@
-#[derive(Debug, Clone, Copy)]
+use engine::moments::Moments;
+use engine::tdigest::TDigest;
+use ordered_float::OrderedFloat;
+use syntax::expression::Expr;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Aggregator {
     Sum,
     Count,
+    Min,
+    Max,
+    Avg,
+    // Exact, counts dictionary codes of columns with a dictionary shared by all batches and integer values otherwise
+    CountDistinct,
+    ApproxCountDistinct,
+    // Approximate, quantile between 0 and 1
+    Percentile(OrderedFloat<f64>),
+    // Sample variance and standard deviation
+    Variance,
+    Stddev,
+    // Sample covariance and correlation of the two arguments of a `FuncType::Pair` expression
+    Covar,
+    Corr,
+    // Value of the first argument at the row with the minimum or maximum value of the second argument
+    ArgMin,
+    ArgMax,
+    // Same as arg_min and arg_max, usually ordered by a timestamp
+    First,
+    Last,
 }
 
 impl Aggregator {
     pub fn combine_i64(&self, accumulator: i64, elem: i64) -> i64 {
-        accumulator + elem
+        match *self {
+            Aggregator::Sum | Aggregator::Count => accumulator + elem,
+            Aggregator::Min => if elem < accumulator { elem } else { accumulator },
+            Aggregator::Max => if elem > accumulator { elem } else { accumulator },
+            Aggregator::Avg => panic!("avg is merged as separate sum and count"),
+            _ => panic!("{:?} is merged as sketch", self),
+        }
+    }
+
+    pub fn combine_f64(&self, accumulator: OrderedFloat<f64>, elem: OrderedFloat<f64>) -> OrderedFloat<f64> {
+        match *self {
+            Aggregator::Sum => OrderedFloat(accumulator.0 + elem.0),
+            Aggregator::Min => if elem < accumulator { elem } else { accumulator },
+            Aggregator::Max => if elem > accumulator { elem } else { accumulator },
+            Aggregator::Count => panic!("counts are not floats"),
+            Aggregator::Avg => panic!("avg is merged as separate sum and count"),
+            _ => panic!("{:?} is merged as sketch", self),
+        }
+    }
+
+    // Merges two sketches of the same group: code bitsets for count(distinct), HyperLogLog registers for
+    // approx_count_distinct, t-digests for percentiles and moments for variance and correlation
+    pub fn merge_sketch(&self, accumulator: &mut [u8], sketch: &[u8]) {
+        match *self {
+            Aggregator::CountDistinct => for (a, &s) in accumulator.iter_mut().zip(sketch) {
+                *a |= s;
+            },
+            Aggregator::ApproxCountDistinct => for (a, &s) in accumulator.iter_mut().zip(sketch) {
+                if s > *a { *a = s; }
+            },
+            Aggregator::Percentile(_) => TDigest::read(accumulator).merge(&TDigest::read(sketch)).write(accumulator),
+            Aggregator::Variance | Aggregator::Stddev | Aggregator::Covar | Aggregator::Corr =>
+                Moments::read(accumulator).merge(&Moments::read(sketch)).write(accumulator),
+            _ => panic!("{:?} is not merged as sketch", self),
+        }
+    }
+
+    pub fn is_sketch(&self) -> bool {
+        match *self {
+            Aggregator::CountDistinct | Aggregator::ApproxCountDistinct | Aggregator::Percentile(_) |
+            Aggregator::Variance | Aggregator::Stddev | Aggregator::Covar | Aggregator::Corr => true,
+            _ => false,
+        }
+    }
+
+    // Aggregates whose partial results are the key of the selected row followed by the selected value
+    pub fn selects_row(&self) -> bool {
+        match *self {
+            Aggregator::ArgMin | Aggregator::ArgMax | Aggregator::First | Aggregator::Last => true,
+            _ => false,
+        }
+    }
+
+    // Whether rows with larger keys are selected
+    pub fn selects_max(&self) -> bool {
+        match *self {
+            Aggregator::ArgMax | Aggregator::Last => true,
+            _ => false,
+        }
+    }
+
+    // Aggregates that are computed and merged for each batch, the final aggregate is derived from these
+    pub fn partial_aggregates(&self) -> Vec<Aggregator> {
+        match *self {
+            Aggregator::Avg => vec![Aggregator::Sum, Aggregator::Count],
+            aggregator => vec![aggregator],
+        }
+    }
+}
+
+// Adjacent percentiles of the same expression (e.g. from `percentiles(x, .5, .9)`) are all computed from the t-digests
+// of the first one
+pub fn shares_digest(aggregate: &[(Aggregator, Expr)], i: usize) -> bool {
+    i > 0 && match (&aggregate[i - 1], &aggregate[i]) {
+        (&(Aggregator::Percentile(_), ref e1), &(Aggregator::Percentile(_), ref e2)) => e1 == e2,
+        _ => false,
     }
 }
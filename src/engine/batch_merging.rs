@@ -1,27 +1,133 @@
-use std::cmp::{max, min};
+use std::borrow::Cow;
+use std::cmp::{max, min, Ordering};
+use std::mem;
+use std::slice;
 use std::usize;
 
-use engine::aggregator::Aggregator;
+use bit_vec::BitVec;
+
+use ::QueryError;
+use engine::aggregator::{self, Aggregator};
+use engine::hyperloglog;
+use engine::moments::Moments;
+use engine::tdigest::TDigest;
 use engine::types::*;
 use engine::*;
+use ordered_float::OrderedFloat;
+use syntax::expression::Expr;
 
 
 pub struct BatchResult<'a> {
     pub group_by: Option<Vec<BoxedVec<'a>>>,
-    pub sort_by: Option<usize>,
+    pub sort_by: Vec<(usize, bool)>,
     pub select: Vec<BoxedVec<'a>>,
     pub aggregators: Vec<Aggregator>,
     pub level: u32,
     pub batch_count: usize,
+    // Type of each output column (grouping keys followed by select), determines how integer values are displayed
+    pub column_types: Vec<BasicType>,
+    // Dictionary of grouping columns that hold the codes of a shared dictionary rather than strings
+    pub dictionaries: Vec<Option<&'a [Option<String>]>>,
 }
 
 impl<'a> BatchResult<'a> {
     pub fn len(&self) -> usize {
         match self.group_by {
-            Some(ref g) => g[0].len(),
+            Some(ref g) => g.get(0).map_or(0, |g| g.len()),
             None => self.select.get(0).map_or(0, |s| s.len()),
         }
     }
+
+    // Replaces partial aggregates (e.g. sum and count for avg) with the final value of each aggregate
+    pub fn finalize_aggregates(mut self, aggregate: &[(Aggregator, Expr)]) -> BatchResult<'a> {
+        let groups = self.len();
+        let mut partials = mem::replace(&mut self.select, Vec::with_capacity(aggregate.len())).into_iter();
+        let mut digests = Vec::new();
+        for (i, &(aggregator, _)) in aggregate.iter().enumerate() {
+            match aggregator {
+                Aggregator::Avg => {
+                    let sums = partials.next().unwrap();
+                    let counts = partials.next().unwrap();
+                    let counts = aggregate_values(&counts);
+                    let averages = if sums.get_type() == EncodingType::F64 {
+                        sums.cast_ref_f64().iter().zip(counts.iter())
+                            .map(|(sum, &count)| OrderedFloat(sum.0 / count as f64))
+                            .collect::<Vec<_>>()
+                    } else {
+                        aggregate_values(&sums).iter().zip(counts.iter())
+                            .map(|(&sum, &count)| OrderedFloat(sum as f64 / count as f64))
+                            .collect::<Vec<_>>()
+                    };
+                    // Groups without any non-null values
+                    let present = counts.iter().map(|&count| count != 0).collect();
+                    self.select.push(TypedVec::nullable(TypedVec::owned(averages), present));
+                }
+                Aggregator::CountDistinct => {
+                    let partial = partials.next().unwrap();
+                    let counts = if partial.get_type() == EncodingType::I64 {
+                        value_sets(partial.cast_ref_i64())
+                            .map(|values| values.len() as i64)
+                            .collect::<Vec<_>>()
+                    } else {
+                        sketches(partial.cast_ref_u8(), groups)
+                            .map(|bitset| bitset.iter().map(|byte| i64::from(byte.count_ones())).sum::<i64>())
+                            .collect::<Vec<_>>()
+                    };
+                    self.select.push(TypedVec::owned(counts));
+                }
+                Aggregator::ApproxCountDistinct => {
+                    let registers = partials.next().unwrap();
+                    let estimates = sketches(registers.cast_ref_u8(), groups)
+                        .map(hyperloglog::estimate)
+                        .collect::<Vec<_>>();
+                    self.select.push(TypedVec::owned(estimates));
+                }
+                Aggregator::Percentile(q) => {
+                    if !aggregator::shares_digest(aggregate, i) {
+                        let partial = partials.next().unwrap();
+                        digests = sketches(partial.cast_ref_u8(), groups).map(TDigest::read).collect();
+                    }
+                    let mut present = BitVec::from_elem(groups, true);
+                    let percentiles = digests.iter()
+                        .enumerate()
+                        .map(|(i, digest)| OrderedFloat(digest.quantile(q.0).unwrap_or_else(|| {
+                            // Groups without any non-null values
+                            present.set(i, false);
+                            0.0
+                        })))
+                        .collect::<Vec<_>>();
+                    self.select.push(TypedVec::nullable(TypedVec::owned(percentiles), present));
+                }
+                Aggregator::Variance | Aggregator::Stddev | Aggregator::Covar | Aggregator::Corr => {
+                    let moments = partials.next().unwrap();
+                    let mut present = BitVec::from_elem(groups, true);
+                    let values = sketches(moments.cast_ref_u8(), groups)
+                        .enumerate()
+                        .map(|(i, moments)| {
+                            let moments = Moments::read(moments);
+                            let value = match aggregator {
+                                Aggregator::Variance => moments.variance(),
+                                Aggregator::Stddev => moments.variance().map(f64::sqrt),
+                                Aggregator::Covar => moments.covariance(),
+                                _ => moments.correlation(),
+                            };
+                            // Undefined for groups with less than two non-null values
+                            present.set(i, value.is_some());
+                            OrderedFloat(value.unwrap_or(0.0))
+                        })
+                        .collect::<Vec<_>>();
+                    self.select.push(TypedVec::nullable(TypedVec::owned(values), present));
+                }
+                Aggregator::ArgMin | Aggregator::ArgMax | Aggregator::First | Aggregator::Last => {
+                    let _keys = partials.next().unwrap();
+                    self.select.push(partials.next().unwrap());
+                }
+                _ => self.select.push(partials.next().unwrap()),
+            }
+        }
+        self.aggregators = aggregate.iter().map(|&(aggregator, _)| aggregator).collect();
+        self
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -38,11 +144,27 @@ struct Premerge {
     right: u16,
 }
 
-pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usize) -> BatchResult<'a> {
+pub fn combine<'a>(mut batch1: BatchResult<'a>, mut batch2: BatchResult<'a>, limit: usize) -> Result<BatchResult<'a>, QueryError> {
+    widen_to_common_type(&mut batch1.select, &mut batch2.select);
+    if let (Some(g1), Some(g2)) = (batch1.group_by.as_mut(), batch2.group_by.as_mut()) {
+        widen_to_common_type(g1, g2);
+    }
     match (batch1.group_by, batch2.group_by) {
         // Aggregation query
         (Some(g1), Some(g2)) => {
-            let (group_by_cols, ops) = if g1.len() == 1 {
+            let single_column = g1.len() == 1 && match (g1[0].get_type(), g2[0].get_type()) {
+                (EncodingType::Str, EncodingType::Str) |
+                (EncodingType::U8, EncodingType::U8) |
+                (EncodingType::I64, EncodingType::I64) => true,
+                _ => false,
+            };
+            let nullable = g1.iter().chain(g2.iter()).any(|g| g.present().is_some());
+            let single_column = single_column && !nullable;
+            let two_columns = !nullable && g1.len() == 2 && (0..2).all(|i| match (g1[i].get_type(), g2[i].get_type()) {
+                (EncodingType::Str, EncodingType::Str) | (EncodingType::I64, EncodingType::I64) => true,
+                _ => false,
+            });
+            let (group_by_cols, ops) = if single_column {
                 // TODO(clemens): other types, val coercion
                 let (merged_grouping, ops) = match (g1[0].get_type(), g2[0].get_type()) {
                     (EncodingType::Str, EncodingType::Str) =>
@@ -54,7 +176,7 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
                     (t1, t2) => unimplemented!("{:?}, {:?}", t1, t2),
                 };
                 (vec![merged_grouping], ops)
-            } else {
+            } else if two_columns {
                 let initial_partitioning = match (g1[0].get_type(), g2[0].get_type()) {
                     (EncodingType::Str, EncodingType::Str) =>
                         partition::<&str>(g1[0].as_ref(), g2[0].as_ref(), usize::MAX),
@@ -85,96 +207,173 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
                 group_by_cols.push(merged_grouping);
 
                 (group_by_cols, ops)
+            } else {
+                merge_deduplicate_rows(&g1, &g2)?
             };
 
             let mut aggregates = Vec::with_capacity(batch1.aggregators.len());
-            for (i, aggregator) in batch1.aggregators.iter().enumerate() {
-                let merged = merge_aggregate(
-                    batch1.select[i].cast_ref_i64(),
-                    batch2.select[i].cast_ref_i64(),
-                    &ops, *aggregator);
+            let mut i = 0;
+            while i < batch1.aggregators.len() {
+                let aggregator = &batch1.aggregators[i];
+                let (left, right) = (&batch1.select[i], &batch2.select[i]);
+                if aggregator.selects_row() {
+                    let (keys, values) = merge_selected_rows(
+                        (left, &batch1.select[i + 1]),
+                        (right, &batch2.select[i + 1]),
+                        &ops,
+                        aggregator.selects_max())?;
+                    aggregates.push(keys);
+                    aggregates.push(values);
+                    i += 2;
+                    continue;
+                }
+                let merged = if *aggregator == Aggregator::CountDistinct && left.get_type() == EncodingType::I64 {
+                    merge_value_sets(left.cast_ref_i64(), right.cast_ref_i64(), &ops)
+                } else if aggregator.is_sketch() {
+                    merge_sketches(left.cast_ref_u8(), right.cast_ref_u8(), &ops, |a, e| aggregator.merge_sketch(a, e))
+                } else if left.get_type() == EncodingType::F64 {
+                    merge_aggregate(left.cast_ref_f64(), right.cast_ref_f64(), (left.present(), right.present()), &ops,
+                                    |a, e| aggregator.combine_f64(a, e))
+                } else {
+                    merge_aggregate(&aggregate_values(left), &aggregate_values(right), (left.present(), right.present()), &ops,
+                                    |a, e| aggregator.combine_i64(a, e))
+                };
                 aggregates.push(merged);
+                i += 1;
             }
-            BatchResult {
+            Ok(BatchResult {
                 group_by: Some(group_by_cols),
-                sort_by: None,
+                sort_by: Vec::with_capacity(0),
                 select: aggregates,
                 aggregators: batch1.aggregators,
                 level: batch1.level + 1,
                 batch_count: batch1.batch_count + batch2.batch_count,
-            }
+                column_types: batch1.column_types,
+                dictionaries: batch1.dictionaries,
+            })
         }
         // No aggregation
         (None, None) => {
-            match batch1.sort_by {
+            if !batch1.sort_by.is_empty() {
                 // Sort query
-                Some(index) => {
-                    let (merged_sort_col, ops) = {
-                        let s1 = &batch1.select[index];
-                        let s2 = &batch2.select[index];
-                        match (s1.get_type(), s2.get_type()) {
-                            (EncodingType::Str, EncodingType::Str) =>
-                                merge_sort(s1.cast_ref_str(), s2.cast_ref_str(), limit),
-                            (EncodingType::I64, EncodingType::I64) =>
-                                merge_sort(s1.cast_ref_i64(), s2.cast_ref_i64(), limit),
-                            (t1, t2) => unimplemented!("{:?}, {:?}", t1, t2),
-                        }
+                let ops = merge_sort(&batch1.select, &batch2.select, &batch1.sort_by, limit)?;
+                let mut result = Vec::with_capacity(batch1.select.len());
+                for (col1, col2) in batch1.select.into_iter().zip(batch2.select) {
+                    let merged = match (col1.get_type(), col2.get_type()) {
+                        (EncodingType::Str, EncodingType::Str) =>
+                            merge(col1.cast_ref_str(), col2.cast_ref_str(), &ops),
+                        (EncodingType::I64, EncodingType::I64) =>
+                            merge(col1.cast_ref_i64(), col2.cast_ref_i64(), &ops),
+                        (EncodingType::F64, EncodingType::F64) =>
+                            merge(col1.cast_ref_f64(), col2.cast_ref_f64(), &ops),
+                        (t1, t2) => bail!(QueryError::NotImplemented, "merging sorted columns of type {:?} and {:?}", t1, t2),
                     };
-
-                    let mut result = Vec::with_capacity(batch1.select.len());
-                    for (i, (col1, col2)) in batch1.select.into_iter().zip(batch2.select).enumerate() {
-                        if i == index {
-                            result.push(TypedVec::empty(0));
-                        } else {
-                            let merged = match (col1.get_type(), col2.get_type()) {
-                                (EncodingType::Str, EncodingType::Str) =>
-                                    merge(col1.cast_ref_str(), col2.cast_ref_str(), &ops),
-                                (EncodingType::I64, EncodingType::I64) =>
-                                    merge(col1.cast_ref_i64(), col2.cast_ref_i64(), &ops),
-                                (t1, t2) => unimplemented!("{:?}, {:?}", t1, t2),
-                            };
-                            result.push(merged);
+                    let merged = match (col1.present(), col2.present()) {
+                        (None, None) => merged,
+                        (p1, p2) => {
+                            let mut present = BitVec::with_capacity(ops.len());
+                            let (mut i, mut j) = (0, 0);
+                            for &take_left in &ops {
+                                if take_left {
+                                    present.push(p1.map_or(true, |p| p[i]));
+                                    i += 1;
+                                } else {
+                                    present.push(p2.map_or(true, |p| p[j]));
+                                    j += 1;
+                                }
+                            }
+                            TypedVec::nullable(merged, present)
                         }
-                    }
-                    result[index] = merged_sort_col;
-
-                    BatchResult {
-                        group_by: None,
-                        sort_by: Some(index),
-                        select: result,
-                        aggregators: Vec::new(),
-                        level: batch1.level + 1,
-                        batch_count: batch1.batch_count + batch2.batch_count,
-                    }
+                    };
+                    result.push(merged);
                 }
+
+                Ok(BatchResult {
+                    group_by: None,
+                    sort_by: batch1.sort_by,
+                    select: result,
+                    aggregators: Vec::new(),
+                    level: batch1.level + 1,
+                    batch_count: batch1.batch_count + batch2.batch_count,
+                    column_types: batch1.column_types,
+                    dictionaries: batch1.dictionaries,
+                })
+            } else {
                 // Select query
-                None => {
-                    let mut result = Vec::with_capacity(batch1.select.len());
-                    for (mut col1, col2) in batch1.select.into_iter().zip(batch2.select) {
-                        let count = if col1.len() >= limit { 0 } else {
-                            min(col2.len(), limit - col1.len())
-                        };
-                        if let Some(newcol) = col1.extend(col2, count) {
-                            result.push(newcol)
-                        } else {
-                            result.push(col1)
-                        }
+                let mut result = Vec::with_capacity(batch1.select.len());
+                for (mut col1, col2) in batch1.select.into_iter().zip(batch2.select) {
+                    if col2.present().is_some() {
+                        col1 = TypedVec::make_nullable(col1);
                     }
-                    BatchResult {
-                        group_by: None,
-                        sort_by: None,
-                        select: result,
-                        aggregators: Vec::new(),
-                        level: batch1.level + 1,
-                        batch_count: batch1.batch_count + batch2.batch_count,
+                    let count = if col1.len() >= limit { 0 } else {
+                        min(col2.len(), limit - col1.len())
+                    };
+                    if let Some(newcol) = col1.extend(col2, count) {
+                        result.push(newcol)
+                    } else {
+                        result.push(col1)
                     }
                 }
+                Ok(BatchResult {
+                    group_by: None,
+                    sort_by: Vec::with_capacity(0),
+                    select: result,
+                    aggregators: Vec::new(),
+                    level: batch1.level + 1,
+                    batch_count: batch1.batch_count + batch2.batch_count,
+                    column_types: batch1.column_types,
+                    dictionaries: batch1.dictionaries,
+                })
             }
         }
         _ => panic!("Trying to merge incompatible batch results"),
     }
 }
 
+// Integer columns are converted to floats when merged with a float column, e.g. when some batches of a float column
+// contain only whole numbers. Columns that are entirely null take on the type of the other column.
+fn widen_to_common_type<'a>(left: &mut [BoxedVec<'a>], right: &mut [BoxedVec<'a>]) {
+    for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+        let (t1, t2) = (l.get_type(), r.get_type());
+        if t1 == t2 { continue; }
+        // Widening keeps the values of null aggregates, which hold the identity of the aggregator
+        if t1 == EncodingType::I64 && t2 == EncodingType::F64 {
+            *l = widen_to_f64(l.as_ref());
+        } else if t1 == EncodingType::F64 && t2 == EncodingType::I64 {
+            *r = widen_to_f64(r.as_ref());
+        } else if is_all_null(l.as_ref()) {
+            if let Some(nulls) = nulls(t2, l.len()) { *l = nulls; }
+        } else if is_all_null(r.as_ref()) {
+            if let Some(nulls) = nulls(t1, r.len()) { *r = nulls; }
+        }
+    }
+}
+
+fn is_all_null<'a>(col: &TypedVec<'a>) -> bool {
+    col.present().map_or(false, |present| present.none())
+}
+
+fn nulls<'a>(t: EncodingType, len: usize) -> Option<BoxedVec<'a>> {
+    let data = match t {
+        EncodingType::Str => TypedVec::owned(vec![""; len]),
+        EncodingType::U8 => TypedVec::owned(vec![0u8; len]),
+        EncodingType::U16 => TypedVec::owned(vec![0u16; len]),
+        EncodingType::U32 => TypedVec::owned(vec![0u32; len]),
+        EncodingType::I64 => TypedVec::owned(vec![0i64; len]),
+        EncodingType::F64 => TypedVec::owned(vec![OrderedFloat(0.0); len]),
+        _ => return None,
+    };
+    Some(TypedVec::nullable(data, BitVec::from_elem(len, false)))
+}
+
+fn widen_to_f64<'a>(col: &TypedVec<'a>) -> BoxedVec<'a> {
+    let floats = col.cast_ref_i64().iter().map(|&x| OrderedFloat(x as f64)).collect::<Vec<_>>();
+    match col.present() {
+        Some(present) => TypedVec::nullable(TypedVec::owned(floats), present.clone()),
+        None => TypedVec::owned(floats),
+    }
+}
+
 fn merge_deduplicate<'a, T: VecType<T> + 'a>(left: &[T], right: &[T]) -> (BoxedVec<'a>, Vec<MergeOp>) {
     // TODO(clemens): figure out maths for precise estimate + variance derived from how much grouping reduced cardinality
     let output_len_estimate = max(left.len(), right.len()) + min(left.len(), right.len()) / 2;
@@ -214,6 +413,68 @@ fn merge_deduplicate<'a, T: VecType<T> + 'a>(left: &[T], right: &[T]) -> (BoxedV
     (TypedVec::owned(result), ops)
 }
 
+// Merges groups consisting of any number of columns of any type
+fn merge_deduplicate_rows<'a>(left: &[BoxedVec<'a>], right: &[BoxedVec<'a>]) -> Result<(Vec<BoxedVec<'a>>, Vec<MergeOp>), QueryError> {
+    for (l, r) in left.iter().zip(right.iter()) {
+        match (l.get_type(), r.get_type()) {
+            (EncodingType::Str, EncodingType::Str) | (EncodingType::U8, EncodingType::U8) |
+            (EncodingType::U16, EncodingType::U16) | (EncodingType::U32, EncodingType::U32) |
+            (EncodingType::I64, EncodingType::I64) | (EncodingType::F64, EncodingType::F64) => {}
+            (t1, t2) => bail!(QueryError::NotImplemented, "merging grouping columns of type {:?} and {:?}", t1, t2),
+        }
+    }
+    let columns = (0..left.len()).map(|i| (i, false)).collect::<Vec<_>>();
+    let left_len = left[0].len();
+    let right_len = right[0].len();
+    let mut ops = Vec::<MergeOp>::with_capacity(left_len + right_len);
+
+    // Last group that was taken from left (true) or right (false)
+    let mut last = None;
+    let mut i = 0;
+    let mut j = 0;
+    while i < left_len && j < right_len {
+        let duplicate = match last {
+            Some((true, l)) => compare_rows(left, l, right, j, &columns)? == Ordering::Equal,
+            Some((false, r)) => compare_rows(right, r, right, j, &columns)? == Ordering::Equal,
+            None => false,
+        };
+        if duplicate {
+            ops.push(MergeOp::MergeRight);
+            j += 1;
+        } else if compare_rows(left, i, right, j, &columns)? != Ordering::Greater {
+            ops.push(MergeOp::TakeLeft);
+            last = Some((true, i));
+            i += 1;
+        } else {
+            ops.push(MergeOp::TakeRight);
+            last = Some((false, j));
+            j += 1;
+        }
+    }
+
+    for _ in i..left_len {
+        ops.push(MergeOp::TakeLeft);
+    }
+    if i == left_len && i > 0 && j < right_len && compare_rows(left, i - 1, right, j, &columns)? == Ordering::Equal {
+        ops.push(MergeOp::MergeRight);
+        j += 1;
+    }
+    for _ in j..right_len {
+        ops.push(MergeOp::TakeRight);
+    }
+
+    let merged = left.iter().zip(right.iter()).map(|(l, r)| match (l.get_type(), r.get_type()) {
+        (EncodingType::Str, EncodingType::Str) => merge_drop::<&str>(l.as_ref(), r.as_ref(), &ops),
+        (EncodingType::U8, EncodingType::U8) => merge_drop::<u8>(l.as_ref(), r.as_ref(), &ops),
+        (EncodingType::U16, EncodingType::U16) => merge_drop::<u16>(l.as_ref(), r.as_ref(), &ops),
+        (EncodingType::U32, EncodingType::U32) => merge_drop::<u32>(l.as_ref(), r.as_ref(), &ops),
+        (EncodingType::I64, EncodingType::I64) => merge_drop::<i64>(l.as_ref(), r.as_ref(), &ops),
+        (EncodingType::F64, EncodingType::F64) => merge_drop::<OrderedFloat<f64>>(l.as_ref(), r.as_ref(), &ops),
+        (t1, t2) => unreachable!("{:?}, {:?}", t1, t2),
+    }).collect();
+    Ok((merged, ops))
+}
+
 fn merge_deduplicate_partitioned<'a, T: VecType<T> + 'a>(partitioning: &[Premerge],
                                                          left: &TypedVec<'a>,
                                                          right: &TypedVec<'a>) -> (BoxedVec<'a>, Vec<MergeOp>) {
@@ -295,62 +556,267 @@ fn partition<'a, T: VecType<T> + 'a>(left: &TypedVec<'a>, right: &TypedVec<'a>,
     result
 }
 
-// TODO(clemens): implement descending ordering
-fn merge_sort<'a, T: VecType<T> + 'a>(left: &[T], right: &[T], limit: usize) -> (BoxedVec<'a>, Vec<bool>) {
-    let mut result = Vec::with_capacity(left.len() + right.len());
-    let mut ops = Vec::<bool>::with_capacity(left.len() + right.len());
+fn merge_sort<'a>(left: &[BoxedVec<'a>], right: &[BoxedVec<'a>], sort_by: &[(usize, bool)], limit: usize) -> Result<Vec<bool>, QueryError> {
+    let left_len = left[sort_by[0].0].len();
+    let right_len = right[sort_by[0].0].len();
+    let mut ops = Vec::<bool>::with_capacity(left_len + right_len);
 
     let mut i = 0;
     let mut j = 0;
-    while i < left.len() && j < right.len() && i + j < limit {
-        if left[i] <= right[j] {
-            result.push(left[i]);
+    while i < left_len && j < right_len && i + j < limit {
+        if compare_rows(left, i, right, j, sort_by)? != Ordering::Greater {
             ops.push(true);
             i += 1;
         } else {
-            result.push(right[j]);
             ops.push(false);
             j += 1;
         }
     }
 
-    for x in left[i..min(left.len(), limit - j)].iter() {
-        result.push(*x);
+    for _ in i..min(left_len, limit - j) {
         ops.push(true);
     }
-    for x in right[j..min(right.len(), limit - i)].iter() {
-        result.push(*x);
+    for _ in j..min(right_len, limit - i) {
         ops.push(false);
     }
+    Ok(ops)
+}
 
-    (TypedVec::owned(result), ops)
+fn compare_rows<'a>(left: &[BoxedVec<'a>], i: usize, right: &[BoxedVec<'a>], j: usize, sort_by: &[(usize, bool)]) -> Result<Ordering, QueryError> {
+    for &(index, desc) in sort_by {
+        let (l, r) = (&left[index], &right[index]);
+        // Null values are greater than all other values
+        let ordering = match (l.present().map_or(true, |p| p[i]), r.present().map_or(true, |p| p[j])) {
+            (false, false) => Ordering::Equal,
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (true, true) => match (l.get_type(), r.get_type()) {
+                (EncodingType::Str, EncodingType::Str) => l.cast_ref_str()[i].cmp(&r.cast_ref_str()[j]),
+                (EncodingType::U8, EncodingType::U8) => l.cast_ref_u8()[i].cmp(&r.cast_ref_u8()[j]),
+                (EncodingType::U16, EncodingType::U16) => l.cast_ref_u16()[i].cmp(&r.cast_ref_u16()[j]),
+                (EncodingType::U32, EncodingType::U32) => l.cast_ref_u32()[i].cmp(&r.cast_ref_u32()[j]),
+                (EncodingType::I64, EncodingType::I64) => l.cast_ref_i64()[i].cmp(&r.cast_ref_i64()[j]),
+                (EncodingType::F64, EncodingType::F64) => l.cast_ref_f64()[i].cmp(&r.cast_ref_f64()[j]),
+                (t1, t2) => bail!(QueryError::NotImplemented, "comparing rows of type {:?} and {:?}", t1, t2),
+            }
+        };
+        if ordering != Ordering::Equal {
+            return Ok(if desc { ordering.reverse() } else { ordering });
+        }
+    }
+    Ok(Ordering::Equal)
 }
 
-fn merge_aggregate<'a>(left: &[i64], right: &[i64], ops: &[MergeOp], aggregator: Aggregator) -> BoxedVec<'a> {
+// Counts are computed as u32 and have to be widened before they can be merged
+fn aggregate_values<'a, 'b>(aggregate: &'b BoxedVec<'a>) -> Cow<'b, [i64]> {
+    match aggregate.get_type() {
+        EncodingType::I64 => Cow::Borrowed(aggregate.cast_ref_i64()),
+        EncodingType::U32 => Cow::Owned(aggregate.cast_ref_u32().iter().map(|&x| i64::from(x)).collect()),
+        t => panic!("unsupported aggregate type {:?}", t),
+    }
+}
+
+// Aggregates of groups without any non-null values are null and hold the identity of `combine`
+fn merge_aggregate<'a, T, F>(left: &[T], right: &[T], present: (Option<&BitVec>, Option<&BitVec>), ops: &[MergeOp], combine: F) -> BoxedVec<'a>
+    where T: VecType<T> + 'a, F: Fn(T, T) -> T {
+    let (left_present, right_present) = present;
+    let nullable = left_present.is_some() || right_present.is_some();
     let mut result = Vec::with_capacity(ops.len());
+    let mut present = BitVec::with_capacity(if nullable { ops.len() } else { 0 });
     let mut i = 0;
     let mut j = 0;
     for op in ops {
         match *op {
             MergeOp::TakeLeft => {
                 result.push(left[i]);
+                if nullable { present.push(left_present.map_or(true, |p| p[i])) }
                 i += 1;
             }
             MergeOp::TakeRight => {
                 result.push(right[j]);
+                if nullable { present.push(right_present.map_or(true, |p| p[j])) }
                 j += 1;
             }
             MergeOp::MergeRight => {
                 // TODO(clemens): make inlining of aggregator operation possible
                 let last = result.len() - 1;
-                result[last] = aggregator.combine_i64(result[last], right[j]);
+                result[last] = combine(result[last], right[j]);
+                if nullable && right_present.map_or(true, |p| p[j]) { present.set(last, true) }
+                j += 1;
+            }
+        }
+    }
+    if nullable {
+        TypedVec::nullable(TypedVec::owned(result), present)
+    } else {
+        TypedVec::owned(result)
+    }
+}
+
+// Selects the row with the smaller or larger key for merged groups, groups without selected row have null keys
+fn merge_selected_rows<'a>(left: (&BoxedVec<'a>, &BoxedVec<'a>),
+                           right: (&BoxedVec<'a>, &BoxedVec<'a>),
+                           ops: &[MergeOp],
+                           select_max: bool) -> Result<(BoxedVec<'a>, BoxedVec<'a>), QueryError> {
+    let (left_keys, right_keys) = (slice::from_ref(left.0), slice::from_ref(right.0));
+    let is_present = |keys: &[BoxedVec<'a>], i: usize| keys[0].present().map_or(true, |p| p[i]);
+    let preferred = if select_max { Ordering::Greater } else { Ordering::Less };
+    // Side (true for left) and index of the row selected for each group
+    let mut rows = Vec::with_capacity(ops.len());
+    let mut i = 0;
+    let mut j = 0;
+    for op in ops {
+        match *op {
+            MergeOp::TakeLeft => {
+                rows.push((true, i));
+                i += 1;
+            }
+            MergeOp::TakeRight => {
+                rows.push((false, j));
+                j += 1;
+            }
+            MergeOp::MergeRight => {
+                let last = rows.len() - 1;
+                let (selected_left, selected) = rows[last];
+                let selected_keys = if selected_left { left_keys } else { right_keys };
+                if is_present(right_keys, j) && (!is_present(selected_keys, selected) ||
+                    compare_rows(right_keys, j, selected_keys, selected, &[(0, false)])? == preferred) {
+                    rows[last] = (false, j);
+                }
                 j += 1;
             }
         }
     }
+    Ok((select_rows(left.0.as_ref(), right.0.as_ref(), &rows)?, select_rows(left.1.as_ref(), right.1.as_ref(), &rows)?))
+}
+
+fn select_rows<'a>(left: &TypedVec<'a>, right: &TypedVec<'a>, rows: &[(bool, usize)]) -> Result<BoxedVec<'a>, QueryError> {
+    Ok(match (left.get_type(), right.get_type()) {
+        (EncodingType::Str, EncodingType::Str) => select_rows_typed::<&str>(left, right, rows),
+        (EncodingType::I64, EncodingType::I64) => select_rows_typed::<i64>(left, right, rows),
+        (EncodingType::F64, EncodingType::F64) => select_rows_typed::<OrderedFloat<f64>>(left, right, rows),
+        (t1, t2) => bail!(QueryError::NotImplemented, "merging selected rows of type {:?} and {:?}", t1, t2),
+    })
+}
+
+fn select_rows_typed<'a, T: VecType<T> + 'a>(left: &TypedVec<'a>, right: &TypedVec<'a>, rows: &[(bool, usize)]) -> BoxedVec<'a> {
+    let (left_present, right_present) = (left.present(), right.present());
+    let (left, right) = (T::unwrap(left), T::unwrap(right));
+    let mut result = Vec::with_capacity(rows.len());
+    let mut present = BitVec::with_capacity(rows.len());
+    for &(from_left, i) in rows {
+        if from_left {
+            result.push(left[i]);
+            present.push(left_present.map_or(true, |p| p[i]));
+        } else {
+            result.push(right[i]);
+            present.push(right_present.map_or(true, |p| p[i]));
+        }
+    }
+    TypedVec::nullable(TypedVec::owned(result), present)
+}
+
+// Sketches consist of a fixed number of bytes per group
+fn merge_sketches<'a, F>(left: &[u8], right: &[u8], ops: &[MergeOp], combine: F) -> BoxedVec<'a>
+    where F: Fn(&mut [u8], &[u8]) {
+    let left_groups = ops.iter().filter(|&op| *op == MergeOp::TakeLeft).count();
+    let right_groups = ops.len() - left_groups;
+    let stride = if left_groups > 0 { left.len() / left_groups } else { right.len() / max(right_groups, 1) };
+    let mut result = Vec::with_capacity(left.len() + right.len());
+    let mut i = 0;
+    let mut j = 0;
+    for op in ops {
+        match *op {
+            MergeOp::TakeLeft => {
+                result.extend_from_slice(&left[i..i + stride]);
+                i += stride;
+            }
+            MergeOp::TakeRight => {
+                result.extend_from_slice(&right[j..j + stride]);
+                j += stride;
+            }
+            MergeOp::MergeRight => {
+                let last = result.len() - stride;
+                combine(&mut result[last..], &right[j..j + stride]);
+                j += stride;
+            }
+        }
+    }
+    TypedVec::owned(result)
+}
+
+fn sketches(sketches: &[u8], groups: usize) -> impl Iterator<Item=&[u8]> {
+    let stride = if groups == 0 { 1 } else { sketches.len() / groups };
+    sketches.chunks(stride)
+}
+
+// Sets of values are stored as the number of values of each group followed by its sorted values
+fn merge_value_sets<'a>(left: &[i64], right: &[i64], ops: &[MergeOp]) -> BoxedVec<'a> {
+    let mut result = Vec::with_capacity(left.len() + right.len());
+    let mut left = value_sets(left);
+    let mut right = value_sets(right);
+    let mut last = 0;
+    for op in ops {
+        let values = match *op {
+            MergeOp::TakeLeft => Cow::Borrowed(left.next().unwrap()),
+            MergeOp::TakeRight => Cow::Borrowed(right.next().unwrap()),
+            MergeOp::MergeRight => {
+                let merged = union(&result[last + 1..], right.next().unwrap());
+                result.truncate(last);
+                Cow::Owned(merged)
+            }
+        };
+        last = result.len();
+        result.push(values.len() as i64);
+        result.extend_from_slice(&values);
+    }
     TypedVec::owned(result)
 }
 
+fn value_sets(sets: &[i64]) -> ValueSets {
+    ValueSets { sets }
+}
+
+struct ValueSets<'a> {
+    sets: &'a [i64],
+}
+
+impl<'a> Iterator for ValueSets<'a> {
+    type Item = &'a [i64];
+
+    fn next(&mut self) -> Option<&'a [i64]> {
+        let (&len, rest) = self.sets.split_first()?;
+        let (values, rest) = rest.split_at(len as usize);
+        self.sets = rest;
+        Some(values)
+    }
+}
+
+fn union(left: &[i64], right: &[i64]) -> Vec<i64> {
+    let mut result = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        match left[i].cmp(&right[j]) {
+            Ordering::Less => {
+                result.push(left[i]);
+                i += 1;
+            }
+            Ordering::Greater => {
+                result.push(right[j]);
+                j += 1;
+            }
+            Ordering::Equal => {
+                result.push(left[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result.extend_from_slice(&left[i..]);
+    result.extend_from_slice(&right[j..]);
+    result
+}
+
 fn merge<'a, T: 'a>(left: &[T], right: &[T], ops: &[bool]) -> BoxedVec<'a>
     where T: VecType<T> {
     let mut result = Vec::with_capacity(ops.len());
@@ -369,20 +835,25 @@ fn merge<'a, T: 'a>(left: &[T], right: &[T], ops: &[bool]) -> BoxedVec<'a>
 }
 
 fn merge_drop<'a, T: VecType<T> + 'a>(left: &TypedVec<'a>, right: &TypedVec<'a>, ops: &[MergeOp]) -> BoxedVec<'a> {
+    let (left_present, right_present) = (left.present(), right.present());
+    let nullable = left_present.is_some() || right_present.is_some();
     let left = T::unwrap(left);
     let right = T::unwrap(right);
     // TODO(clemens): this is an overestimate
     let mut result = Vec::with_capacity(ops.len());
+    let mut present = BitVec::with_capacity(if nullable { ops.len() } else { 0 });
     let mut i = 0;
     let mut j = 0;
     for op in ops {
         match *op {
             MergeOp::TakeLeft => {
                 result.push(left[i]);
+                if nullable { present.push(left_present.map_or(true, |p| p[i])) }
                 i += 1;
             }
             MergeOp::TakeRight => {
                 result.push(right[j]);
+                if nullable { present.push(right_present.map_or(true, |p| p[j])) }
                 j += 1;
             }
             MergeOp::MergeRight => {
@@ -390,7 +861,11 @@ fn merge_drop<'a, T: VecType<T> + 'a>(left: &TypedVec<'a>, right: &TypedVec<'a>,
             }
         }
     }
-    TypedVec::owned(result)
+    if nullable {
+        TypedVec::nullable(TypedVec::owned(result), present)
+    } else {
+        TypedVec::owned(result)
+    }
 }
 
 #[cfg(test)]
@@ -429,4 +904,40 @@ mod tests {
             TakeRight,
         ]);
     }
+
+    #[test]
+    fn test_merge_sort_int_and_float_batches() {
+        let batch = |values: BoxedVec<'static>| BatchResult {
+            group_by: None,
+            sort_by: vec![(0, false)],
+            select: vec![values],
+            aggregators: Vec::new(),
+            level: 0,
+            batch_count: 1,
+            column_types: vec![BasicType::Float],
+            dictionaries: Vec::new(),
+        };
+        let ints = batch(TypedVec::owned(vec![1i64, 3]));
+        let floats = batch(TypedVec::owned(vec![OrderedFloat(2.5), OrderedFloat(4.0)]));
+        let merged = combine(ints, floats, 10).unwrap();
+        assert_eq!(merged.select[0].cast_ref_f64(),
+                   &[OrderedFloat(1.0), OrderedFloat(2.5), OrderedFloat(3.0), OrderedFloat(4.0)]);
+    }
+
+    #[test]
+    fn test_merge_sort_mismatched_types() {
+        let batch = |values: BoxedVec<'static>| BatchResult {
+            group_by: None,
+            sort_by: vec![(0, false)],
+            select: vec![values],
+            aggregators: Vec::new(),
+            level: 0,
+            batch_count: 1,
+            column_types: vec![BasicType::String],
+            dictionaries: Vec::new(),
+        };
+        let strings = batch(TypedVec::owned(vec!["a", "c"]));
+        let ints = batch(TypedVec::owned(vec![1i64, 3]));
+        assert!(combine(strings, ints, 10).is_err());
+    }
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use engine::vector_op::vector_operator::BufferRef;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum Filter {
     None,
     BitVec(BufferRef),
@@ -9,4 +9,13 @@ pub enum Filter {
 
 impl Default for Filter {
     fn default() -> Filter { Filter::None }
-}
\ No newline at end of file
+}
+
+impl Filter {
+    pub fn is_none(&self) -> bool {
+        match *self {
+            Filter::None => true,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::f64;
+
+// HyperLogLog sketches estimate the number of distinct values from the maximum number of leading zeros observed in the
+// hashes of values, tracked separately in each of `REGISTERS` buckets. Sketches are merged by taking the maximum of
+// each register. Standard error is 1.04 / sqrt(REGISTERS), about 3%.
+
+pub const PRECISION: u32 = 10;
+pub const REGISTERS: usize = 1 << PRECISION;
+
+pub fn insert(registers: &mut [u8], hash: u64) {
+    let index = (hash >> (64 - PRECISION)) as usize;
+    // Sentinel bit bounds the rank if all remaining bits are zero
+    let rest = hash << PRECISION | 1 << (PRECISION - 1);
+    let rank = rest.leading_zeros() as u8 + 1;
+    if rank > registers[index] {
+        registers[index] = rank;
+    }
+}
+
+pub fn estimate(registers: &[u8]) -> i64 {
+    let m = registers.len() as f64;
+    let alpha = 0.7213 / (1.0 + 1.079 / m);
+    let sum = registers.iter().map(|&r| 2f64.powi(-i32::from(r))).sum::<f64>();
+    let raw = alpha * m * m / sum;
+    let zeros = registers.iter().filter(|&&r| r == 0).count();
+    // Linear counting is much more accurate for small cardinalities
+    let estimate = if raw <= 2.5 * m && zeros > 0 {
+        m * (m / zeros as f64).ln()
+    } else {
+        raw
+    };
+    estimate.round() as i64
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::Hasher;
+    use seahash::SeaHasher;
+
+    fn hash(i: u64) -> u64 {
+        let mut hasher = SeaHasher::default();
+        hasher.write_u64(i);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut left = vec![0u8; REGISTERS];
+        let mut right = vec![0u8; REGISTERS];
+        for i in 0..50_000 {
+            insert(&mut left, hash(i));
+            insert(&mut right, hash(i + 25_000));
+        }
+        let left_estimate = estimate(&left);
+        assert!((left_estimate - 50_000).abs() < 5_000, "{}", left_estimate);
+
+        let merged = left.iter().zip(right.iter()).map(|(&l, &r)| if l > r { l } else { r }).collect::<Vec<_>>();
+        let merged_estimate = estimate(&merged);
+        assert!((merged_estimate - 75_000).abs() < 7_500, "{}", merged_estimate);
+
+        let mut small = vec![0u8; REGISTERS];
+        for i in 0..5 {
+            insert(&mut small, hash(i));
+            insert(&mut small, hash(i));
+        }
+        assert_eq!(estimate(&small), 5);
+    }
+}
@@ -1,11 +1,15 @@
 mod aggregation_operator;
 mod batch_merging;
+mod hyperloglog;
+mod moments;
+mod tdigest;
 mod query_plan;
 pub mod vector_op;
 pub mod aggregator;
 pub mod filter;
 pub mod query;
 pub mod query_task;
+pub mod time_function;
 pub mod typed_vec;
 pub mod types;
 
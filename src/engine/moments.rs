@@ -0,0 +1,112 @@
+use engine::tdigest::{read_f64, write_f64};
+
+// Count, means and sums of squared deviations from the mean of pairs of values, from which variance, covariance and
+// correlation are derived. Updated incrementally (Welford) and merged pairwise (Chan et al.), which avoids the
+// cancellation errors of computing sums of squares directly.
+
+// Count, mean of x and y, co-moment and M2 of x and y
+pub const SKETCH_BYTES: usize = 48;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Moments {
+    count: f64,
+    mean_x: f64,
+    mean_y: f64,
+    comoment: f64,
+    m2_x: f64,
+    m2_y: f64,
+}
+
+impl Moments {
+    pub fn push(&mut self, x: f64, y: f64) {
+        self.count += 1.0;
+        let dx = x - self.mean_x;
+        self.mean_x += dx / self.count;
+        let dy = y - self.mean_y;
+        self.mean_y += dy / self.count;
+        self.comoment += dx * (y - self.mean_y);
+        self.m2_x += dx * (x - self.mean_x);
+        self.m2_y += dy * (y - self.mean_y);
+    }
+
+    pub fn merge(&self, other: &Moments) -> Moments {
+        if self.count == 0.0 { return *other; }
+        if other.count == 0.0 { return *self; }
+        let count = self.count + other.count;
+        let dx = other.mean_x - self.mean_x;
+        let dy = other.mean_y - self.mean_y;
+        let weight = self.count * other.count / count;
+        Moments {
+            count,
+            mean_x: self.mean_x + dx * other.count / count,
+            mean_y: self.mean_y + dy * other.count / count,
+            comoment: self.comoment + other.comoment + dx * dy * weight,
+            m2_x: self.m2_x + other.m2_x + dx * dx * weight,
+            m2_y: self.m2_y + other.m2_y + dy * dy * weight,
+        }
+    }
+
+    // Sample variance of x
+    pub fn variance(&self) -> Option<f64> {
+        if self.count < 2.0 { None } else { Some(self.m2_x / (self.count - 1.0)) }
+    }
+
+    // Sample covariance
+    pub fn covariance(&self) -> Option<f64> {
+        if self.count < 2.0 { None } else { Some(self.comoment / (self.count - 1.0)) }
+    }
+
+    // Pearson correlation coefficient, undefined if either x or y is constant
+    pub fn correlation(&self) -> Option<f64> {
+        if self.count < 2.0 || self.m2_x == 0.0 || self.m2_y == 0.0 {
+            None
+        } else {
+            Some(self.comoment / (self.m2_x * self.m2_y).sqrt())
+        }
+    }
+
+    pub fn read(bytes: &[u8]) -> Moments {
+        Moments {
+            count: read_f64(bytes, 0),
+            mean_x: read_f64(bytes, 8),
+            mean_y: read_f64(bytes, 16),
+            comoment: read_f64(bytes, 24),
+            m2_x: read_f64(bytes, 32),
+            m2_y: read_f64(bytes, 40),
+        }
+    }
+
+    pub fn write(&self, bytes: &mut [u8]) {
+        write_f64(bytes, 0, self.count);
+        write_f64(bytes, 8, self.mean_x);
+        write_f64(bytes, 16, self.mean_y);
+        write_f64(bytes, 24, self.comoment);
+        write_f64(bytes, 32, self.m2_x);
+        write_f64(bytes, 40, self.m2_y);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let xs = [1e9 + 4.0, 1e9 + 7.0, 1e9 + 13.0, 1e9 + 16.0];
+        let ys = [2.0, 1.0, 4.0, 3.0];
+        let mut all = Moments::default();
+        let mut left = Moments::default();
+        let mut right = Moments::default();
+        for i in 0..4 {
+            all.push(xs[i], ys[i]);
+            if i < 1 { left.push(xs[i], ys[i]) } else { right.push(xs[i], ys[i]) }
+        }
+        let merged = left.merge(&right);
+        assert!((all.variance().unwrap() - 30.0).abs() < 1e-6);
+        assert!((merged.variance().unwrap() - 30.0).abs() < 1e-6);
+        assert!((merged.covariance().unwrap() - 5.0).abs() < 1e-6);
+        assert!((merged.correlation().unwrap() - 5.0 / (30f64 * 5.0 / 3.0).sqrt()).abs() < 1e-9);
+        assert_eq!(Moments::default().merge(&left).variance(), None);
+    }
+}
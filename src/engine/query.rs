@@ -8,8 +8,11 @@ use engine::batch_merging::*;
 use engine::filter::Filter;
 use engine::query_plan::{QueryPlan, QueryExecutor};
 use engine::query_plan;
-use engine::types::EncodingType;
+use engine::types::{BasicType, EncodingType};
+use engine::vector_op::StringArena;
+use ingest::raw_val::RawVal;
 use mem_store::column::Column;
+use ordered_float::OrderedFloat;
 use syntax::expression::*;
 use syntax::limit::*;
 
@@ -20,16 +23,24 @@ pub struct Query {
     pub table: String,
     pub filter: Expr,
     pub aggregate: Vec<(Aggregator, Expr)>,
-    pub order_by: Option<String>,
-    pub order_desc: bool,
+    pub having: Option<Expr>,
+    // Alias for each output column, if any
+    pub aliases: Vec<Option<String>>,
+    // Maps output columns to grouping keys followed by aggregates
+    pub projection: Option<Vec<usize>>,
+    pub order_by: Vec<(Expr, bool)>,
     pub limit: LimitClause,
-    pub order_by_index: Option<usize>,
+    // Sort keys as index into result columns (grouping keys followed by aggregates for aggregation queries)
+    pub order_by_index: Vec<(usize, bool)>,
 }
 
 impl Query {
     #[inline(never)] // produces more useful profiles
-    pub fn run<'a>(&self, columns: &HashMap<&'a str, &'a Column>) -> Result<BatchResult<'a>, QueryError> {
-        let mut executor = QueryExecutor::default();
+    // Strings computed by the query are allocated in `strings`
+    pub fn run<'a>(&self,
+                   columns: &HashMap<&'a str, &'a Column>,
+                   strings: &'a StringArena) -> Result<BatchResult<'a>, QueryError> {
+        let mut executor = QueryExecutor::new(strings);
 
         let (filter_plan, filter_type) = QueryPlan::create_query_plan(&self.filter, columns)?;
         match filter_type.encoding_type() {
@@ -41,25 +52,45 @@ impl Query {
         }
 
         let mut select = Vec::new();
-        if let Some(index) = self.order_by_index {
+        if !self.order_by_index.is_empty() {
             // TODO(clemens): Reuse sort_column for result
             // TODO(clemens): Optimization: sort directly if only single column selected
-            let (plan, _) = query_plan::order_preserving(
-                QueryPlan::create_query_plan(&self.select[index], columns)?);
-            let sort_column = query_plan::prepare(plan.clone(), &mut executor);
-            let sort_indices = query_plan::prepare(
-                QueryPlan::SortIndices(
-                    Box::new(QueryPlan::ReadBuffer(sort_column)),
-                    self.order_desc),
-                &mut executor);
+            // Sort by least significant key first, subsequent stable sorts preserve that order for equal keys
+            let mut sort_indices = None;
+            for &(index, desc) in self.order_by_index.iter().rev() {
+                let (plan, _) = query_plan::order_preserving(
+                    QueryPlan::create_query_plan(&self.select[index], columns)?);
+                let sort_column = Box::new(QueryPlan::ReadBuffer(query_plan::prepare(plan, &mut executor)));
+                let plan = match sort_indices {
+                    None => QueryPlan::SortIndices(sort_column, desc),
+                    Some(indices) => QueryPlan::SortBy(sort_column, Box::new(QueryPlan::ReadBuffer(indices)), desc),
+                };
+                sort_indices = Some(query_plan::prepare(plan, &mut executor));
+                // Null values sort after all other values
+                if let Some(present) = QueryPlan::compile_present(&self.select[index], columns)? {
+                    let is_null = QueryPlan::TypeConversion(
+                        Box::new(QueryPlan::Not(Box::new(present))), EncodingType::BitVec, EncodingType::I64);
+                    let plan = QueryPlan::SortBy(Box::new(is_null), Box::new(QueryPlan::ReadBuffer(sort_indices.unwrap())), desc);
+                    sort_indices = Some(query_plan::prepare(plan, &mut executor));
+                }
+            }
             executor.new_stage();
-            executor.set_filter(Filter::Indices(sort_indices));
+            executor.set_filter(Filter::Indices(sort_indices.unwrap()));
         }
+        let mut column_types = Vec::with_capacity(self.select.len());
         for expr in &self.select {
             let (mut plan, plan_type) = QueryPlan::create_query_plan(expr, columns)?;
+            column_types.push(plan_type.decoded);
             if let Some(codec) = plan_type.codec {
                 plan = QueryPlan::DecodeWith(Box::new(plan), codec);
             }
+            // Boolean values are returned as 0 or 1
+            if plan_type.encoding_type() == EncodingType::BitVec {
+                plan = QueryPlan::TypeConversion(Box::new(plan), EncodingType::BitVec, EncodingType::I64);
+            }
+            if let Some(present) = QueryPlan::compile_present(expr, columns)? {
+                plan = QueryPlan::Nullable(Box::new(plan), Box::new(present));
+            }
             select.push(query_plan::prepare(plan, &mut executor));
         }
 
@@ -69,19 +100,25 @@ impl Query {
 
         Ok(BatchResult {
             group_by: None,
-            sort_by: self.order_by_index,
+            sort_by: self.order_by_index.clone(),
             select,
             aggregators: Vec::with_capacity(0),
             level: 0,
             batch_count: 1,
+            column_types,
+            dictionaries: Vec::with_capacity(0),
         })
     }
 
+    // `shared_dictionaries` contains columns that are encoded with the same dictionary in all batches
     #[inline(never)] // produces more useful profiles
-    pub fn run_aggregate<'a>(&self, columns: &HashMap<&'a str, &'a Column>) -> Result<BatchResult<'a>, QueryError> {
+    pub fn run_aggregate<'a>(&self,
+                             columns: &HashMap<&'a str, &'a Column>,
+                             shared_dictionaries: &HashSet<String>,
+                             strings: &'a StringArena) -> Result<BatchResult<'a>, QueryError> {
         trace_start!("run_aggregate");
 
-        let mut executor = QueryExecutor::default();
+        let mut executor = QueryExecutor::new(strings);
 
         let (filter_plan, filter_type) = QueryPlan::create_query_plan(&self.filter, columns)?;
         match filter_type.encoding_type() {
@@ -92,55 +129,111 @@ impl Query {
             _ => {}
         }
 
-        let (grouping_key_plan, grouping_key_type, max_grouping_key, decode_plans) =
-            QueryPlan::compile_grouping_key(&self.select, columns)?;
-        let raw_grouping_key = query_plan::prepare(grouping_key_plan, &mut executor);
-
-        let (encoded_group_by_column, grouping_key, aggregation_cardinality) =
-        // TODO(clemens): refine criterion
-        // TODO(clemens): can often collect group_by from non-zero positions in aggregation result
-            if max_grouping_key < 1 << 16 && grouping_key_type.is_positive_integer() {
-                let max_grouping_key_buf = executor.new_buffer();
-                (query_plan::prepare_unique(
-                    raw_grouping_key,
-                    grouping_key_type.encoding_type(),
-                    max_grouping_key as usize,
-                    &mut executor),
-                 raw_grouping_key,
-                 max_grouping_key_buf)
-            } else {
-                query_plan::prepare_hashmap_grouping(
-                    raw_grouping_key,
-                    grouping_key_type.encoding_type(),
-                    max_grouping_key as usize,
-                    &mut executor)
-            };
+        let grouping_key = QueryPlan::compile_grouping_key(&self.select, columns)?;
+        let batch_len = columns.values().next().map_or(0, |c| c.len());
+        let (grouping_key, grouping_key_type, max_grouping_key, encoded_group_by_column, decode_plans) =
+            query_plan::prepare_grouping(grouping_key, batch_len, &mut executor);
 
         executor.set_encoded_group_by(encoded_group_by_column);
         // TODO(clemens): fix for multiple groups
         // let groups = groups.order_preserving();
 
+        let mut column_types = Vec::with_capacity(self.select.len() + self.aggregate.len());
+        for expr in &self.select {
+            column_types.push(QueryPlan::create_query_plan(expr, columns)?.1.decoded);
+        }
+
         let mut result = Vec::new();
-        for &(aggregator, ref expr) in &self.aggregate {
+        let mut partial_aggregators = Vec::with_capacity(self.aggregate.len());
+        for (i, &(aggregator, ref expr)) in self.aggregate.iter().enumerate() {
             trace_start!("aggregator {:?}", aggregator);
-            let (plan, plan_type) = QueryPlan::create_query_plan(expr, columns)?;
-            // TODO(clemens): Use more precise aggregation_cardinality instead of max_grouping_key
-            let mut aggregate = query_plan::prepare_aggregation(
-                plan,
-                plan_type,
-                grouping_key,
-                grouping_key_type.encoding_type(),
-                max_grouping_key as usize,
-                aggregator,
-                &mut executor)?;
-            result.push(aggregate)
-            // TODO(clemens): renable
-            // result.push(compiled.execute().index_decode(&grouping_sort_indices));
+            if shares_digest(&self.aggregate, i) {
+                column_types.push(BasicType::Float);
+                continue;
+            }
+            let (expr, condition) = match *expr {
+                Expr::Func(FuncType::AggregateFilter, ref expr, ref condition) => (&**expr, Some(&**condition)),
+                ref expr => (expr, None),
+            };
+            // Null values and rows not satisfying the FILTER condition do not contribute to aggregates
+            let mut present = QueryPlan::compile_present(expr, columns)?;
+            if let Some(condition) = condition {
+                let (condition_plan, condition_type) = QueryPlan::create_query_plan(condition, columns)?;
+                if condition_type.encoding_type() != EncodingType::BitVec {
+                    bail!(QueryError::TypeError, "FILTER (WHERE {:?}) is not a boolean condition", condition);
+                }
+                present = Some(match present {
+                    Some(present) => QueryPlan::And(Box::new(present), Box::new(condition_plan)),
+                    None => condition_plan,
+                });
+            }
+            let present = present.map(|present| query_plan::prepare(present, &mut executor));
+            // Bitsets of dictionary codes can only be merged if all batches use the same dictionary
+            let shared_dictionary = match *expr {
+                Expr::ColName(ref name) => shared_dictionaries.contains(name),
+                _ => false,
+            };
+            column_types.push(match aggregator {
+                Aggregator::Count | Aggregator::CountDistinct | Aggregator::ApproxCountDistinct => BasicType::Integer,
+                Aggregator::Percentile(_) | Aggregator::Variance | Aggregator::Stddev |
+                Aggregator::Covar | Aggregator::Corr => BasicType::Float,
+                Aggregator::ArgMin | Aggregator::ArgMax | Aggregator::First | Aggregator::Last => match *expr {
+                    Expr::Func(FuncType::Pair, ref x, _) => QueryPlan::create_query_plan(x, columns)?.1.decoded,
+                    _ => bail!(QueryError::TypeError, "{:?} expects two arguments", aggregator),
+                },
+                _ => match QueryPlan::create_query_plan(expr, columns)?.1.decoded {
+                    BasicType::Timestamp if aggregator == Aggregator::Sum => BasicType::Integer,
+                    BasicType::Integer if aggregator == Aggregator::Avg => BasicType::Float,
+                    basic_type => basic_type,
+                },
+            });
+            for partial in aggregator.partial_aggregates() {
+                // TODO(clemens): Use more precise aggregation_cardinality instead of max_grouping_key
+                let aggregates = match *expr {
+                    Expr::Func(FuncType::Pair, ref x, ref y) => query_plan::prepare_bivariate_aggregation(
+                        QueryPlan::create_query_plan(x, columns)?,
+                        QueryPlan::create_query_plan(y, columns)?,
+                        grouping_key,
+                        present,
+                        grouping_key_type,
+                        max_grouping_key,
+                        partial,
+                        &mut executor)?,
+                    _ => {
+                        let (plan, plan_type) = QueryPlan::create_query_plan(expr, columns)?;
+                        vec![query_plan::prepare_aggregation(
+                            plan,
+                            plan_type,
+                            grouping_key,
+                            present,
+                            grouping_key_type,
+                            max_grouping_key,
+                            partial,
+                            shared_dictionary,
+                            &mut executor)?]
+                    }
+                };
+                for aggregate in aggregates {
+                    result.push(aggregate);
+                    partial_aggregators.push(partial);
+                }
+                // TODO(clemens): renable
+                // result.push(compiled.execute().index_decode(&grouping_sort_indices));
+            }
         }
 
         trace_replace!("decode grouping_key");
         let mut grouping_columns = Vec::with_capacity(decode_plans.len());
-        for decode_plan in decode_plans {
+        let mut dictionaries = Vec::with_capacity(decode_plans.len());
+        // Aggregates without GROUP BY have a single hidden grouping column that does not correspond to any expression
+        for (i, decode_plan) in decode_plans.into_iter().enumerate() {
+            let (decode_plan, dictionary) = match self.select.get(i) {
+                Some(&Expr::ColName(ref name)) if shared_dictionaries.contains(name) => query_plan::dictionary_codes(decode_plan),
+                _ => (decode_plan, None),
+            };
+            if i < self.select.len() {
+                dictionaries.push(dictionary);
+            }
             let decoded = query_plan::prepare(decode_plan.clone(), &mut executor);
             // TODO(clemens): renable
             // .index_decode(&grouping_sort_indices);
@@ -164,11 +257,13 @@ impl Query {
         trace_replace!("final decode");
         Ok(BatchResult {
             group_by: Some(group_by),
-            sort_by: None,
+            sort_by: Vec::with_capacity(0),
             select: select,
-            aggregators: self.aggregate.iter().map(|x| x.0).collect(),
+            aggregators: partial_aggregators,
             level: 0,
             batch_count: 1,
+            column_types,
+            dictionaries,
         })
     }
 
@@ -202,12 +297,154 @@ impl Query {
                 match agg {
                     Aggregator::Count => format!("count_{}", anon_aggregates),
                     Aggregator::Sum => format!("sum_{}", anon_aggregates),
+                    Aggregator::Min => format!("min_{}", anon_aggregates),
+                    Aggregator::Max => format!("max_{}", anon_aggregates),
+                    Aggregator::Avg => format!("avg_{}", anon_aggregates),
+                    Aggregator::CountDistinct => format!("count_distinct_{}", anon_aggregates),
+                    Aggregator::ApproxCountDistinct => format!("approx_count_distinct_{}", anon_aggregates),
+                    Aggregator::Percentile(_) => format!("percentile_{}", anon_aggregates),
+                    Aggregator::Variance => format!("variance_{}", anon_aggregates),
+                    Aggregator::Stddev => format!("stddev_{}", anon_aggregates),
+                    Aggregator::Covar => format!("covar_{}", anon_aggregates),
+                    Aggregator::Corr => format!("corr_{}", anon_aggregates),
+                    Aggregator::ArgMin => format!("arg_min_{}", anon_aggregates),
+                    Aggregator::ArgMax => format!("arg_max_{}", anon_aggregates),
+                    Aggregator::First => format!("first_{}", anon_aggregates),
+                    Aggregator::Last => format!("last_{}", anon_aggregates),
                 }
             });
 
-        select_cols.chain(aggregate_cols).collect()
+        let colnames = select_cols.chain(aggregate_cols).collect::<Vec<_>>();
+        let colnames = match self.projection {
+            Some(ref projection) => projection.iter().map(|&i| colnames[i].clone()).collect(),
+            None => colnames,
+        };
+        colnames.into_iter()
+            .enumerate()
+            .map(|(i, name)| match self.aliases.get(i) {
+                Some(&Some(ref alias)) => alias.clone(),
+                _ => name,
+            })
+            .collect()
     }
 
+    // Evaluates HAVING clause on a row consisting of grouping keys followed by aggregates
+    pub fn satisfies_having(&self, row: &[RawVal]) -> Result<bool, QueryError> {
+        match self.having {
+            Some(ref having) => match self.evaluate(having, row)? {
+                RawVal::Int(x) => Ok(x != 0),
+                // Groups for which the HAVING clause is null are excluded
+                RawVal::Null => Ok(false),
+                x => bail!(QueryError::TypeError, "Expected boolean HAVING clause, found {}", x),
+            },
+            None => Ok(true),
+        }
+    }
+
+    fn evaluate(&self, expr: &Expr, row: &[RawVal]) -> Result<RawVal, QueryError> {
+        use self::FuncType::*;
+        use self::RawVal::*;
+        if let Some(index) = self.select.iter().position(|e| e == expr) {
+            return Ok(row[index].clone());
+        }
+        Ok(match *expr {
+            Expr::Aggregate(aggregator, ref e) => {
+                let e = &**e;
+                match self.aggregate.iter().position(|&(a, ref x)| a == aggregator && x == e) {
+                    Some(index) => row[self.select.len() + index].clone(),
+                    None => bail!(QueryError::FatalError, "Aggregate {:?} was not computed", expr),
+                }
+            }
+            Expr::Func(func, ref lhs, ref rhs) => {
+                let lhs = self.evaluate(lhs, row)?;
+                let rhs = self.evaluate(rhs, row)?;
+                // Integers are converted to floats when combined with floats
+                let (lhs, rhs) = match (lhs, rhs) {
+                    (Int(l), Float(r)) => (Float(OrderedFloat(l as f64)), Float(r)),
+                    (Float(l), Int(r)) => (Float(l), Float(OrderedFloat(r as f64))),
+                    operands => operands,
+                };
+                match (func, lhs, rhs) {
+                    // Boolean operators follow three-valued logic
+                    (And, Int(l), Int(r)) => Int((l != 0 && r != 0) as i64),
+                    (And, Int(0), Null) | (And, Null, Int(0)) => Int(0),
+                    (And, Int(_), Null) | (And, Null, Int(_)) | (And, Null, Null) => Null,
+                    (Or, Int(l), Int(r)) => Int((l != 0 || r != 0) as i64),
+                    (Or, Int(0), Null) | (Or, Null, Int(0)) | (Or, Null, Null) => Null,
+                    (Or, Int(_), Null) | (Or, Null, Int(_)) => Int(1),
+                    (Not, Int(x), _) => Int((x == 0) as i64),
+                    (Negate, Int(x), _) => Int(x.wrapping_neg()),
+                    (Negate, Float(x), _) => Float(OrderedFloat(-x.0)),
+                    (IsNull, x, _) => Int((x == Null) as i64),
+                    (IsNotNull, x, _) => Int((x != Null) as i64),
+                    // Comparisons and arithmetic with null operands are null
+                    (func, ref l, ref r) if *l == Null || *r == Null => match func {
+                        Equals | NotEquals | LT | LTE | GT | GTE | Add | Subtract | Multiply | Divide | Not | Negate => Null,
+                        _ => bail!(QueryError::NotImplemented, "{:?}({}, {}) in HAVING clause", func, l, r),
+                    },
+                    (Equals, l, r) => Int((l == r) as i64),
+                    (NotEquals, l, r) => Int((l != r) as i64),
+                    (LT, Int(l), Int(r)) => Int((l < r) as i64),
+                    (LT, Str(l), Str(r)) => Int((l < r) as i64),
+                    (LT, Float(l), Float(r)) => Int((l < r) as i64),
+                    (LTE, Int(l), Int(r)) => Int((l <= r) as i64),
+                    (LTE, Str(l), Str(r)) => Int((l <= r) as i64),
+                    (LTE, Float(l), Float(r)) => Int((l <= r) as i64),
+                    (GT, Int(l), Int(r)) => Int((l > r) as i64),
+                    (GT, Str(l), Str(r)) => Int((l > r) as i64),
+                    (GT, Float(l), Float(r)) => Int((l > r) as i64),
+                    (GTE, Int(l), Int(r)) => Int((l >= r) as i64),
+                    (GTE, Str(l), Str(r)) => Int((l >= r) as i64),
+                    (GTE, Float(l), Float(r)) => Int((l >= r) as i64),
+                    (Add, Int(l), Int(r)) => Int(l.wrapping_add(r)),
+                    (Add, Float(l), Float(r)) => Float(OrderedFloat(l.0 + r.0)),
+                    (Subtract, Int(l), Int(r)) => Int(l.wrapping_sub(r)),
+                    (Subtract, Float(l), Float(r)) => Float(OrderedFloat(l.0 - r.0)),
+                    (Multiply, Int(l), Int(r)) => Int(l.wrapping_mul(r)),
+                    (Multiply, Float(l), Float(r)) => Float(OrderedFloat(l.0 * r.0)),
+                    (Divide, Int(_), Int(0)) => Null,
+                    (Divide, Int(l), Int(r)) => Int(l.wrapping_div(r)),
+                    (Divide, Float(l), Float(r)) => Float(OrderedFloat(l.0 / r.0)),
+                    (func, l, r) => bail!(QueryError::NotImplemented, "{:?}({}, {}) in HAVING clause", func, l, r),
+                }
+            }
+            Expr::In(ref e, ref values) => {
+                match self.evaluate(e, row)? {
+                    Null => Null,
+                    value => Int(values.contains(&value) as i64),
+                }
+            }
+            Expr::Const(ref v) => v.clone(),
+            Expr::ColName(ref name) => bail!(QueryError::TypeError, "{} must appear in GROUP BY clause", name),
+        })
+    }
+
+    // Resolves ORDER BY clause to indices of result columns
+    pub fn resolve_order_by(&mut self, output_colnames: &[String]) -> Result<(), QueryError> {
+        let mut order_by_index = Vec::with_capacity(self.order_by.len());
+        for &(ref expr, desc) in &self.order_by {
+            let output_index = match *expr {
+                Expr::ColName(ref name) => output_colnames.iter().position(|c| c == name),
+                _ => None,
+            };
+            let index = match (output_index, &self.projection) {
+                (Some(index), &Some(ref projection)) => Some(projection[index]),
+                (Some(index), &None) => Some(index),
+                (None, _) => self.select.iter().position(|e| e == expr).or_else(|| match *expr {
+                    Expr::Aggregate(aggregator, ref e) => self.aggregate.iter()
+                        .position(|&(a, ref x)| a == aggregator && x == &**e)
+                        .map(|i| self.select.len() + i),
+                    _ => None,
+                }),
+            };
+            match index {
+                Some(index) => order_by_index.push((index, desc)),
+                None => bail!(QueryError::NotImplemented, "ORDER BY {:?} which is not part of result", expr),
+            }
+        }
+        self.order_by_index = order_by_index;
+        Ok(())
+    }
 
     pub fn find_referenced_cols(&self) -> HashSet<String> {
         let mut colnames = HashSet::new();
@@ -1,15 +1,23 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::i64;
 
 use ::QueryError;
+use engine::aggregation_operator;
 use engine::aggregator::Aggregator;
 use engine::filter::Filter;
+use engine::time_function::{TimeFunction, TimeUnit};
 use engine::types::*;
 use engine::vector_op::*;
 use engine::vector_op::vector_operator::BufferRef;
+use ingest::extractor::parse_timestamp;
 use ingest::raw_val::RawVal;
 use mem_store::column::Column;
 use mem_store::column::{ColumnData, ColumnCodec};
+use ordered_float::OrderedFloat;
+use regex;
+use regex::Regex;
 use syntax::expression::*;
 
 
@@ -18,6 +26,9 @@ pub enum QueryPlan<'a> {
     ReadColumn(&'a ColumnCodec),
     DecodeColumn(&'a ColumnData),
     ReadBuffer(BufferRef),
+    ReadPresent(&'a ColumnData),
+    // Zero for every row of the column, used as grouping key when there is no GROUP BY
+    Zeros(&'a ColumnData),
     // TODO(clemens): make it possible to replace this with Decode(ReadColumn)
 
     DecodeWith(Box<QueryPlan<'a>>, &'a ColumnCodec),
@@ -29,21 +40,65 @@ pub enum QueryPlan<'a> {
     BitPack(Box<QueryPlan<'a>>, Box<QueryPlan<'a>>, i64),
     BitUnpack(Box<QueryPlan<'a>>, u8, u8),
 
+    Add(EncodingType, EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    Subtract(EncodingType, EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    Multiply(EncodingType, EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    Divide(EncodingType, EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    AddVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    SubtractVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    SubtractSV(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    MultiplyVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    DivideVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    DivideSV(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+
     LessThanVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    LessThanEqualsVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    GreaterThanVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    GreaterThanEqualsVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
     EqualsVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    NotEqualsVS(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    LessThanVV(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    LessThanEqualsVV(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    EqualsVV(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    NotEqualsVV(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    // Comparison of a run-length encoded column with a constant, evaluated once per run. The last plan compares
+    // row by row and is used instead when the column is filtered.
+    CompareRuns(FuncType, &'a ColumnCodec, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    RegexMatch(Box<QueryPlan<'a>>, Regex),
+    ConvertCase(Box<QueryPlan<'a>>, Case),
+    // Converts the dictionary entries of codes
+    ConvertCaseDictionary(EncodingType, Box<QueryPlan<'a>>, Case, &'a ColumnCodec),
+    TimeFunction(EncodingType, Box<QueryPlan<'a>>, TimeFunction),
+    RegexMatchDictionary(Regex, &'a ColumnCodec),
+    InSetDictionary(HashSet<String>, &'a ColumnCodec),
+    CodeInSet(EncodingType, Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
     And(Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
     Or(Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
+    Not(Box<QueryPlan<'a>>),
+    Nullable(Box<QueryPlan<'a>>, Box<QueryPlan<'a>>),
 
     SortIndices(Box<QueryPlan<'a>>, bool),
+    SortBy(Box<QueryPlan<'a>>, Box<QueryPlan<'a>>, bool),
+    Gather(Box<QueryPlan<'a>>, EncodingType, Box<QueryPlan<'a>>),
 
     EncodedGroupByPlaceholder,
 
     Constant(RawVal),
 }
 
+pub enum GroupingKey<'a> {
+    // All grouping columns packed into a single integer with the given maximum value
+    Packed(QueryPlan<'a>, Type<'a>, i64, Vec<QueryPlan<'a>>),
+    // Grouping columns that cannot be packed are grouped by the byte representation of each row
+    Rows(Vec<(QueryPlan<'a>, Type<'a>)>),
+    // Nullable grouping columns are preceded by a column that indicates null values
+    Nullable(Box<GroupingKey<'a>>, Vec<bool>),
+}
+
 pub struct QueryExecutor<'a> {
     stages: Vec<ExecutorStage<'a>>,
     count: usize,
+    strings: &'a StringArena,
 }
 
 #[derive(Default)]
@@ -54,6 +109,14 @@ struct ExecutorStage<'a> {
 }
 
 impl<'a> QueryExecutor<'a> {
+    pub fn new(strings: &'a StringArena) -> QueryExecutor<'a> {
+        QueryExecutor {
+            stages: vec![ExecutorStage::default()],
+            count: 0,
+            strings,
+        }
+    }
+
     pub fn new_stage(&mut self) {
         self.stages.push(ExecutorStage::default());
     }
@@ -77,6 +140,9 @@ impl<'a> QueryExecutor<'a> {
 
     fn filter(&self) -> Filter { self.stages.last().unwrap().filter }
 
+    // Runs only line up with the rows of the unfiltered column
+    fn can_use_runs(&self) -> bool { self.filter().is_none() }
+
     pub fn set_filter(&mut self, filter: Filter) {
         self.stages.last_mut().unwrap().filter = filter;
     }
@@ -90,15 +156,6 @@ impl<'a> QueryExecutor<'a> {
     }
 }
 
-impl<'a> Default for QueryExecutor<'a> {
-    fn default() -> QueryExecutor<'a> {
-        QueryExecutor {
-            stages: vec![ExecutorStage::default()],
-            count: 0
-        }
-    }
-}
-
 impl<'a> fmt::Display for QueryExecutor<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for (i, stage) in self.stages.iter().enumerate() {
@@ -135,6 +192,8 @@ pub fn prepare<'a>(plan: QueryPlan<'a>, result: &mut QueryExecutor<'a>) -> Buffe
             Filter::BitVec(filter) => Box::new(FilterEncoded::new(col, filter, result.new_buffer())),
             Filter::Indices(filter) => Box::new(IndexEncoded::new(col, filter, result.new_buffer())),
         }
+        QueryPlan::ReadPresent(col) => Box::new(GetPresent::new(col, result.filter(), result.new_buffer())),
+        QueryPlan::Zeros(col) => Box::new(Zeros::new(col, result.filter(), result.new_buffer())),
         QueryPlan::Constant(ref c) => Box::new(Constant::new(c.clone(), result.new_buffer())),
         QueryPlan::DecodeWith(plan, codec) => Box::new(DecodeWith::new(prepare(*plan, result), result.new_buffer(), codec)),
         QueryPlan::TypeConversion(plan, initial_type, target_type) =>
@@ -147,10 +206,65 @@ pub fn prepare<'a>(plan: QueryPlan<'a>, result: &mut QueryExecutor<'a>) -> Buffe
             VecOperator::bit_shift_left_add(prepare(*lhs, result), prepare(*rhs, result), result.new_buffer(), shift_amount),
         QueryPlan::BitUnpack(inner, shift, width) =>
             VecOperator::bit_unpack(prepare(*inner, result), result.new_buffer(), shift, width),
+        QueryPlan::Add(lhs_type, rhs_type, lhs, rhs) =>
+            VecOperator::addition_vv(lhs_type, rhs_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::Subtract(lhs_type, rhs_type, lhs, rhs) =>
+            VecOperator::subtraction_vv(lhs_type, rhs_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::Multiply(lhs_type, rhs_type, lhs, rhs) =>
+            VecOperator::multiplication_vv(lhs_type, rhs_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::Divide(lhs_type, rhs_type, lhs, rhs) =>
+            VecOperator::division_vv(lhs_type, rhs_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::AddVS(left_type, lhs, rhs) =>
+            VecOperator::addition_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::SubtractVS(left_type, lhs, rhs) =>
+            VecOperator::subtraction_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::SubtractSV(right_type, lhs, rhs) =>
+            VecOperator::subtraction_sv(right_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::MultiplyVS(left_type, lhs, rhs) =>
+            VecOperator::multiplication_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::DivideVS(left_type, lhs, rhs) =>
+            VecOperator::division_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::DivideSV(right_type, lhs, rhs) =>
+            VecOperator::division_sv(right_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
         QueryPlan::LessThanVS(left_type, lhs, rhs) =>
             VecOperator::less_than_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::LessThanEqualsVS(left_type, lhs, rhs) =>
+            VecOperator::less_than_equals_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::GreaterThanVS(left_type, lhs, rhs) =>
+            VecOperator::greater_than_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::GreaterThanEqualsVS(left_type, lhs, rhs) =>
+            VecOperator::greater_than_equals_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
         QueryPlan::EqualsVS(left_type, lhs, rhs) =>
             VecOperator::equals_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::NotEqualsVS(left_type, lhs, rhs) =>
+            VecOperator::not_equals_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::LessThanVV(t, lhs, rhs) =>
+            VecOperator::less_than_vv(t, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::LessThanEqualsVV(t, lhs, rhs) =>
+            VecOperator::less_than_equals_vv(t, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::EqualsVV(t, lhs, rhs) =>
+            VecOperator::equals_vv(t, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::NotEqualsVV(t, lhs, rhs) =>
+            VecOperator::not_equals_vv(t, prepare(*lhs, result), prepare(*rhs, result), result.new_buffer()),
+        QueryPlan::CompareRuns(op, codec, rhs, fallback) => if result.can_use_runs() {
+            VecOperator::compare_runs(op, codec, prepare(*rhs, result), result.new_buffer())
+        } else {
+            return prepare(*fallback, result);
+        },
+        QueryPlan::RegexMatch(plan, regex) =>
+            VecOperator::regex_match(prepare(*plan, result), result.new_buffer(), regex),
+        QueryPlan::ConvertCase(plan, case) =>
+            VecOperator::convert_case(prepare(*plan, result), result.new_buffer(), case, result.strings),
+        QueryPlan::ConvertCaseDictionary(t, codes, case, codec) =>
+            VecOperator::convert_case_dictionary(t, prepare(*codes, result), result.new_buffer(), case, codec, result.strings),
+        QueryPlan::TimeFunction(t, plan, function) =>
+            VecOperator::time_function(t, prepare(*plan, result), result.new_buffer(), function),
+        QueryPlan::RegexMatchDictionary(regex, codec) =>
+            VecOperator::regex_match_dictionary(result.new_buffer(), regex, codec),
+        QueryPlan::InSetDictionary(set, codec) =>
+            VecOperator::in_set_dictionary(result.new_buffer(), set, codec),
+        QueryPlan::CodeInSet(code_type, codes, set) =>
+            VecOperator::code_in_set(code_type, prepare(*codes, result), prepare(*set, result), result.new_buffer()),
         QueryPlan::Or(lhs, rhs) => {
             let inplace = prepare(*lhs, result);
             // If we don't assign to `operation` and pass expression directly to push, we trigger an infinite loop in the compiler
@@ -167,9 +281,20 @@ pub fn prepare<'a>(plan: QueryPlan<'a>, result: &mut QueryExecutor<'a>) -> Buffe
             result.push(operation);
             return inplace;
         }
+        QueryPlan::Not(plan) => {
+            let inplace = prepare(*plan, result);
+            result.push(Boolean::not(inplace));
+            return inplace;
+        }
+        QueryPlan::Nullable(plan, present) =>
+            Box::new(MakeNullable::new(prepare(*plan, result), prepare(*present, result), result.new_buffer())),
         QueryPlan::EncodedGroupByPlaceholder => return result.encoded_group_by().unwrap(),
         QueryPlan::SortIndices(plan, descending) =>
             VecOperator::sort_indices(prepare(*plan, result), result.new_buffer(), descending),
+        QueryPlan::Gather(plan, t, indices) =>
+            VecOperator::gather(prepare(*plan, result), prepare(*indices, result), result.new_buffer(), t),
+        QueryPlan::SortBy(plan, indices, descending) =>
+            VecOperator::sort_by(prepare(*plan, result), prepare(*indices, result), result.new_buffer(), descending),
         QueryPlan::ReadBuffer(buffer) => return buffer,
     };
     result.push(operation);
@@ -197,23 +322,123 @@ pub fn prepare_hashmap_grouping(raw_grouping_key: BufferRef,
     (unique_out, grouping_key_out, cardinality_out)
 }
 
+// Replaces decoding of a grouping column encoded with a shared dictionary by its dictionary codes, which can be merged
+// across batches without comparing strings. Returns the dictionary that maps the codes back to strings.
+pub fn dictionary_codes<'a>(decode_plan: QueryPlan<'a>) -> (QueryPlan<'a>, Option<&'a [Option<String>]>) {
+    match decode_plan {
+        QueryPlan::DecodeWith(plan, codec) if codec.has_shared_dictionary() =>
+            (QueryPlan::TypeConversion(plan, codec.encoding_type(), EncodingType::I64), codec.dictionary()),
+        QueryPlan::Nullable(plan, present) => {
+            let (plan, dictionary) = dictionary_codes(*plan);
+            (QueryPlan::Nullable(Box::new(plan), present), dictionary)
+        }
+        plan => (plan, None),
+    }
+}
+
+// Returns grouping key, its type, the maximum grouping key, encoded group by column and plans to decode group by columns
+pub fn prepare_grouping<'a>(grouping_key: GroupingKey<'a>,
+                            batch_len: usize,
+                            result: &mut QueryExecutor<'a>) -> (BufferRef, EncodingType, usize, BufferRef, Vec<QueryPlan<'a>>) {
+    match grouping_key {
+        GroupingKey::Packed(plan, plan_type, max_grouping_key, decode_plans) => {
+            let raw_grouping_key = prepare(plan, result);
+            // TODO(clemens): refine criterion
+            // TODO(clemens): can often collect group_by from non-zero positions in aggregation result
+            if max_grouping_key < 1 << 16 && plan_type.is_positive_integer() {
+                let unique = prepare_unique(
+                    raw_grouping_key,
+                    plan_type.encoding_type(),
+                    max_grouping_key as usize,
+                    result);
+                (raw_grouping_key, plan_type.encoding_type(), max_grouping_key as usize, unique, decode_plans)
+            } else {
+                // Number of groups is bounded by number of rows
+                let (unique, grouping_key, _) = prepare_hashmap_grouping(
+                    raw_grouping_key,
+                    plan_type.encoding_type(),
+                    batch_len,
+                    result);
+                (grouping_key, EncodingType::U32, batch_len, unique, decode_plans)
+            }
+        }
+        GroupingKey::Rows(plans) => {
+            let mut inputs = Vec::with_capacity(plans.len());
+            let mut decode_plans = Vec::with_capacity(plans.len());
+            for (plan, plan_type) in plans {
+                let input = prepare(plan, result);
+                let mut decode_plan = QueryPlan::Gather(
+                    Box::new(QueryPlan::ReadBuffer(input)),
+                    plan_type.encoding_type(),
+                    Box::new(QueryPlan::EncodedGroupByPlaceholder));
+                if let Some(codec) = plan_type.codec {
+                    decode_plan = QueryPlan::DecodeWith(Box::new(decode_plan), codec);
+                }
+                inputs.push(input);
+                decode_plans.push(decode_plan);
+            }
+            let grouping_key = result.new_buffer();
+            let first_rows = result.new_buffer();
+            result.push(VecOperator::hash_map_grouping_byte_slices(inputs, grouping_key, first_rows));
+            (grouping_key, EncodingType::U32, batch_len, first_rows, decode_plans)
+        }
+        GroupingKey::Nullable(grouping_key, nullable) => {
+            let (grouping_key, grouping_key_type, max_grouping_key, encoded_group_by, decode_plans) =
+                prepare_grouping(*grouping_key, batch_len, result);
+            let mut decode_plans = decode_plans.into_iter();
+            let decode_plans = nullable.into_iter().map(|is_nullable| {
+                let decode_plan = decode_plans.next().unwrap();
+                if is_nullable {
+                    let present = QueryPlan::EqualsVS(
+                        EncodingType::I64, Box::new(decode_plan), Box::new(QueryPlan::Constant(RawVal::Int(0))));
+                    QueryPlan::Nullable(Box::new(decode_plans.next().unwrap()), Box::new(present))
+                } else {
+                    decode_plan
+                }
+            }).collect();
+            (grouping_key, grouping_key_type, max_grouping_key, encoded_group_by, decode_plans)
+        }
+    }
+}
+
 // TODO(clemens): add QueryPlan::Aggregation and merge with prepare function
+// Bitsets of codes for count(distinct) take up `MAX_DISTINCT_CODES / 8` bytes per group
+const MAX_DISTINCT_CODES: usize = 1 << 16;
+
 pub fn prepare_aggregation<'a, 'b>(plan: QueryPlan<'a>,
                                    mut plan_type: Type<'a>,
                                    grouping_key: BufferRef,
+                                   present: Option<BufferRef>,
                                    grouping_type: EncodingType,
                                    max_index: usize,
                                    aggregator: Aggregator,
+                                   shared_dictionary: bool,
                                    result: &mut QueryExecutor<'a>) -> Result<BufferRef, QueryError> {
+    match aggregator {
+        Aggregator::Sum | Aggregator::Min | Aggregator::Max => match plan_type.decoded {
+            BasicType::Integer | BasicType::Float | BasicType::Decimal(_) | BasicType::Timestamp => {}
+            basic_type => bail!(QueryError::TypeError, "{:?}({:?}), expected number", aggregator, basic_type),
+        },
+        _ => {}
+    }
     let output_location = result.new_buffer();
     let operation: BoxedOperator<'a> = match (aggregator, plan) {
         (Aggregator::Count, _) =>
             VecOperator::count(grouping_key,
+                               present,
                                output_location,
                                grouping_type,
                                max_index,
                                false),
 
+        (Aggregator::Sum, QueryPlan::ReadColumn(codec)) if codec.runs().is_some() && present.is_none() && result.can_use_runs() =>
+            VecOperator::summation_runs(codec,
+                                        grouping_key,
+                                        output_location,
+                                        grouping_type,
+                                        max_index,
+                                        false),
+
         (Aggregator::Sum, mut plan) => {
             if !plan_type.is_summation_preserving() {
                 plan = QueryPlan::DecodeWith(Box::new(plan), plan_type.codec.unwrap());
@@ -221,17 +446,297 @@ pub fn prepare_aggregation<'a, 'b>(plan: QueryPlan<'a>,
             }
             VecOperator::summation(prepare(plan, result),
                                    grouping_key,
+                                   present,
                                    output_location,
                                    plan_type.encoding_type(),
                                    grouping_type,
                                    max_index,
                                    false) // TODO(clemens): determine dense groupings
         }
+
+        (Aggregator::Min, plan) => {
+            let (plan, plan_type) = summation_preserving((plan, plan_type));
+            VecOperator::minimum(prepare(plan, result),
+                                 grouping_key,
+                                 present,
+                                 output_location,
+                                 plan_type.encoding_type(),
+                                 grouping_type,
+                                 max_index,
+                                 false)
+        }
+
+        (Aggregator::Max, plan) => {
+            let (plan, plan_type) = summation_preserving((plan, plan_type));
+            VecOperator::maximum(prepare(plan, result),
+                                 grouping_key,
+                                 present,
+                                 output_location,
+                                 plan_type.encoding_type(),
+                                 grouping_type,
+                                 max_index,
+                                 false)
+        }
+
+        (Aggregator::CountDistinct, plan) => {
+            let dictionary_len = plan_type.codec.and_then(|codec| codec.dictionary())
+                .map(|dictionary| dictionary.len())
+                .filter(|&len| shared_dictionary && len <= MAX_DISTINCT_CODES);
+            match dictionary_len {
+                Some(dictionary_len) =>
+                    VecOperator::distinct_codes(prepare(plan, result),
+                                                grouping_key,
+                                                present,
+                                                output_location,
+                                                plan_type.encoding_type(),
+                                                grouping_type,
+                                                max_index,
+                                                dictionary_len,
+                                                false),
+                // Codes of different dictionaries can't be merged, integers are counted exactly by collecting their values
+                None => match plan_type.decoded {
+                    BasicType::Integer => {
+                        let (plan, _) = decode((plan, plan_type));
+                        VecOperator::distinct_values(prepare(plan, result),
+                                                     grouping_key,
+                                                     present,
+                                                     output_location,
+                                                     grouping_type,
+                                                     max_index,
+                                                     false)
+                    }
+                    basic_type => bail!(QueryError::NotImplemented,
+                                        "count(distinct) on {:?} column without a dictionary shared by all batches, use approx_count_distinct instead",
+                                        basic_type),
+                },
+            }
+        }
+
+        (Aggregator::ApproxCountDistinct, plan) => {
+            let (plan, plan_type) = decode((plan, plan_type));
+            VecOperator::hyper_log_log(prepare(plan, result),
+                                       grouping_key,
+                                       present,
+                                       output_location,
+                                       plan_type.encoding_type(),
+                                       grouping_type,
+                                       max_index,
+                                       false)
+        }
+
+        (Aggregator::Percentile(_), plan) => {
+            let (plan, _) = float(decode((plan, plan_type)));
+            VecOperator::t_digest(prepare(plan, result),
+                                  grouping_key,
+                                  present,
+                                  output_location,
+                                  grouping_type,
+                                  max_index,
+                                  false)
+        }
+
+        (Aggregator::Variance, plan) | (Aggregator::Stddev, plan) => {
+            let (plan, _) = numeric(aggregator, (plan, plan_type))?;
+            let input = prepare(plan, result);
+            VecOperator::moments(input,
+                                 input,
+                                 grouping_key,
+                                 present,
+                                 output_location,
+                                 grouping_type,
+                                 max_index,
+                                 false)
+        }
+
+        (Aggregator::Covar, _) | (Aggregator::Corr, _) | (Aggregator::ArgMin, _) | (Aggregator::ArgMax, _) |
+        (Aggregator::First, _) | (Aggregator::Last, _) => bail!(QueryError::TypeError, "{:?} expects two arguments", aggregator),
+
+        (Aggregator::Avg, _) => bail!(QueryError::FatalError, "avg must be computed from partial sum and count"),
     };
     result.push(operation);
     Ok(output_location)
 }
 
+// Aggregates of two arguments, e.g. covar(a, b). Some aggregates consist of multiple partial results.
+pub fn prepare_bivariate_aggregation<'a>(x: (QueryPlan<'a>, Type<'a>),
+                                         y: (QueryPlan<'a>, Type<'a>),
+                                         grouping_key: BufferRef,
+                                         present: Option<BufferRef>,
+                                         grouping_type: EncodingType,
+                                         max_index: usize,
+                                         aggregator: Aggregator,
+                                         result: &mut QueryExecutor<'a>) -> Result<Vec<BufferRef>, QueryError> {
+    match aggregator {
+        Aggregator::Covar | Aggregator::Corr => {
+            let output_location = result.new_buffer();
+            let (x, _) = numeric(aggregator, x)?;
+            let (y, _) = numeric(aggregator, y)?;
+            let operation = VecOperator::moments(prepare(x, result),
+                                                 prepare(y, result),
+                                                 grouping_key,
+                                                 present,
+                                                 output_location,
+                                                 grouping_type,
+                                                 max_index,
+                                                 false);
+            result.push(operation);
+            Ok(vec![output_location])
+        }
+        // Partial results are the key of the selected row and the value of x at that row
+        Aggregator::ArgMin | Aggregator::ArgMax | Aggregator::First | Aggregator::Last => {
+            let (value_plan, value_type) = x;
+            let (key_plan, key_type) = decode(y);
+            match key_type.decoded {
+                BasicType::Integer | BasicType::Float | BasicType::Decimal(_) | BasicType::Timestamp => {}
+                basic_type => bail!(QueryError::TypeError, "{:?} ordered by {:?}, expected number", aggregator, basic_type),
+            }
+            let value = prepare(value_plan, result);
+            let key = prepare(key_plan, result);
+            let (keys, indices, found) = (result.new_buffer(), result.new_buffer(), result.new_buffer());
+            let operation = match aggregator {
+                Aggregator::ArgMin | Aggregator::First =>
+                    VecOperator::arg_minimum(key, grouping_key, present, (keys, indices, found),
+                                             key_type.encoding_type(), grouping_type, max_index, false),
+                _ => VecOperator::arg_maximum(key, grouping_key, present, (keys, indices, found),
+                                              key_type.encoding_type(), grouping_type, max_index, false),
+            };
+            result.push(operation);
+
+            // Values are decoded only for the selected rows
+            let mut values = QueryPlan::Gather(
+                Box::new(QueryPlan::ReadBuffer(value)),
+                value_type.encoding_type(),
+                Box::new(QueryPlan::ReadBuffer(indices)));
+            if let Some(codec) = value_type.codec {
+                values = QueryPlan::DecodeWith(Box::new(values), codec);
+            }
+            let keys = prepare(QueryPlan::Nullable(Box::new(QueryPlan::ReadBuffer(keys)), Box::new(QueryPlan::ReadBuffer(found))), result);
+            let values = prepare(QueryPlan::Nullable(Box::new(values), Box::new(QueryPlan::ReadBuffer(found))), result);
+            Ok(vec![keys, values])
+        }
+        _ => bail!(QueryError::TypeError, "{:?} expects a single argument", aggregator),
+    }
+}
+
+// Converts arguments of statistical aggregates to floats
+fn numeric<'a>(aggregator: Aggregator, typed_plan: (QueryPlan<'a>, Type<'a>)) -> Result<(QueryPlan<'a>, Type<'a>), QueryError> {
+    match typed_plan.1.decoded {
+        BasicType::Integer | BasicType::Float | BasicType::Decimal(_) | BasicType::Timestamp => Ok(float(decode(typed_plan))),
+        basic_type => bail!(QueryError::TypeError, "{:?}({:?}), expected number", aggregator, basic_type),
+    }
+}
+
+pub fn summation_preserving<'a>(typed_plan: (QueryPlan<'a>, Type<'a>)) -> (QueryPlan<'a>, Type<'a>) {
+    if typed_plan.1.is_summation_preserving() {
+        typed_plan
+    } else {
+        (QueryPlan::DecodeWith(Box::new(typed_plan.0), typed_plan.1.codec.unwrap()), typed_plan.1.decoded())
+    }
+}
+
+pub fn decode<'a>(typed_plan: (QueryPlan<'a>, Type<'a>)) -> (QueryPlan<'a>, Type<'a>) {
+    match typed_plan.1.codec {
+        Some(codec) => (QueryPlan::DecodeWith(Box::new(typed_plan.0), codec), typed_plan.1.decoded()),
+        None => typed_plan,
+    }
+}
+
+// Decodes integers and decimals and converts them to floats so they can be combined with float values
+pub fn float<'a>(typed_plan: (QueryPlan<'a>, Type<'a>)) -> (QueryPlan<'a>, Type<'a>) {
+    if typed_plan.1.decoded == BasicType::Float || typed_plan.1.is_scalar {
+        return typed_plan;
+    }
+    let scale = typed_plan.1.decoded.decimal_scale();
+    let (plan, plan_type) = decode(typed_plan);
+    let mut plan = QueryPlan::TypeConversion(Box::new(plan), plan_type.encoding_type(), EncodingType::F64);
+    if let Some(scale) = scale {
+        let divisor = RawVal::Float(OrderedFloat(10f64.powi(i32::from(scale))));
+        plan = QueryPlan::DivideVS(EncodingType::F64, Box::new(plan), Box::new(QueryPlan::Constant(divisor)));
+    }
+    (plan, Type::new(BasicType::Float, None).mutable())
+}
+
+// Converts a constant to the scaled integer representation of a decimal, if this is possible without loss of precision
+fn scale_constant(value: &RawVal, scale: u8) -> Option<i64> {
+    let factor = 10i64.checked_pow(u32::from(scale))?;
+    match *value {
+        RawVal::Int(i) => i.checked_mul(factor),
+        RawVal::Float(f) => {
+            let scaled = f.into_inner() * factor as f64;
+            if scaled.fract() == 0.0 && scaled.abs() < (1u64 << 53) as f64 {
+                Some(scaled as i64)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn comparison_vs<'a>(op: FuncType,
+                     t: EncodingType,
+                     lhs: Box<QueryPlan<'a>>,
+                     rhs: Box<QueryPlan<'a>>) -> Result<QueryPlan<'a>, QueryError> {
+    use self::FuncType::*;
+    Ok(match op {
+        LT => QueryPlan::LessThanVS(t, lhs, rhs),
+        LTE => QueryPlan::LessThanEqualsVS(t, lhs, rhs),
+        GT => QueryPlan::GreaterThanVS(t, lhs, rhs),
+        GTE => QueryPlan::GreaterThanEqualsVS(t, lhs, rhs),
+        Equals => QueryPlan::EqualsVS(t, lhs, rhs),
+        NotEquals => QueryPlan::NotEqualsVS(t, lhs, rhs),
+        _ => bail!(QueryError::FatalError, "{:?} is not a comparison operator", op),
+    })
+}
+
+fn regex_match<'a>(typed_plan: (QueryPlan<'a>, Type<'a>), regex: Regex) -> QueryPlan<'a> {
+    match typed_plan.1.codec {
+        // Evaluate regex once per dictionary entry and then filter on the encoded values
+        Some(codec) if codec.dictionary().is_some() => QueryPlan::CodeInSet(
+            typed_plan.1.encoding_type(),
+            Box::new(typed_plan.0),
+            Box::new(QueryPlan::RegexMatchDictionary(regex, codec))),
+        Some(codec) => QueryPlan::RegexMatch(Box::new(QueryPlan::DecodeWith(Box::new(typed_plan.0), codec)), regex),
+        None => QueryPlan::RegexMatch(Box::new(typed_plan.0), regex),
+    }
+}
+
+fn like_regex(pattern: &str, case_insensitive: bool) -> Regex {
+    let mut regex = String::from(if case_insensitive { "(?is)^" } else { "(?s)^" });
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).unwrap()
+}
+
+// Number of dictionary entries that are smaller than `value`
+fn lower_bound(dictionary: &[Option<String>], value: &str) -> i64 {
+    partition_point(dictionary, |entry| entry < Some(value))
+}
+
+// Number of dictionary entries that are smaller than or equal to `value`
+fn upper_bound(dictionary: &[Option<String>], value: &str) -> i64 {
+    partition_point(dictionary, |entry| entry <= Some(value))
+}
+
+// Index of the first dictionary entry after all entries that start with `prefix`
+fn prefix_end(dictionary: &[Option<String>], prefix: &str) -> i64 {
+    partition_point(dictionary, |entry| entry < Some(prefix) || entry.map_or(false, |s| s.starts_with(prefix)))
+}
+
+// Index of the first entry for which `pred` is false, `pred` must be true for all preceding entries
+fn partition_point<F: Fn(Option<&str>) -> bool>(dictionary: &[Option<String>], pred: F) -> i64 {
+    let index = dictionary
+        .binary_search_by(|entry| if pred(entry.as_ref().map(|s| s.as_str())) { Ordering::Less } else { Ordering::Greater })
+        .unwrap_err();
+    index as i64
+}
+
 pub fn order_preserving<'a>(typed_plan: (QueryPlan<'a>, Type<'a>)) -> (QueryPlan<'a>, Type<'a>) {
     if typed_plan.1.is_order_preserving() {
         typed_plan
@@ -247,67 +752,37 @@ impl<'a> QueryPlan<'a> {
         use self::FuncType::*;
         Ok(match *expr {
             ColName(ref name) => match columns.get::<str>(name.as_ref()) {
-                Some(c) => {
-                    let t = c.data().full_type();
-                    match c.data().to_codec() {
-                        None => (QueryPlan::DecodeColumn(c.data()), t.decoded()),
-                        Some(codec) => (QueryPlan::ReadColumn(codec), t),
-                    }
-                }
+                Some(c) => QueryPlan::read_column(c.data()),
                 None => bail!(QueryError::NotImplemented, "Referencing missing column {}", name)
             }
-            Func(LT, ref lhs, ref rhs) => {
-                let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, columns)?;
-                let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, columns)?;
-                match (type_lhs.decoded, type_rhs.decoded) {
-                    (BasicType::Integer, BasicType::Integer) => {
-                        let plan = if type_rhs.is_scalar {
-                            if type_lhs.is_encoded() {
-                                let encoded = QueryPlan::EncodeIntConstant(Box::new(plan_rhs), type_lhs.codec.unwrap());
-                                QueryPlan::LessThanVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(encoded))
-                            } else {
-                                QueryPlan::LessThanVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(plan_rhs))
-                            }
-                        } else {
-                            bail!(QueryError::NotImplemented, "< operator only implemented for column < constant")
-                        };
-                        (plan, Type::new(BasicType::Boolean, None).mutable())
-                    }
-                    _ => bail!(QueryError::TypeError, "{:?} < {:?}", type_lhs, type_rhs)
-                }
+            Func(op @ LT, ref lhs, ref rhs) | Func(op @ LTE, ref lhs, ref rhs) |
+            Func(op @ GT, ref lhs, ref rhs) | Func(op @ GTE, ref lhs, ref rhs) |
+            Func(op @ Equals, ref lhs, ref rhs) | Func(op @ NotEquals, ref lhs, ref rhs) => {
+                let (plan, plan_type) = QueryPlan::compile_comparison(op, lhs, rhs, columns)?;
+                (QueryPlan::and_present(plan, expr, columns)?, plan_type)
             }
-            Func(Equals, ref lhs, ref rhs) => {
-                let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, columns)?;
-                let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, columns)?;
-                match (type_lhs.decoded, type_rhs.decoded) {
-                    (BasicType::String, BasicType::String) => {
-                        let plan = if type_rhs.is_scalar {
-                            if type_lhs.is_encoded() {
-                                let encoded = QueryPlan::EncodeStrConstant(Box::new(plan_rhs), type_lhs.codec.unwrap());
-                                QueryPlan::EqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(encoded))
-                            } else {
-                                QueryPlan::EqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(plan_rhs))
-                            }
-                        } else {
-                            bail!(QueryError::NotImplemented, "= operator only implemented for column = constant")
-                        };
-                        (plan, Type::new(BasicType::Boolean, None).mutable())
-                    }
-                    (BasicType::Integer, BasicType::Integer) => {
-                        let plan = if type_rhs.is_scalar {
-                            if type_lhs.is_encoded() {
-                                let encoded = QueryPlan::EncodeIntConstant(Box::new(plan_rhs), type_lhs.codec.unwrap());
-                                QueryPlan::EqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(encoded))
-                            } else {
-                                QueryPlan::EqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(plan_rhs))
-                            }
-                        } else {
-                            bail!(QueryError::NotImplemented, "= operator only implemented for column = constant")
-                        };
-                        (plan, Type::new(BasicType::Boolean, None).mutable())
+            In(ref inner, ref values) => {
+                let (plan, plan_type) = QueryPlan::compile_in(inner, values, columns)?;
+                (QueryPlan::and_present(plan, expr, columns)?, plan_type)
+            }
+            Func(IsNull, ref inner, _) | Func(IsNotNull, ref inner, _) => {
+                let present = match QueryPlan::compile_present(inner, columns)? {
+                    Some(present) => present,
+                    // All values are present, read validity of any column to obtain bitmap of correct length
+                    None => {
+                        let mut colnames = HashSet::new();
+                        inner.add_colnames(&mut colnames);
+                        match colnames.iter().filter_map(|name| columns.get::<str>(name.as_ref())).next() {
+                            Some(column) => QueryPlan::ReadPresent(column.data()),
+                            None => bail!(QueryError::NotImplemented, "IS NULL on expression without columns: {:?}", inner),
+                        }
                     }
-                    _ => bail!(QueryError::TypeError, "{:?} = {:?}", type_lhs, type_rhs)
-                }
+                };
+                let plan = match *expr {
+                    Func(IsNull, _, _) => QueryPlan::Not(Box::new(present)),
+                    _ => present,
+                };
+                (plan, Type::bit_vec())
             }
             Func(Or, ref lhs, ref rhs) => {
                 let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, columns)?;
@@ -325,89 +800,641 @@ impl<'a> QueryPlan<'a> {
                 }
                 (QueryPlan::And(Box::new(plan_lhs), Box::new(plan_rhs)), Type::bit_vec())
             }
+            Func(RegexMatch, ref expr, ref pattern) => {
+                let regex = match **pattern {
+                    Const(RawVal::Str(ref pattern)) => match Regex::new(pattern) {
+                        Ok(regex) => regex,
+                        Err(err) => bail!(QueryError::ParseError, "Invalid regex {:?}: {}", pattern, err),
+                    },
+                    _ => bail!(QueryError::TypeError, "Expected string constant as regex pattern, found {:?}", pattern),
+                };
+                let (plan, plan_type) = QueryPlan::create_query_plan(expr, columns)?;
+                if plan_type.decoded != BasicType::String {
+                    bail!(QueryError::TypeError, "regex({:?}, {:?})", plan_type, regex)
+                }
+                (QueryPlan::and_present(regex_match((plan, plan_type), regex), expr, columns)?, Type::bit_vec())
+            }
+            Func(op @ Like, ref expr, ref pattern) | Func(op @ ILike, ref expr, ref pattern) => match **pattern {
+                Const(RawVal::Str(ref pattern)) => {
+                    let (plan, plan_type) = QueryPlan::compile_like(expr, pattern, op == ILike, columns)?;
+                    (QueryPlan::and_present(plan, expr, columns)?, plan_type)
+                }
+                _ => bail!(QueryError::TypeError, "Expected string constant as LIKE pattern, found {:?}", pattern),
+            },
+            Func(op @ Lower, ref inner, _) | Func(op @ Upper, ref inner, _) =>
+                QueryPlan::compile_convert_case(if op == Lower { Case::Lower } else { Case::Upper }, inner, columns)?,
+            Func(TimeBucket, ref expr, ref width) => match **width {
+                Const(RawVal::Int(width)) if width > 0 =>
+                    QueryPlan::compile_time_function(TimeFunction::Bucket(width), expr, columns)?,
+                _ => bail!(QueryError::TypeError, "Expected positive integer constant as bucket width, found {:?}", width),
+            },
+            Func(DateTrunc, ref unit, ref expr) => match **unit {
+                Const(RawVal::Str(ref unit)) => match TimeUnit::parse(unit) {
+                    Some(unit) => QueryPlan::compile_time_function(TimeFunction::Truncate(unit), expr, columns)?,
+                    None => bail!(QueryError::NotImplemented, "date_trunc to unit {:?}", unit),
+                },
+                _ => bail!(QueryError::TypeError, "Expected string constant as date_trunc unit, found {:?}", unit),
+            },
+            Func(Year, ref expr, _) => QueryPlan::compile_time_function(TimeFunction::Year, expr, columns)?,
+            Func(Month, ref expr, _) => QueryPlan::compile_time_function(TimeFunction::Month, expr, columns)?,
+            Func(Day, ref expr, _) => QueryPlan::compile_time_function(TimeFunction::Day, expr, columns)?,
+            Func(Hour, ref expr, _) => QueryPlan::compile_time_function(TimeFunction::Hour, expr, columns)?,
+            Func(Minute, ref expr, _) => QueryPlan::compile_time_function(TimeFunction::Minute, expr, columns)?,
+            Func(Weekday, ref expr, _) => QueryPlan::compile_time_function(TimeFunction::Weekday, expr, columns)?,
+            Func(Not, ref expr, _) => QueryPlan::compile_negation(expr, columns)?,
+            Func(Add, ref lhs, ref rhs) => QueryPlan::compile_arithmetic(Add, lhs, rhs, columns)?,
+            Func(Subtract, ref lhs, ref rhs) => QueryPlan::compile_arithmetic(Subtract, lhs, rhs, columns)?,
+            Func(Multiply, ref lhs, ref rhs) => QueryPlan::compile_arithmetic(Multiply, lhs, rhs, columns)?,
+            Func(Divide, ref lhs, ref rhs) => QueryPlan::compile_arithmetic(Divide, lhs, rhs, columns)?,
+            Func(Negate, ref expr, _) =>
+                QueryPlan::compile_arithmetic(Subtract, &Const(RawVal::Int(0)), expr, columns)?,
             Const(ref v) => (QueryPlan::Constant(v.clone()), Type::scalar(v.get_type())),
             ref x => bail!(QueryError::NotImplemented, "{:?}.compile_vec()", x),
         })
     }
 
-    pub fn compile_grouping_key<'b>(exprs: &[Expr],
-                                    columns: &HashMap<&'b str, &'b Column>) -> Result<(QueryPlan<'b>, Type<'b>, i64, Vec<QueryPlan<'b>>), QueryError> {
-        if exprs.len() == 1 {
-            QueryPlan::create_query_plan(&exprs[0], columns)
-                .map(|(gk_plan, gk_type)| {
-                    let max_cardinality = QueryPlan::encoding_range(&gk_plan).map_or(1 << 63, |i| i.1);
-                    let decoded_group_by = gk_type.codec.map_or(
-                        QueryPlan::EncodedGroupByPlaceholder,
-                        |codec| QueryPlan::DecodeWith(
-                            Box::new(QueryPlan::EncodedGroupByPlaceholder),
-                            codec));
-                    (gk_plan.clone(), gk_type, max_cardinality, vec![decoded_group_by])
-                })
-        } else if exprs.len() == 2 {
-            let mut total_width = 0;
-            let mut largest_key = 0;
-            let mut plan = None;
-            let mut decode_plans = Vec::with_capacity(exprs.len());
-            for expr in exprs.iter().rev() {
-                let (query_plan, plan_type) = QueryPlan::create_query_plan(expr, columns)?;
-                // TODO(clemens): Potentially subtract min if min is negative or this makes grouping key fit into 64 bits
-                if let Some((min, max)) = QueryPlan::encoding_range(&query_plan) {
-                    if min < 0 {
-                        plan = None;
-                        break;
-                    }
-                    let query_plan = QueryPlan::TypeConversion(Box::new(query_plan),
-                                                               plan_type.encoding_type(),
-                                                               EncodingType::I64);
-                    let bits = (max as f64).log2().floor() as i64 + 1;
-                    if total_width == 0 {
-                        plan = Some(query_plan);
-                    } else {
-                        plan = plan.map(|plan|
-                            QueryPlan::BitPack(Box::new(plan), Box::new(query_plan), total_width));
-                    }
+    fn read_column<'b>(data: &'b ColumnData) -> (QueryPlan<'b>, Type<'b>) {
+        let t = data.full_type();
+        match data.to_codec() {
+            None => (QueryPlan::DecodeColumn(data), t.decoded()),
+            Some(codec) => (QueryPlan::ReadColumn(codec), t),
+        }
+    }
 
-                    let mut decode_plan = QueryPlan::BitUnpack(
-                        Box::new(QueryPlan::EncodedGroupByPlaceholder),
-                        total_width as u8,
-                        bits as u8);
-                    decode_plan = QueryPlan::TypeConversion(
-                        Box::new(decode_plan),
-                        EncodingType::I64,
-                        plan_type.encoding_type());
-                    if let Some(codec) = plan_type.codec {
-                        decode_plan = QueryPlan::DecodeWith(
-                            Box::new(decode_plan),
-                            codec)
+    // Dictionary entries are converted instead of the values of each row where possible
+    fn compile_convert_case<'b>(case: Case,
+                                expr: &Expr,
+                                columns: &HashMap<&'b str, &'b Column>) -> Result<(QueryPlan<'b>, Type<'b>), QueryError> {
+        let (plan, plan_type) = QueryPlan::create_query_plan(expr, columns)?;
+        if plan_type.decoded != BasicType::String {
+            bail!(QueryError::TypeError, "{:?}({:?}), expected string", case, plan_type)
+        }
+        if let QueryPlan::Constant(RawVal::Str(ref s)) = plan {
+            return Ok((QueryPlan::Constant(RawVal::Str(case.convert(s))), plan_type));
+        }
+        let plan = match plan_type.codec {
+            Some(codec) if codec.dictionary().is_some() =>
+                QueryPlan::ConvertCaseDictionary(plan_type.encoding_type(), Box::new(plan), case, codec),
+            _ => QueryPlan::ConvertCase(Box::new(decode((plan, plan_type)).0), case),
+        };
+        Ok((plan, Type::new(BasicType::String, None).mutable()))
+    }
+
+    // Integer columns are interpreted as seconds since the Unix epoch
+    fn compile_time_function<'b>(function: TimeFunction,
+                                 expr: &Expr,
+                                 columns: &HashMap<&'b str, &'b Column>) -> Result<(QueryPlan<'b>, Type<'b>), QueryError> {
+        let (plan, plan_type) = decode(QueryPlan::create_query_plan(expr, columns)?);
+        match plan_type.decoded {
+            BasicType::Timestamp | BasicType::Integer if !plan_type.is_scalar => {}
+            _ => bail!(QueryError::TypeError, "{:?}({:?}), expected timestamp", function, plan_type),
+        }
+        let plan = QueryPlan::TimeFunction(plan_type.encoding_type(), Box::new(plan), function);
+        Ok((plan, Type::new(function.result_type(), None).mutable()))
+    }
+
+    // Pushes negations down to the leaves of boolean expressions so that rows with NULL values are excluded by both
+    // `p` and `NOT p` (three-valued logic)
+    fn compile_negation<'b>(expr: &Expr,
+                            columns: &HashMap<&'b str, &'b Column>) -> Result<(QueryPlan<'b>, Type<'b>), QueryError> {
+        use self::Expr::*;
+        use self::FuncType::*;
+        let negated = match *expr {
+            Func(Not, ref inner, _) => return QueryPlan::create_query_plan(inner, columns),
+            Func(And, ref lhs, ref rhs) =>
+                Expr::func(Or, Expr::func(Not, (**lhs).clone(), Const(RawVal::Null)), Expr::func(Not, (**rhs).clone(), Const(RawVal::Null))),
+            Func(Or, ref lhs, ref rhs) =>
+                Expr::func(And, Expr::func(Not, (**lhs).clone(), Const(RawVal::Null)), Expr::func(Not, (**rhs).clone(), Const(RawVal::Null))),
+            Func(LT, ref lhs, ref rhs) => Expr::func(GTE, (**lhs).clone(), (**rhs).clone()),
+            Func(LTE, ref lhs, ref rhs) => Expr::func(GT, (**lhs).clone(), (**rhs).clone()),
+            Func(GT, ref lhs, ref rhs) => Expr::func(LTE, (**lhs).clone(), (**rhs).clone()),
+            Func(GTE, ref lhs, ref rhs) => Expr::func(LT, (**lhs).clone(), (**rhs).clone()),
+            Func(Equals, ref lhs, ref rhs) => Expr::func(NotEquals, (**lhs).clone(), (**rhs).clone()),
+            Func(NotEquals, ref lhs, ref rhs) => Expr::func(Equals, (**lhs).clone(), (**rhs).clone()),
+            Func(IsNull, ref inner, ref rhs) => Expr::func(IsNotNull, (**inner).clone(), (**rhs).clone()),
+            Func(IsNotNull, ref inner, ref rhs) => Expr::func(IsNull, (**inner).clone(), (**rhs).clone()),
+            _ => {
+                let (plan, plan_type) = QueryPlan::create_query_plan(expr, columns)?;
+                if plan_type.decoded != BasicType::Boolean {
+                    bail!(QueryError::TypeError, "Found NOT {:?}, expected NOT bool", plan_type)
+                }
+                return Ok((QueryPlan::and_present(QueryPlan::Not(Box::new(plan)), expr, columns)?, Type::bit_vec()));
+            }
+        };
+        QueryPlan::create_query_plan(&negated, columns)
+    }
+
+    // Returns plan that computes which rows have non-null values for all columns referenced by `expr`, or `None` if
+    // none of the columns contain null values
+    pub fn compile_present<'b>(expr: &Expr, columns: &HashMap<&'b str, &'b Column>) -> Result<Option<QueryPlan<'b>>, QueryError> {
+        let mut colnames = HashSet::new();
+        // Division by zero produces null
+        let mut divisors = Vec::new();
+        expr.add_null_sources(&mut colnames, &mut divisors);
+        let mut colnames = colnames.into_iter().collect::<Vec<_>>();
+        colnames.sort();
+        let mut nonzero = Vec::with_capacity(divisors.len());
+        for divisor in divisors {
+            nonzero.push(match divisor {
+                // Constant divisors are zero, which makes every row null
+                Expr::Const(_) => match columns.values().next() {
+                    Some(column) => QueryPlan::EqualsVS(EncodingType::I64,
+                                                        Box::new(QueryPlan::Zeros(column.data())),
+                                                        Box::new(QueryPlan::Constant(RawVal::Int(1)))),
+                    None => continue,
+                },
+                divisor => {
+                    let divisor_nonzero = Expr::func(FuncType::NotEquals, divisor, Expr::Const(RawVal::Int(0)));
+                    QueryPlan::create_query_plan(&divisor_nonzero, columns)?.0
+                }
+            });
+        }
+        Ok(colnames.iter()
+            .filter_map(|name| columns.get::<str>(name.as_ref()))
+            .filter(|column| column.data().present().is_some())
+            .map(|column| QueryPlan::ReadPresent(column.data()))
+            .chain(nonzero)
+            .fold(None, |acc, present| match acc {
+                None => Some(present),
+                Some(acc) => Some(QueryPlan::And(Box::new(acc), Box::new(present))),
+            }))
+    }
+
+    fn and_present<'b>(plan: QueryPlan<'b>, expr: &Expr, columns: &HashMap<&'b str, &'b Column>) -> Result<QueryPlan<'b>, QueryError> {
+        Ok(match QueryPlan::compile_present(expr, columns)? {
+            Some(present) => QueryPlan::And(Box::new(plan), Box::new(present)),
+            None => plan,
+        })
+    }
+
+    fn compile_in<'b>(expr: &Expr,
+                      values: &[RawVal],
+                      columns: &HashMap<&'b str, &'b Column>) -> Result<(QueryPlan<'b>, Type<'b>), QueryError> {
+        use self::Expr::*;
+        use self::FuncType::*;
+        let (plan, plan_type) = QueryPlan::create_query_plan(expr, columns)?;
+        Ok(match plan_type.codec {
+            // Look up all values in the dictionary once and then filter on the encoded values
+            Some(codec) if codec.dictionary().is_some() => {
+                let mut set = HashSet::with_capacity(values.len());
+                for value in values {
+                    match *value {
+                        RawVal::Str(ref s) => { set.insert(s.clone()); }
+                        ref v => bail!(QueryError::TypeError, "{:?} IN (.., {}, ..)", plan_type, v),
                     }
-                    decode_plans.push(decode_plan);
+                }
+                (QueryPlan::CodeInSet(plan_type.encoding_type(), Box::new(plan), Box::new(QueryPlan::InSetDictionary(set, codec))),
+                 Type::bit_vec())
+            }
+            _ => {
+                let mut disjunction = Expr::func(Equals, expr.clone(), Const(values[0].clone()));
+                for value in &values[1..] {
+                    disjunction = Expr::func(Or, disjunction, Expr::func(Equals, expr.clone(), Const(value.clone())));
+                }
+                QueryPlan::create_query_plan(&disjunction, columns)?
+            }
+        })
+    }
 
-                    largest_key += max << total_width;
-                    total_width += bits;
+    fn compile_comparison<'b>(op: FuncType,
+                              lhs: &Expr,
+                              rhs: &Expr,
+                              columns: &HashMap<&'b str, &'b Column>) -> Result<(QueryPlan<'b>, Type<'b>), QueryError> {
+        use self::FuncType::*;
+        let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, columns)?;
+        let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, columns)?;
+        // Normalize `constant op column` to `column op' constant`
+        let (op, plan_lhs, type_lhs, plan_rhs, type_rhs) = if type_lhs.is_scalar && !type_rhs.is_scalar {
+            let flipped = match op {
+                LT => GT,
+                LTE => GTE,
+                GT => LT,
+                GTE => LTE,
+                op => op,
+            };
+            (flipped, plan_rhs, type_rhs, plan_lhs, type_lhs)
+        } else {
+            (op, plan_lhs, type_lhs, plan_rhs, type_rhs)
+        };
+        if type_lhs.is_scalar {
+            bail!(QueryError::NotImplemented, "{:?} between two constants", op)
+        }
+        if !type_rhs.is_scalar {
+            return QueryPlan::compile_comparison_vv(op, (plan_lhs, type_lhs), (plan_rhs, type_rhs));
+        }
+        // Decimals are compared in scaled units, or as floats if the constant has more digits than the decimal
+        let (plan_rhs, type_rhs, compare_as_float) = match (type_lhs.decoded, &plan_rhs) {
+            (BasicType::Decimal(scale), &QueryPlan::Constant(ref value)) => match scale_constant(value, scale) {
+                Some(scaled) => (QueryPlan::Constant(RawVal::Int(scaled)), Type::scalar(BasicType::Integer), false),
+                None => (plan_rhs, type_rhs, true),
+            },
+            (BasicType::Timestamp, &QueryPlan::Constant(RawVal::Str(ref value))) => match parse_timestamp(value) {
+                Some(timestamp) => (QueryPlan::Constant(RawVal::Int(timestamp)), Type::scalar(BasicType::Integer), false),
+                None => bail!(QueryError::TypeError, "Failed to parse {:?} as timestamp", value),
+            },
+            _ => (plan_rhs, type_rhs, false),
+        };
+        if type_lhs.decoded == BasicType::String && type_rhs.decoded == BasicType::String && op != Equals && op != NotEquals {
+            return QueryPlan::compile_string_comparison(op, (plan_lhs, type_lhs), &plan_rhs);
+        }
+        let (plan_lhs, type_lhs) = if type_rhs.decoded == BasicType::Float || compare_as_float {
+            float((plan_lhs, type_lhs))
+        } else {
+            (plan_lhs, type_lhs)
+        };
+        let plan_rhs = match (type_lhs.decoded, type_rhs.decoded) {
+            (BasicType::Integer, BasicType::Integer) | (BasicType::Decimal(_), BasicType::Integer) |
+            (BasicType::Timestamp, BasicType::Integer) => match type_lhs.codec {
+                Some(codec) => QueryPlan::EncodeIntConstant(Box::new(plan_rhs), codec),
+                None => plan_rhs,
+            },
+            (BasicType::Float, BasicType::Float) | (BasicType::Float, BasicType::Integer) => plan_rhs,
+            (BasicType::String, BasicType::String) if op == Equals || op == NotEquals => match type_lhs.codec {
+                Some(codec) => QueryPlan::EncodeStrConstant(Box::new(plan_rhs), codec),
+                None => plan_rhs,
+            },
+            _ => bail!(QueryError::TypeError, "{:?} {:?} {:?}", type_lhs, op, type_rhs),
+        };
+        let t = type_lhs.encoding_type();
+        let plan = match plan_lhs {
+            QueryPlan::ReadColumn(codec) if codec.runs().is_some() => {
+                let fallback = comparison_vs(op, t, Box::new(QueryPlan::ReadColumn(codec)), Box::new(plan_rhs.clone()))?;
+                QueryPlan::CompareRuns(op, codec, Box::new(plan_rhs), Box::new(fallback))
+            }
+            _ => comparison_vs(op, t, Box::new(plan_lhs), Box::new(plan_rhs))?,
+        };
+        Ok((plan, Type::bit_vec()))
+    }
+
+    // Dictionaries are sorted, so order comparisons on dictionary encoded strings become comparisons of codes
+    fn compile_string_comparison<'b>(op: FuncType,
+                                     lhs: (QueryPlan<'b>, Type<'b>),
+                                     rhs: &QueryPlan<'b>) -> Result<(QueryPlan<'b>, Type<'b>), QueryError> {
+        use self::FuncType::*;
+        let value = match *rhs {
+            QueryPlan::Constant(RawVal::Str(ref value)) => value,
+            ref plan => bail!(QueryError::NotImplemented, "{:?} {:?} {:?}", lhs.1, op, plan),
+        };
+        let plan = match lhs.1.codec.and_then(|codec| codec.dictionary()) {
+            Some(dictionary) => {
+                let (op, code) = match op {
+                    LT => (LT, lower_bound(dictionary, value)),
+                    LTE => (LT, upper_bound(dictionary, value)),
+                    GT => (GTE, upper_bound(dictionary, value)),
+                    _ => (GTE, lower_bound(dictionary, value)),
+                };
+                comparison_vs(op, lhs.1.encoding_type(), Box::new(lhs.0), Box::new(QueryPlan::Constant(RawVal::Int(code))))?
+            }
+            None => {
+                let (plan, plan_type) = decode(lhs);
+                comparison_vs(op, plan_type.encoding_type(), Box::new(plan), Box::new(rhs.clone()))?
+            }
+        };
+        Ok((plan, Type::bit_vec()))
+    }
+
+    // `%` matches any sequence of characters and `_` any single character
+    fn compile_like<'b>(expr: &Expr,
+                        pattern: &str,
+                        case_insensitive: bool,
+                        columns: &HashMap<&'b str, &'b Column>) -> Result<(QueryPlan<'b>, Type<'b>), QueryError> {
+        let is_wildcard = |c| c == '%' || c == '_';
+        let prefix = match pattern.find(is_wildcard) {
+            _ if case_insensitive => None,
+            None => return QueryPlan::compile_comparison(FuncType::Equals, expr, &Expr::Const(RawVal::Str(pattern.to_string())), columns),
+            Some(index) if index == pattern.len() - 1 && pattern.ends_with('%') => Some(&pattern[..index]),
+            Some(_) => None,
+        };
+        let (plan, plan_type) = QueryPlan::create_query_plan(expr, columns)?;
+        if plan_type.decoded != BasicType::String {
+            bail!(QueryError::TypeError, "{:?} LIKE {:?}", plan_type, pattern)
+        }
+        let plan = match (prefix, plan_type.codec.and_then(|codec| codec.dictionary())) {
+            // Strings with a common prefix form a contiguous range in the sorted dictionary
+            (Some(prefix), Some(dictionary)) => {
+                let t = plan_type.encoding_type();
+                let start = QueryPlan::Constant(RawVal::Int(lower_bound(dictionary, prefix)));
+                let end = QueryPlan::Constant(RawVal::Int(prefix_end(dictionary, prefix)));
+                QueryPlan::And(
+                    Box::new(QueryPlan::GreaterThanEqualsVS(t, Box::new(plan.clone()), Box::new(start))),
+                    Box::new(QueryPlan::LessThanVS(t, Box::new(plan), Box::new(end))))
+            }
+            _ => regex_match((plan, plan_type), like_regex(pattern, case_insensitive)),
+        };
+        Ok((plan, Type::bit_vec()))
+    }
+
+    fn compile_comparison_vv<'b>(op: FuncType,
+                                 lhs: (QueryPlan<'b>, Type<'b>),
+                                 rhs: (QueryPlan<'b>, Type<'b>)) -> Result<(QueryPlan<'b>, Type<'b>), QueryError> {
+        use self::FuncType::*;
+        let (lhs, rhs) = match (lhs.1.decoded, rhs.1.decoded) {
+            (BasicType::Integer, BasicType::Integer) | (BasicType::String, BasicType::String) => (lhs, rhs),
+            (BasicType::Decimal(l), BasicType::Decimal(r)) if l == r => (lhs, rhs),
+            (BasicType::Timestamp, BasicType::Timestamp) | (BasicType::Timestamp, BasicType::Integer) |
+            (BasicType::Integer, BasicType::Timestamp) => (lhs, rhs),
+            (BasicType::Float, BasicType::Float) | (BasicType::Float, BasicType::Integer) |
+            (BasicType::Integer, BasicType::Float) => (float(lhs), float(rhs)),
+            (BasicType::Decimal(_), BasicType::Integer) | (BasicType::Decimal(_), BasicType::Float) |
+            (BasicType::Decimal(_), BasicType::Decimal(_)) | (BasicType::Integer, BasicType::Decimal(_)) |
+            (BasicType::Float, BasicType::Decimal(_)) => (float(lhs), float(rhs)),
+            _ => bail!(QueryError::TypeError, "{:?} {:?} {:?}", lhs.1, op, rhs.1),
+        };
+        // Values that share a codec can be compared without decoding them, provided the codec preserves ordering
+        let compare_encoded = lhs.1.has_same_codec(&rhs.1) &&
+            (op == Equals || op == NotEquals || lhs.1.is_order_preserving());
+        let ((plan_lhs, type_lhs), (plan_rhs, _)) = if compare_encoded {
+            (lhs, rhs)
+        } else {
+            (decode(lhs), decode(rhs))
+        };
+        let t = type_lhs.encoding_type();
+        let (lhs, rhs) = (Box::new(plan_lhs), Box::new(plan_rhs));
+        let plan = match op {
+            LT => QueryPlan::LessThanVV(t, lhs, rhs),
+            LTE => QueryPlan::LessThanEqualsVV(t, lhs, rhs),
+            GT => QueryPlan::LessThanVV(t, rhs, lhs),
+            GTE => QueryPlan::LessThanEqualsVV(t, rhs, lhs),
+            Equals => QueryPlan::EqualsVV(t, lhs, rhs),
+            NotEquals => QueryPlan::NotEqualsVV(t, lhs, rhs),
+            _ => bail!(QueryError::FatalError, "{:?} is not a comparison operator", op),
+        };
+        Ok((plan, Type::bit_vec()))
+    }
+
+    fn compile_arithmetic<'b>(op: FuncType,
+                              lhs: &Expr,
+                              rhs: &Expr,
+                              columns: &HashMap<&'b str, &'b Column>) -> Result<(QueryPlan<'b>, Type<'b>), QueryError> {
+        use self::FuncType::*;
+        let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, columns)?;
+        let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, columns)?;
+        let numeric = |t: &Type| match t.decoded {
+            BasicType::Integer | BasicType::Float | BasicType::Decimal(_) | BasicType::Timestamp => true,
+            _ => false,
+        };
+        if !numeric(&type_lhs) || !numeric(&type_rhs) {
+            bail!(QueryError::TypeError, "{:?} {:?} {:?}", type_lhs, op, type_rhs)
+        }
+        // Seconds can be added to or subtracted from timestamps, the difference of two timestamps is in seconds
+        let timestamp_result = match (op, type_lhs.decoded, type_rhs.decoded) {
+            (_, BasicType::Integer, BasicType::Integer) => None,
+            (Add, BasicType::Timestamp, BasicType::Integer) | (Add, BasicType::Integer, BasicType::Timestamp) |
+            (Subtract, BasicType::Timestamp, BasicType::Integer) => Some(BasicType::Timestamp),
+            (Subtract, BasicType::Timestamp, BasicType::Timestamp) => Some(BasicType::Integer),
+            (_, BasicType::Timestamp, _) | (_, _, BasicType::Timestamp) =>
+                bail!(QueryError::TypeError, "{:?} {:?} {:?}", type_lhs, op, type_rhs),
+            _ => None,
+        };
+        // Decimals stay scaled integers when added to decimals of the same scale or to integer constants, and when
+        // multiplied or divided by integers. All other combinations involving decimals are computed as floats.
+        let integer = |t: &Type| t.decoded == BasicType::Integer;
+        let integer_constant = |t: &Type| integer(t) && t.is_scalar;
+        let scale = match (op, type_lhs.decoded.decimal_scale(), type_rhs.decoded.decimal_scale()) {
+            (Add, Some(l), Some(r)) | (Subtract, Some(l), Some(r)) if l == r => Some(l),
+            (Add, Some(s), None) | (Subtract, Some(s), None) if integer_constant(&type_rhs) => Some(s),
+            (Add, None, Some(s)) | (Subtract, None, Some(s)) if integer_constant(&type_lhs) => Some(s),
+            (Multiply, Some(s), None) | (Divide, Some(s), None) if integer(&type_rhs) => Some(s),
+            (Multiply, None, Some(s)) if integer(&type_lhs) => Some(s),
+            _ => None,
+        };
+        let (plan_lhs, plan_rhs) = match (op, scale) {
+            (Add, Some(s)) | (Subtract, Some(s)) => {
+                let rescale = |plan: QueryPlan<'b>| match plan {
+                    QueryPlan::Constant(ref value) => match scale_constant(value, s) {
+                        Some(scaled) => Ok(QueryPlan::Constant(RawVal::Int(scaled))),
+                        None => bail!(QueryError::TypeError, "Integer overflow in {:?} {:?} {:?}", lhs, op, rhs),
+                    },
+                    plan => Ok(plan),
+                };
+                (rescale(plan_lhs)?, rescale(plan_rhs)?)
+            }
+            _ => (plan_lhs, plan_rhs),
+        };
+        let is_decimal = |t: &Type| t.decoded.decimal_scale().is_some();
+        let is_float = type_lhs.decoded == BasicType::Float || type_rhs.decoded == BasicType::Float ||
+            scale.is_none() && (is_decimal(&type_lhs) || is_decimal(&type_rhs));
+        let ((plan_lhs, type_lhs), (plan_rhs, type_rhs)) = if is_float {
+            (float((plan_lhs, type_lhs)), float((plan_rhs, type_rhs)))
+        } else {
+            // Arithmetic can operate directly on encoded values unless the codec shifts them (e.g. offset encoding)
+            (summation_preserving((plan_lhs, type_lhs)), summation_preserving((plan_rhs, type_rhs)))
+        };
+        let (lhs, rhs) = (Box::new(plan_lhs), Box::new(plan_rhs));
+        let plan = match (type_lhs.is_scalar, type_rhs.is_scalar) {
+            (true, true) if is_float => {
+                let value = |plan: &QueryPlan| match *plan {
+                    QueryPlan::Constant(RawVal::Int(i)) => i as f64,
+                    QueryPlan::Constant(RawVal::Float(f)) => f.into_inner(),
+                    _ => unreachable!(),
+                };
+                let (l, r) = (value(&*lhs), value(&*rhs));
+                if op == Divide && r == 0.0 {
+                    return Ok((QueryPlan::Constant(RawVal::Null), Type::scalar(BasicType::Null)));
+                }
+                let folded = match op {
+                    Add => l + r,
+                    Subtract => l - r,
+                    Multiply => l * r,
+                    Divide => l / r,
+                    _ => bail!(QueryError::FatalError, "{:?} is not an arithmetic operator", op),
+                };
+                return Ok((QueryPlan::Constant(RawVal::Float(OrderedFloat(folded))), Type::scalar(BasicType::Float)));
+            }
+            (true, true) => {
+                if let (Divide, &QueryPlan::Constant(RawVal::Int(0))) = (op, &*rhs) {
+                    return Ok((QueryPlan::Constant(RawVal::Null), Type::scalar(BasicType::Null)));
+                }
+                let folded = match (&*lhs, &*rhs) {
+                    (&QueryPlan::Constant(RawVal::Int(l)), &QueryPlan::Constant(RawVal::Int(r))) => match op {
+                        Add => l.checked_add(r),
+                        Subtract => l.checked_sub(r),
+                        Multiply => l.checked_mul(r),
+                        Divide => l.checked_div(r),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                match folded {
+                    Some(value) => return Ok((QueryPlan::Constant(RawVal::Int(value)), Type::scalar(BasicType::Integer))),
+                    None => bail!(QueryError::TypeError, "Integer overflow in {:?} {:?} {:?}", lhs, op, rhs),
+                }
+            }
+            (false, true) => {
+                // All rows are masked as null by `compile_present`, the divisor only has to be replaced to avoid
+                // dividing by zero
+                let zero_divisor = op == Divide && match *rhs {
+                    QueryPlan::Constant(ref value) => value.is_zero(),
+                    _ => false,
+                };
+                let rhs = if !zero_divisor {
+                    rhs
+                } else if is_float {
+                    Box::new(QueryPlan::Constant(RawVal::Float(OrderedFloat(1.0))))
                 } else {
-                    plan = None;
-                    break;
+                    Box::new(QueryPlan::Constant(RawVal::Int(1)))
+                };
+                let t = type_lhs.encoding_type();
+                match op {
+                    Add => QueryPlan::AddVS(t, lhs, rhs),
+                    Subtract => QueryPlan::SubtractVS(t, lhs, rhs),
+                    Multiply => QueryPlan::MultiplyVS(t, lhs, rhs),
+                    Divide => QueryPlan::DivideVS(t, lhs, rhs),
+                    _ => bail!(QueryError::FatalError, "{:?} is not an arithmetic operator", op),
                 }
             }
-
-            if let Some(plan) = plan {
-                if total_width <= 64 {
-                    decode_plans.reverse();
-                    return Ok((plan, Type::new(BasicType::Integer, None), largest_key, decode_plans));
+            (true, false) => {
+                let t = type_rhs.encoding_type();
+                match op {
+                    Add => QueryPlan::AddVS(t, rhs, lhs),
+                    Subtract => QueryPlan::SubtractSV(t, lhs, rhs),
+                    Multiply => QueryPlan::MultiplyVS(t, rhs, lhs),
+                    Divide => QueryPlan::DivideSV(t, lhs, rhs),
+                    _ => bail!(QueryError::FatalError, "{:?} is not an arithmetic operator", op),
+                }
+            }
+            (false, false) => {
+                let (lt, rt) = (type_lhs.encoding_type(), type_rhs.encoding_type());
+                match op {
+                    Add => QueryPlan::Add(lt, rt, lhs, rhs),
+                    Subtract => QueryPlan::Subtract(lt, rt, lhs, rhs),
+                    Multiply => QueryPlan::Multiply(lt, rt, lhs, rhs),
+                    Divide => QueryPlan::Divide(lt, rt, lhs, rhs),
+                    _ => bail!(QueryError::FatalError, "{:?} is not an arithmetic operator", op),
                 }
             }
-            // TODO(clemens): add u8, u16, u32, u128 grouping keys
-            // TODO(clemens): implement general case using bites slice as grouping key
-            bail!(QueryError::NotImplemented, "Failed to pack group by columns into 64 bit value")
+        };
+        let basic_type = match (timestamp_result, scale) {
+            (Some(basic_type), _) => basic_type,
+            _ if is_float => BasicType::Float,
+            (None, Some(scale)) => BasicType::Decimal(scale),
+            (None, None) => BasicType::Integer,
+        };
+        Ok((plan, Type::new(basic_type, None).mutable()))
+    }
+
+    pub fn compile_grouping_key<'b>(exprs: &[Expr],
+                                    columns: &HashMap<&'b str, &'b Column>) -> Result<GroupingKey<'b>, QueryError> {
+        // Without GROUP BY all rows form a single group. Its key is kept as hidden grouping column so that results
+        // of different batches can be merged like any other groups.
+        if exprs.is_empty() {
+            return match columns.values().next() {
+                Some(column) => Ok(GroupingKey::Packed(QueryPlan::Zeros(column.data()),
+                                                       Type::new(BasicType::Integer, None),
+                                                       0,
+                                                       vec![QueryPlan::EncodedGroupByPlaceholder])),
+                None => bail!(QueryError::NotImplemented, "Aggregation without any columns"),
+            };
+        }
+        let mut plans = Vec::with_capacity(exprs.len());
+        let mut nullable = Vec::with_capacity(exprs.len());
+        for expr in exprs {
+            // Nullable expressions are grouped by an additional column that is 1 for null values
+            let present = QueryPlan::compile_present(expr, columns)?;
+            nullable.push(present.is_some());
+            if let Some(present) = present {
+                let is_null = QueryPlan::TypeConversion(
+                    Box::new(QueryPlan::Not(Box::new(present))), EncodingType::BitVec, EncodingType::I64);
+                plans.push((is_null, Type::new(BasicType::Integer, None)));
+            }
+            plans.push(QueryPlan::create_query_plan(expr, columns)?);
+        }
+
+        let grouping_key = QueryPlan::compile_grouping_key_plans(plans)?;
+        if nullable.iter().any(|&n| n) {
+            Ok(GroupingKey::Nullable(Box::new(grouping_key), nullable))
         } else {
-            bail!(QueryError::NotImplemented, "Can only group by one or two columns. Actual: {}", exprs.len())
+            Ok(grouping_key)
+        }
+    }
+
+    fn compile_grouping_key_plans<'b>(mut plans: Vec<(QueryPlan<'b>, Type<'b>)>) -> Result<GroupingKey<'b>, QueryError> {
+        if plans.len() == 1 {
+            let range = plans[0].0.encoding_range();
+            let is_integer = match plans[0].1.encoding_type() {
+                EncodingType::U8 | EncodingType::U16 | EncodingType::U32 | EncodingType::I64 => true,
+                _ => false,
+            };
+            // Negative values are offset by packing into grouping key
+            if is_integer && range.map_or(true, |(min, _)| min >= 0) {
+                let (gk_plan, gk_type) = plans.pop().unwrap();
+                let max_cardinality = range.map_or(i64::MAX, |i| i.1);
+                let decoded_group_by = gk_type.codec.map_or(
+                    QueryPlan::EncodedGroupByPlaceholder,
+                    |codec| QueryPlan::DecodeWith(
+                        Box::new(QueryPlan::EncodedGroupByPlaceholder),
+                        codec));
+                return Ok(GroupingKey::Packed(gk_plan, gk_type, max_cardinality, vec![decoded_group_by]));
+            }
+        }
+
+        if let Some(packed) = QueryPlan::pack_grouping_key(&plans) {
+            return Ok(packed);
+        }
+        // Byte representation of values must be order preserving to allow merging of sorted groups
+        let plans = plans.into_iter().map(order_preserving).collect::<Vec<_>>();
+        for &(_, ref plan_type) in &plans {
+            if !aggregation_operator::is_byte_slice_groupable(plan_type.encoding_type()) {
+                bail!(QueryError::NotImplemented, "grouping by column of type {:?}", plan_type.encoding_type())
+            }
+        }
+        Ok(GroupingKey::Rows(plans))
+    }
+
+    fn pack_grouping_key<'b>(plans: &[(QueryPlan<'b>, Type<'b>)]) -> Option<GroupingKey<'b>> {
+        let mut total_width = 0;
+        let mut largest_key = 0;
+        let mut packed_plan = None;
+        let mut decode_plans = Vec::with_capacity(plans.len());
+        for &(ref plan, ref plan_type) in plans.iter().rev() {
+            let (min, max) = plan.encoding_range()?;
+            let bits = 64 - i64::from(max.checked_sub(min)?.leading_zeros());
+            // Leave sign bit unused to keep grouping key positive
+            if total_width + bits > 63 {
+                return None;
+            }
+
+            let encoding_type = plan_type.encoding_type();
+            let mut plan = plan.clone();
+            if encoding_type != EncodingType::I64 {
+                plan = QueryPlan::TypeConversion(Box::new(plan), encoding_type, EncodingType::I64);
+            }
+            if min != 0 {
+                plan = QueryPlan::AddVS(EncodingType::I64, Box::new(plan), Box::new(QueryPlan::Constant(RawVal::Int(-min))));
+            }
+            packed_plan = Some(match packed_plan {
+                None => plan,
+                Some(packed) => QueryPlan::BitPack(Box::new(packed), Box::new(plan), total_width),
+            });
+
+            let mut decode_plan = QueryPlan::BitUnpack(
+                Box::new(QueryPlan::EncodedGroupByPlaceholder),
+                total_width as u8,
+                bits as u8);
+            if min != 0 {
+                decode_plan = QueryPlan::AddVS(EncodingType::I64, Box::new(decode_plan), Box::new(QueryPlan::Constant(RawVal::Int(min))));
+            }
+            if encoding_type != EncodingType::I64 {
+                decode_plan = QueryPlan::TypeConversion(Box::new(decode_plan), EncodingType::I64, encoding_type);
+            }
+            if let Some(codec) = plan_type.codec {
+                decode_plan = QueryPlan::DecodeWith(Box::new(decode_plan), codec)
+            }
+            decode_plans.push(decode_plan);
+
+            largest_key += (max - min) << total_width;
+            total_width += bits;
         }
+        decode_plans.reverse();
+        Some(GroupingKey::Packed(packed_plan?, Type::new(BasicType::Integer, None), largest_key, decode_plans))
     }
 
     fn encoding_range(&self) -> Option<(i64, i64)> {
         use self::QueryPlan::*;
         match *self {
             ReadColumn(codec) => codec.encoding_range(),
+            TypeConversion(_, EncodingType::BitVec, _) => Some((0, 1)),
+            Zeros(_) => Some((0, 0)),
             _ => None, // TODO(clemens): many more cases where we can determine range
         }
     }
@@ -11,9 +11,13 @@ use QueryResult;
 use engine::aggregator::*;
 use engine::batch_merging::*;
 use engine::query::Query;
+use engine::time_function::format_timestamp;
+use engine::types::BasicType;
+use engine::vector_op::StringArena;
 use ingest::raw_val::RawVal;
 use mem_store::batch::Batch;
 use mem_store::column::Column;
+use ordered_float::OrderedFloat;
 use scheduler::*;
 use syntax::expression::*;
 use time::precise_time_ns;
@@ -22,7 +26,11 @@ use time::precise_time_ns;
 pub struct QueryTask {
     query: Query,
     batches: Vec<Batch>,
+    // Columns derived from the referenced columns of each batch, dropped together with the query
+    derived: Vec<Vec<Column>>,
+    strings: StringArena,
     referenced_cols: HashSet<String>,
+    shared_dictionaries: HashSet<String>,
     output_colnames: Vec<String>,
     aggregate: Vec<Aggregator>,
     start_time_ns: u64,
@@ -68,29 +76,34 @@ impl Default for QueryStats {
 
 
 impl QueryTask {
-    pub fn new(mut query: Query, source: Vec<Batch>, sender: SharedSender<QueryResult>) -> QueryTask {
+    pub fn new(mut query: Query, source: Vec<Batch>, sender: SharedSender<QueryResult>) -> Result<QueryTask, QueryError> {
         let start_time_ns = precise_time_ns();
         if query.is_select_star() {
             query.select = find_all_cols(&source).into_iter().map(Expr::ColName).collect();
         }
 
         let output_colnames = query.result_column_names();
-        let mut order_by_index = None;
-        if let Some(ref col) = query.order_by {
-            for (i, name) in output_colnames.iter().enumerate() {
-                if name == col {
-                    order_by_index = Some(i);
-                }
+        query.resolve_order_by(&output_colnames)?;
+        let mut referenced_cols = query.find_referenced_cols();
+        // Aggregates that don't reference any columns (e.g. `count(1)`) still need a column to count the rows of each batch
+        if referenced_cols.is_empty() && !query.aggregate.is_empty() {
+            if let Some(column) = source.get(0).and_then(|batch| batch.cols().get(0)) {
+                referenced_cols.insert(column.name().to_string());
             }
         }
-        query.order_by_index = order_by_index;
-        let referenced_cols = query.find_referenced_cols();
         let aggregate = query.aggregate.iter().map(|&(aggregate, _)| aggregate).collect();
+        let shared_dictionaries = find_shared_dictionaries(&source);
+        let derived = source.iter()
+            .map(|batch| derive_columns(batch, &referenced_cols))
+            .collect();
 
-        QueryTask {
+        Ok(QueryTask {
             query,
             batches: source,
+            derived,
+            strings: StringArena::default(),
             referenced_cols,
+            shared_dictionaries,
             output_colnames,
             aggregate,
             start_time_ns,
@@ -104,7 +117,7 @@ impl QueryTask {
             batch_index: AtomicUsize::new(0),
             completed: AtomicBool::new(false),
             sender,
-        }
+        })
     }
 
     pub fn run(&self) {
@@ -114,11 +127,11 @@ impl QueryTask {
         while let Some((batch, id)) = self.next_batch() {
             trace_start!("Batch {}", id);
             rows_scanned += batch.cols().get(0).map_or(0, |c| c.len());
-            let batch = QueryTask::prepare_batch(&self.referenced_cols, batch);
+            let batch = QueryTask::prepare_batch(&self.referenced_cols, batch, &self.derived[id]);
             let mut batch_result = match if self.aggregate.is_empty() {
-                self.query.run(&batch)
+                self.query.run(&batch, &self.strings)
             } else {
-                self.query.run_aggregate(&batch)
+                self.query.run_aggregate(&batch, &self.shared_dictionaries, &self.strings)
             } {
                 Ok(result) => result,
                 Err(error) => {
@@ -131,7 +144,13 @@ impl QueryTask {
             // Merge only with previous batch results of same level to get O(n log n) complexity
             while let Some(br) = batch_results.pop() {
                 if br.level == batch_result.level {
-                    batch_result = combine(br, batch_result, self.combined_limit());
+                    batch_result = match combine(br, batch_result, self.combined_limit()) {
+                        Ok(result) => result,
+                        Err(error) => {
+                            self.fail_with(error);
+                            return;
+                        }
+                    };
                 } else {
                     batch_results.push(br);
                     break;
@@ -147,21 +166,23 @@ impl QueryTask {
             }
         }
 
-        if let Some(result) = QueryTask::combine_results(batch_results, self.combined_limit()) {
-            self.push_result(result, rows_scanned, rows_collected);
+        match QueryTask::combine_results(batch_results, self.combined_limit()) {
+            Ok(Some(result)) => self.push_result(result, rows_scanned, rows_collected),
+            Ok(None) => {}
+            Err(error) => self.fail_with(error),
         }
     }
 
-    fn combine_results(batch_results: Vec<BatchResult>, limit: usize) -> Option<BatchResult> {
+    fn combine_results(batch_results: Vec<BatchResult>, limit: usize) -> Result<Option<BatchResult>, QueryError> {
         let mut full_result = None;
         for batch_result in batch_results {
             if let Some(partial) = full_result {
-                full_result = Some(combine(partial, batch_result, limit));
+                full_result = Some(combine(partial, batch_result, limit)?);
             } else {
                 full_result = Some(batch_result);
             }
         }
-        full_result
+        Ok(full_result)
     }
 
     fn push_result(&self, result: BatchResult, rows_scanned: usize, rows_collected: usize) {
@@ -177,10 +198,17 @@ impl QueryTask {
         if state.completed_batches == self.batches.len() || self.sufficient_rows(state.rows_collected) {
             let mut owned_results = Vec::with_capacity(0);
             mem::swap(&mut owned_results, &mut state.partial_results);
-            // TODO(clemens): Handle empty table
-            let full_result = QueryTask::combine_results(owned_results, self.combined_limit()).unwrap();
-            let final_result = self.convert_to_output_format(&full_result, state.rows_scanned);
-            self.sender.send(Ok(final_result));
+            let rows_scanned = state.rows_scanned;
+            let final_result = QueryTask::combine_results(owned_results, self.combined_limit())
+                .and_then(|full_result| {
+                    // TODO(clemens): Handle empty table
+                    let mut full_result = full_result.unwrap();
+                    if !self.aggregate.is_empty() {
+                        full_result = full_result.finalize_aggregates(&self.query.aggregate);
+                    }
+                    self.convert_to_output_format(&full_result, rows_scanned)
+                });
+            self.sender.send(final_result);
             self.completed.store(true, Ordering::SeqCst);
         }
     }
@@ -194,7 +222,7 @@ impl QueryTask {
     }
 
     fn sufficient_rows(&self, rows_collected: usize) -> bool {
-        let unordered_select = self.query.aggregate.is_empty() && self.query.order_by.is_none();
+        let unordered_select = self.query.aggregate.is_empty() && self.query.order_by.is_empty();
         unordered_select && self.combined_limit() < rows_collected
     }
 
@@ -205,38 +233,89 @@ impl QueryTask {
 
     fn convert_to_output_format(&self,
                                 full_result: &BatchResult,
-                                rows_scanned: usize) -> QueryOutput {
+                                rows_scanned: usize) -> Result<QueryOutput, QueryError> {
         let limit = self.query.limit.limit as usize;
         let offset = self.query.limit.offset as usize;
         let mut result_rows = Vec::new();
-        let count = cmp::min(limit, full_result.len() - offset);
-        for i in offset..(count + offset) {
+        // Grouped results are sorted only after all groups are merged
+        let sort_rows = full_result.group_by.is_some() && !self.query.order_by_index.is_empty();
+        let mut skipped = 0;
+        for i in 0..full_result.len() {
+            if !sort_rows && result_rows.len() >= limit { break; }
             let mut record = Vec::with_capacity(self.output_colnames.len());
             if let Some(ref gs) = full_result.group_by {
-                for g in gs {
+                for g in gs.iter().take(self.query.select.len()) {
                     record.push(g.get_raw(i));
                 }
             }
             for col in &full_result.select {
                 record.push(col.get_raw(i));
             }
+            let record = record.into_iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    let value = match (value, full_result.dictionaries.get(index)) {
+                        (RawVal::Int(code), Some(&Some(dictionary))) => output_string(&dictionary[code as usize]),
+                        (value, _) => value,
+                    };
+                    match full_result.column_types.get(index) {
+                        Some(&basic_type) => output_value(value, basic_type),
+                        None => value,
+                    }
+                })
+                .collect::<Vec<_>>();
+            if !self.query.satisfies_having(&record)? { continue; }
+            if !sort_rows && skipped < offset {
+                skipped += 1;
+                continue;
+            }
             result_rows.push(record);
         }
 
-        QueryOutput {
+        // Aggregates without GROUP BY return a single row even if no rows were selected
+        if full_result.len() == 0 && self.query.select.is_empty() && !self.aggregate.is_empty() {
+            let record = self.aggregate.iter()
+                .map(|aggregator| match *aggregator {
+                    Aggregator::Count | Aggregator::CountDistinct | Aggregator::ApproxCountDistinct => RawVal::Int(0),
+                    _ => RawVal::Null,
+                })
+                .collect::<Vec<_>>();
+            if offset == 0 && self.query.satisfies_having(&record)? {
+                result_rows.push(record);
+            }
+        }
+
+        if sort_rows {
+            let order_by = &self.query.order_by_index;
+            result_rows.sort_by(|r1, r2| order_by.iter().fold(cmp::Ordering::Equal, |ordering, &(index, desc)| {
+                ordering.then_with(|| if desc { r2[index].cmp(&r1[index]) } else { r1[index].cmp(&r2[index]) })
+            }));
+            result_rows = result_rows.into_iter().skip(offset).take(limit).collect();
+        }
+        if let Some(ref projection) = self.query.projection {
+            for record in &mut result_rows {
+                *record = projection.iter().map(|&i| record[i].clone()).collect();
+            }
+        }
+
+        Ok(QueryOutput {
             colnames: self.output_colnames.clone(),
             rows: result_rows,
             stats: QueryStats {
                 runtime_ns: precise_time_ns() - self.start_time_ns,
                 rows_scanned,
             }
-        }
+        })
     }
 
-    fn prepare_batch<'a>(referenced_cols: &'a HashSet<String>, source: &'a Batch) -> HashMap<&'a str, &'a Column> {
+    fn prepare_batch<'a>(referenced_cols: &'a HashSet<String>,
+                         source: &'a Batch,
+                         derived: &'a [Column]) -> HashMap<&'a str, &'a Column> {
         trace_start!("prepare_batch");
+        // Copies of compressed columns replace the original columns
         source.cols().iter()
             .filter(|col| referenced_cols.contains(col.name()))
+            .chain(derived)
             .map(|col| (col.name(), col))
             .collect()
     }
@@ -255,6 +334,56 @@ impl Task for QueryTask {
     fn multithreaded(&self) -> bool { true }
 }
 
+// Converts the integer representation of decimals and timestamps into the value they represent
+fn output_value(value: RawVal, basic_type: BasicType) -> RawVal {
+    match (value, basic_type) {
+        (RawVal::Int(scaled), BasicType::Decimal(scale)) =>
+            RawVal::Float(OrderedFloat(scaled as f64 / 10f64.powi(i32::from(scale)))),
+        (RawVal::Int(timestamp), BasicType::Timestamp) => RawVal::Str(format_timestamp(timestamp)),
+        // Averages of decimals and timestamps are computed on the scaled integer representation
+        (RawVal::Float(scaled), BasicType::Decimal(scale)) =>
+            RawVal::Float(OrderedFloat(scaled.0 / 10f64.powi(i32::from(scale)))),
+        (RawVal::Float(timestamp), BasicType::Timestamp) => RawVal::Str(format_timestamp(timestamp.0 as i64)),
+        (value, _) => value,
+    }
+}
+
+fn output_string(value: &Option<String>) -> RawVal {
+    match *value {
+        Some(ref string) => RawVal::Str(string.clone()),
+        None => RawVal::Null,
+    }
+}
+
+// Copies of compressed columns that decompress them on demand
+fn derive_columns(batch: &Batch, referenced_cols: &HashSet<String>) -> Vec<Column> {
+    batch.cols().iter()
+        .filter(|col| referenced_cols.contains(col.name()))
+        .filter_map(|col| col.data().decompressing_copy().map(|data| Column::new(col.name().to_string(), data)))
+        .collect()
+}
+
+// Columns whose codec holds the same shared dictionary in every batch
+fn find_shared_dictionaries(source: &[Batch]) -> HashSet<String> {
+    let mut dictionaries = HashMap::new();
+    for batch in source {
+        for column in batch.cols() {
+            let dictionary = column.data().to_codec()
+                .filter(|codec| codec.has_shared_dictionary())
+                .and_then(|codec| codec.dictionary())
+                .map(|dictionary| dictionary.as_ptr());
+            let shared = dictionaries.entry(column.name()).or_insert(dictionary);
+            if *shared != dictionary {
+                *shared = None;
+            }
+        }
+    }
+    dictionaries.into_iter()
+        .filter(|&(_, dictionary)| dictionary.is_some())
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
 fn find_all_cols(source: &[Batch]) -> Vec<String> {
     let mut cols = HashSet::new();
     for batch in source {
@@ -0,0 +1,177 @@
+use std::f64;
+
+use ordered_float::OrderedFloat;
+
+// t-digests summarize a distribution by clusters (centroids) of adjacent values. Centroids near the tails are kept
+// small, so extreme quantiles like p99 stay accurate while the digest is bounded in size. Digests of different batches
+// are merged by combining their centroids.
+
+const COMPRESSION: f64 = 100.0;
+// Adjacent centroids always span more than one unit of the scale function, which ranges over COMPRESSION / 2 units
+const MAX_CENTROIDS: usize = COMPRESSION as usize + 2;
+// Number of centroids, min and max, followed by mean and weight of each centroid
+pub const SKETCH_BYTES: usize = 24 + 16 * MAX_CENTROIDS;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TDigest {
+    centroids: Vec<Centroid>,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn from_values(values: &mut [f64]) -> TDigest {
+        values.sort_by_key(|&v| OrderedFloat(v));
+        let centroids = values.iter().map(|&v| Centroid { mean: v, weight: 1.0 }).collect();
+        TDigest {
+            centroids: compress(centroids),
+            min: values.first().cloned().unwrap_or(f64::INFINITY),
+            max: values.last().cloned().unwrap_or(f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn merge(&self, other: &TDigest) -> TDigest {
+        let mut centroids = Vec::with_capacity(self.centroids.len() + other.centroids.len());
+        centroids.extend_from_slice(&self.centroids);
+        centroids.extend_from_slice(&other.centroids);
+        centroids.sort_by_key(|c| OrderedFloat(c.mean));
+        // Empty digests (e.g. zeroed sketches of groups without values) don't have meaningful bounds
+        let nonempty = [self, other];
+        let nonempty = nonempty.iter().filter(|digest| !digest.centroids.is_empty());
+        TDigest {
+            centroids: compress(centroids),
+            min: nonempty.clone().map(|digest| digest.min).fold(f64::INFINITY, f64::min),
+            max: nonempty.map(|digest| digest.max).fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+
+    // Interpolates between the centers of centroids, which lie at the midpoint of their cumulative weight
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.centroids.is_empty() { return None; }
+        let total = self.centroids.iter().map(|c| c.weight).sum::<f64>();
+        let target = q * total;
+        let mut previous = (0.0, self.min);
+        let mut cumulative = 0.0;
+        for c in &self.centroids {
+            let center = cumulative + c.weight / 2.0;
+            if target < center {
+                return Some(interpolate(previous, (center, c.mean), target));
+            }
+            previous = (center, c.mean);
+            cumulative += c.weight;
+        }
+        Some(interpolate(previous, (total, self.max), target))
+    }
+
+    pub fn read(bytes: &[u8]) -> TDigest {
+        let len = read_f64(bytes, 0) as usize;
+        let centroids = (0..len)
+            .map(|i| Centroid {
+                mean: read_f64(bytes, 24 + 16 * i),
+                weight: read_f64(bytes, 32 + 16 * i),
+            })
+            .collect();
+        TDigest {
+            centroids,
+            min: read_f64(bytes, 8),
+            max: read_f64(bytes, 16),
+        }
+    }
+
+    pub fn write(&self, bytes: &mut [u8]) {
+        assert!(self.centroids.len() <= MAX_CENTROIDS, "t-digest with {} centroids", self.centroids.len());
+        write_f64(bytes, 0, self.centroids.len() as f64);
+        write_f64(bytes, 8, self.min);
+        write_f64(bytes, 16, self.max);
+        for (i, c) in self.centroids.iter().enumerate() {
+            write_f64(bytes, 24 + 16 * i, c.mean);
+            write_f64(bytes, 32 + 16 * i, c.weight);
+        }
+    }
+}
+
+// Merges adjacent centroids as long as the merged centroid spans at most one unit of the scale function
+fn compress(centroids: Vec<Centroid>) -> Vec<Centroid> {
+    let total = centroids.iter().map(|c| c.weight).sum::<f64>();
+    let mut result: Vec<Centroid> = Vec::with_capacity(MAX_CENTROIDS);
+    let mut weight_before = 0.0;
+    let mut k_lower = scale(0.0);
+    for c in centroids {
+        let merge = match result.last() {
+            Some(last) => scale((weight_before + last.weight + c.weight) / total) - k_lower <= 1.0,
+            None => false,
+        };
+        if merge {
+            let last = result.last_mut().unwrap();
+            let weight = last.weight + c.weight;
+            last.mean += (c.mean - last.mean) * c.weight / weight;
+            last.weight = weight;
+        } else {
+            if let Some(last) = result.last() {
+                weight_before += last.weight;
+                k_lower = scale(weight_before / total);
+            }
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn scale(q: f64) -> f64 {
+    COMPRESSION / (2.0 * f64::consts::PI) * (2.0 * q - 1.0).min(1.0).asin()
+}
+
+fn interpolate((x0, y0): (f64, f64), (x1, y1): (f64, f64), x: f64) -> f64 {
+    if x1 <= x0 { y1 } else { y0 + (x - x0) / (x1 - x0) * (y1 - y0) }
+}
+
+pub fn read_f64(bytes: &[u8], offset: usize) -> f64 {
+    let mut bits = 0u64;
+    for i in 0..8 {
+        bits |= u64::from(bytes[offset + i]) << (8 * i);
+    }
+    f64::from_bits(bits)
+}
+
+pub fn write_f64(bytes: &mut [u8], offset: usize, value: f64) {
+    let bits = value.to_bits();
+    for i in 0..8 {
+        bytes[offset + i] = (bits >> (8 * i)) as u8;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles() {
+        let mut small = vec![5.0, 1.0, 4.0, 2.0, 3.0];
+        let digest = TDigest::from_values(&mut small);
+        assert_eq!(digest.quantile(0.0), Some(1.0));
+        assert_eq!(digest.quantile(0.5), Some(3.0));
+        assert_eq!(digest.quantile(1.0), Some(5.0));
+        assert_eq!(TDigest::from_values(&mut []).quantile(0.5), None);
+        assert_eq!(TDigest::read(&[0u8; SKETCH_BYTES]).merge(&digest).quantile(0.0), Some(1.0));
+
+        let mut left = (0..50_000).map(|i| f64::from(i * 2)).collect::<Vec<_>>();
+        let mut right = (0..50_000).map(|i| f64::from(i * 2 + 1)).collect::<Vec<_>>();
+        let merged = TDigest::from_values(&mut left).merge(&TDigest::from_values(&mut right));
+        assert!(merged.centroids.len() <= MAX_CENTROIDS);
+        for &(q, expected) in &[(0.5, 50_000.0), (0.9, 90_000.0), (0.99, 99_000.0), (0.999, 99_900.0)] {
+            let estimate = merged.quantile(q).unwrap();
+            assert!((estimate - expected).abs() < 500.0, "{} {}", q, estimate);
+        }
+
+        let mut bytes = vec![0u8; SKETCH_BYTES];
+        merged.write(&mut bytes);
+        assert_eq!(TDigest::read(&bytes), merged);
+    }
+}
@@ -0,0 +1,134 @@
+use chrono::prelude::*;
+
+use engine::types::BasicType;
+
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+// The Unix epoch was a Thursday, weeks start on Monday
+const WEEK_OFFSET: i64 = 3 * DAY;
+
+// Functions of timestamps in seconds since the Unix epoch, evaluated in UTC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeFunction {
+    // Rounds down to a multiple of the given number of seconds
+    Bucket(i64),
+    // Rounds down to the start of the given calendar unit
+    Truncate(TimeUnit),
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    // ISO 8601 day of the week, from 1 for Monday to 7 for Sunday
+    Weekday,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeUnit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl TimeFunction {
+    pub fn apply(&self, timestamp: i64) -> i64 {
+        match *self {
+            TimeFunction::Bucket(width) => floor(timestamp, width),
+            TimeFunction::Truncate(TimeUnit::Second) => timestamp,
+            TimeFunction::Truncate(TimeUnit::Minute) => floor(timestamp, MINUTE),
+            TimeFunction::Truncate(TimeUnit::Hour) => floor(timestamp, HOUR),
+            TimeFunction::Truncate(TimeUnit::Day) => floor(timestamp, DAY),
+            TimeFunction::Truncate(TimeUnit::Week) => floor(timestamp + WEEK_OFFSET, WEEK) - WEEK_OFFSET,
+            TimeFunction::Truncate(TimeUnit::Month) => {
+                let date = date_time(timestamp);
+                NaiveDate::from_ymd(date.year(), date.month(), 1).and_hms(0, 0, 0).timestamp()
+            }
+            TimeFunction::Truncate(TimeUnit::Year) =>
+                NaiveDate::from_ymd(date_time(timestamp).year(), 1, 1).and_hms(0, 0, 0).timestamp(),
+            TimeFunction::Year => i64::from(date_time(timestamp).year()),
+            TimeFunction::Month => i64::from(date_time(timestamp).month()),
+            TimeFunction::Day => i64::from(date_time(timestamp).day()),
+            TimeFunction::Hour => modulo(timestamp, DAY) / HOUR,
+            TimeFunction::Minute => modulo(timestamp, HOUR) / MINUTE,
+            TimeFunction::Weekday => modulo(timestamp + WEEK_OFFSET, WEEK) / DAY + 1,
+        }
+    }
+
+    pub fn result_type(&self) -> BasicType {
+        match *self {
+            TimeFunction::Bucket(_) | TimeFunction::Truncate(_) => BasicType::Timestamp,
+            _ => BasicType::Integer,
+        }
+    }
+}
+
+impl TimeUnit {
+    pub fn parse(unit: &str) -> Option<TimeUnit> {
+        Some(match unit.to_lowercase().as_ref() {
+            "second" => TimeUnit::Second,
+            "minute" => TimeUnit::Minute,
+            "hour" => TimeUnit::Hour,
+            "day" => TimeUnit::Day,
+            "week" => TimeUnit::Week,
+            "month" => TimeUnit::Month,
+            "year" => TimeUnit::Year,
+            _ => return None,
+        })
+    }
+
+    // Length of the unit in seconds, `None` for units of varying length
+    pub fn seconds(&self) -> Option<i64> {
+        match *self {
+            TimeUnit::Second => Some(1),
+            TimeUnit::Minute => Some(MINUTE),
+            TimeUnit::Hour => Some(HOUR),
+            TimeUnit::Day => Some(DAY),
+            TimeUnit::Week => Some(WEEK),
+            TimeUnit::Month | TimeUnit::Year => None,
+        }
+    }
+}
+
+// Formats timestamp in the same format that is accepted when loading timestamp columns
+pub fn format_timestamp(timestamp: i64) -> String {
+    date_time(timestamp).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn date_time(timestamp: i64) -> NaiveDateTime {
+    NaiveDateTime::from_timestamp_opt(timestamp, 0)
+        .unwrap_or_else(|| NaiveDateTime::from_timestamp(0, 0))
+}
+
+fn floor(value: i64, width: i64) -> i64 {
+    value - modulo(value, width)
+}
+
+fn modulo(value: i64, width: i64) -> i64 {
+    ((value % width) + width) % width
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendar_functions() {
+        // 2018-03-04 05:06:07, a Sunday
+        let timestamp = 1520139967;
+        assert_eq!(format_timestamp(TimeFunction::Truncate(TimeUnit::Week).apply(timestamp)), "2018-02-26 00:00:00");
+        assert_eq!(format_timestamp(TimeFunction::Truncate(TimeUnit::Month).apply(timestamp)), "2018-03-01 00:00:00");
+        assert_eq!(format_timestamp(TimeFunction::Bucket(HOUR).apply(timestamp)), "2018-03-04 05:00:00");
+        assert_eq!(TimeFunction::Weekday.apply(timestamp), 7);
+        assert_eq!(TimeFunction::Hour.apply(timestamp), 5);
+        assert_eq!(TimeFunction::Day.apply(timestamp), 4);
+        assert_eq!(TimeFunction::Bucket(DAY).apply(-1), -DAY);
+    }
+}
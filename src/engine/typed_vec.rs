@@ -5,9 +5,10 @@ use std::string;
 
 use bit_vec::BitVec;
 use engine::types::*;
-use heapsize::HeapSizeOf;
 use ingest::raw_val::RawVal;
+use heapsize::HeapSizeOf;
 use num::PrimInt;
+use ordered_float::OrderedFloat;
 
 
 pub type BoxedVec<'a> = Box<TypedVec<'a> + 'a>;
@@ -17,6 +18,7 @@ pub trait TypedVec<'a>: Send {
     fn len(&self) -> usize;
     fn get_raw(&self, i: usize) -> RawVal;
     fn get_type(&self) -> EncodingType;
+    // Sorts are stable, which allows sorting by multiple keys with one sort per key
     fn sort_indices_desc(&self, indices: &mut Vec<usize>);
     fn sort_indices_asc(&self, indices: &mut Vec<usize>);
     fn type_error(&self, func_name: &str) -> String;
@@ -28,8 +30,10 @@ pub trait TypedVec<'a>: Send {
     fn cast_ref_u32<'b>(&'b self) -> &[u32] { panic!(self.type_error("cast_ref_u32")) }
     fn cast_ref_u16<'b>(&'b self) -> &[u16] { panic!(self.type_error("cast_ref_u16")) }
     fn cast_ref_u8<'b>(&'b self) -> &[u8] { panic!(self.type_error("cast_ref_u8")) }
+    fn cast_ref_f64(&self) -> &[OrderedFloat<f64>] { panic!(self.type_error("cast_ref_f64")) }
     fn cast_str_const(&self) -> string::String { panic!(self.type_error("cast_str_const")) }
     fn cast_i64_const(&self) -> i64 { panic!(self.type_error("cast_str_const")) }
+    fn cast_f64_const(&self) -> OrderedFloat<f64> { panic!(self.type_error("cast_f64_const")) }
 
     fn cast_ref_mut_str<'b>(&'b mut self) -> &'b mut [&'a str] { panic!(self.type_error("cast_ref_mut_str")) }
     fn cast_ref_mut_usize(&mut self) -> &mut [usize] { panic!(self.type_error("cast_ref_mut_usize")) }
@@ -37,9 +41,13 @@ pub trait TypedVec<'a>: Send {
     fn cast_ref_mut_u32(&mut self) -> &mut [u32] { panic!(self.type_error("cast_ref_mut_u32")) }
     fn cast_ref_mut_u16(&mut self) -> &mut [u16] { panic!(self.type_error("cast_ref_mut_u16")) }
     fn cast_ref_mut_u8(&mut self) -> &mut [u8] { panic!(self.type_error("cast_ref_mut_u8")) }
+    fn cast_ref_mut_f64(&mut self) -> &mut [OrderedFloat<f64>] { panic!(self.type_error("cast_ref_mut_f64")) }
 
     fn cast_ref_mut_bit_vec(&mut self) -> &mut BitVec { panic!(self.type_error("cast_ref_mut_bit_vec")) }
     fn cast_ref_bit_vec(&self) -> &BitVec { panic!(self.type_error("cast_ref_bit_vec")) }
+
+    // Bitmap that is unset for null values, `None` if the vector does not contain nulls
+    fn present(&self) -> Option<&BitVec> { None }
 }
 
 impl<'a> TypedVec<'a> {
@@ -48,6 +56,17 @@ impl<'a> TypedVec<'a> {
     pub fn bit_vec(value: BitVec) -> BoxedVec<'a> { Box::new(value) }
     pub fn constant(value: RawVal) -> BoxedVec<'a> { Box::new(value) }
     pub fn empty(length: usize) -> BoxedVec<'a> { Box::new(length) }
+    pub fn nullable(data: BoxedVec<'a>, present: BitVec) -> BoxedVec<'a> { Box::new(NullableVec { data, present }) }
+
+    // Wraps vector with all values present if it is not nullable already
+    pub fn make_nullable(data: BoxedVec<'a>) -> BoxedVec<'a> {
+        if data.present().is_some() {
+            data
+        } else {
+            let present = BitVec::from_elem(data.len(), true);
+            TypedVec::nullable(data, present)
+        }
+    }
 }
 
 
@@ -56,10 +75,10 @@ impl<'a, T: VecType<T> + 'a> TypedVec<'a> for Vec<T> {
     fn get_raw(&self, i: usize) -> RawVal { T::wrap_one(self[i]) }
     fn get_type(&self) -> EncodingType { T::t() }
     fn sort_indices_desc(&self, indices: &mut Vec<usize>) {
-        indices.sort_unstable_by(|i, j| self[*i].cmp(&self[*j]).reverse());
+        indices.sort_by(|i, j| self[*i].cmp(&self[*j]).reverse());
     }
     fn sort_indices_asc(&self, indices: &mut Vec<usize>) {
-        indices.sort_unstable_by_key(|i| self[*i]);
+        indices.sort_by_key(|i| self[*i]);
     }
 
     fn type_error(&self, func_name: &str) -> String { format!("Vec<{:?}>.{}", T::t(), func_name) }
@@ -101,15 +120,20 @@ impl<'a> TypedVec<'a> for Vec<u8> {
     fn cast_ref_mut_u8(&mut self) -> &mut [u8] { self }
 }
 
+impl<'a> TypedVec<'a> for Vec<OrderedFloat<f64>> {
+    fn cast_ref_f64(&self) -> &[OrderedFloat<f64>] { self }
+    fn cast_ref_mut_f64(&mut self) -> &mut [OrderedFloat<f64>] { self }
+}
+
 impl<'a, 'b, T: VecType<T> + 'a> TypedVec<'a> for &'b [T] {
     fn len(&self) -> usize { <[T]>::len(self) }
     fn get_raw(&self, i: usize) -> RawVal { T::wrap_one(self[i]) }
     fn get_type(&self) -> EncodingType { T::t() }
     fn sort_indices_desc(&self, indices: &mut Vec<usize>) {
-        indices.sort_unstable_by(|i, j| self[*i].cmp(&self[*j]).reverse());
+        indices.sort_by(|i, j| self[*i].cmp(&self[*j]).reverse());
     }
     fn sort_indices_asc(&self, indices: &mut Vec<usize>) {
-        indices.sort_unstable_by_key(|i| self[*i]);
+        indices.sort_by_key(|i| self[*i]);
     }
 
     fn type_error(&self, func_name: &str) -> String { format!("[{:?}].{}", T::t(), func_name) }
@@ -144,6 +168,10 @@ impl<'a, 'c> TypedVec<'a> for &'c [u8] {
     fn cast_ref_u8<'b>(&'b self) -> &'b [u8] { self }
 }
 
+impl<'a, 'c> TypedVec<'a> for &'c [OrderedFloat<f64>] {
+    fn cast_ref_f64<'b>(&'b self) -> &'b [OrderedFloat<f64>] { self }
+}
+
 impl<'a> TypedVec<'a> for BitVec {
     fn len(&self) -> usize { BitVec::len(self) }
     fn get_raw(&self, _i: usize) -> RawVal { panic!("BitVec.get_raw") }
@@ -157,12 +185,51 @@ impl<'a> TypedVec<'a> for BitVec {
 }
 
 
+// Values at positions that are unset in `present` are null, all other operations are forwarded to `data`
+pub struct NullableVec<'a> {
+    data: BoxedVec<'a>,
+    present: BitVec,
+}
+
+impl<'a> TypedVec<'a> for NullableVec<'a> {
+    fn len(&self) -> usize { self.data.len() }
+    fn get_raw(&self, i: usize) -> RawVal {
+        if self.present[i] { self.data.get_raw(i) } else { RawVal::Null }
+    }
+    fn get_type(&self) -> EncodingType { self.data.get_type() }
+    fn sort_indices_desc(&self, indices: &mut Vec<usize>) { self.data.sort_indices_desc(indices) }
+    fn sort_indices_asc(&self, indices: &mut Vec<usize>) { self.data.sort_indices_asc(indices) }
+    fn type_error(&self, func_name: &str) -> String { format!("Nullable<{}>", self.data.type_error(func_name)) }
+
+    fn extend(&mut self, other: BoxedVec<'a>, count: usize) -> Option<BoxedVec<'a>> {
+        match other.present() {
+            Some(present) => Extend::extend(&mut self.present, present.iter()),
+            None => self.present.grow(other.len(), true),
+        }
+        if let Some(data) = self.data.extend(other, count) {
+            self.data = data;
+        }
+        None
+    }
+
+    fn cast_ref_str<'b>(&'b self) -> &'b [&'a str] { self.data.cast_ref_str() }
+    fn cast_ref_usize(&self) -> &[usize] { self.data.cast_ref_usize() }
+    fn cast_ref_i64(&self) -> &[i64] { self.data.cast_ref_i64() }
+    fn cast_ref_u32<'b>(&'b self) -> &[u32] { self.data.cast_ref_u32() }
+    fn cast_ref_u16<'b>(&'b self) -> &[u16] { self.data.cast_ref_u16() }
+    fn cast_ref_u8<'b>(&'b self) -> &[u8] { self.data.cast_ref_u8() }
+    fn cast_ref_f64(&self) -> &[OrderedFloat<f64>] { self.data.cast_ref_f64() }
+
+    fn present(&self) -> Option<&BitVec> { Some(&self.present) }
+}
+
 impl<'a> TypedVec<'a> for usize {
     fn len(&self) -> usize { *self }
     fn get_raw(&self, i: usize) -> RawVal { panic!("EmptyVector.get_raw") }
     fn get_type(&self) -> EncodingType { panic!("EmptyVector.get_type") }
-    fn sort_indices_desc(&self, _indices: &mut Vec<usize>) { panic!("EmptyVector.sort_indices_desc") }
-    fn sort_indices_asc(&self, _indices: &mut Vec<usize>) { panic!("EmptyVector.sort_indices_asc") }
+    // All values are null and compare equal
+    fn sort_indices_desc(&self, _indices: &mut Vec<usize>) {}
+    fn sort_indices_asc(&self, _indices: &mut Vec<usize>) {}
     fn type_error(&self, func_name: &str) -> String { format!("EmptyVector.{}", func_name) }
     fn extend(&mut self, _other: BoxedVec<'a>, count: usize) -> Option<BoxedVec<'a>> { panic!("EmptyVector.extend") }
 }
@@ -188,9 +255,16 @@ impl<'a> TypedVec<'a> for RawVal {
             _ => panic!("{}.cast_i64_const", &self),
         }
     }
+    fn cast_f64_const(&self) -> OrderedFloat<f64> {
+        match self {
+            RawVal::Float(f) => *f,
+            RawVal::Int(i) => OrderedFloat(*i as f64),
+            _ => panic!("{}.cast_f64_const", &self),
+        }
+    }
 }
 
-pub trait VecType<T>: PartialEq + Ord + Copy + Debug + Sync + Send + HeapSizeOf {
+pub trait VecType<T>: PartialEq + Ord + Copy + Debug + Sync + Send {
     fn unwrap<'a, 'b>(vec: &'b TypedVec<'a>) -> &'b [T] where T: 'a;
     fn unwrap_mut<'a, 'b>(vec: &'b mut TypedVec<'a>) -> &'b mut [T] where T: 'a;
     fn wrap_one(_value: T) -> RawVal { panic!("Can't wrap scalar of type {:?}", Self::t()) }
@@ -223,6 +297,13 @@ impl VecType<i64> for i64 {
     fn t() -> EncodingType { EncodingType::I64 }
 }
 
+impl VecType<OrderedFloat<f64>> for OrderedFloat<f64> {
+    fn unwrap<'a, 'b>(vec: &'b TypedVec<'a>) -> &'b [OrderedFloat<f64>] where OrderedFloat<f64>: 'a { vec.cast_ref_f64() }
+    fn unwrap_mut<'a, 'b>(vec: &'b mut TypedVec<'a>) -> &'b mut [OrderedFloat<f64>] where OrderedFloat<f64>: 'a { vec.cast_ref_mut_f64() }
+    fn wrap_one(value: OrderedFloat<f64>) -> RawVal { RawVal::Float(value) }
+    fn t() -> EncodingType { EncodingType::F64 }
+}
+
 impl VecType<usize> for usize {
     fn unwrap<'a, 'b>(vec: &'b TypedVec<'a>) -> &'b [usize] where usize: 'a { vec.cast_ref_usize() }
     fn unwrap_mut<'a, 'b>(vec: &'b mut TypedVec<'a>) -> &'b mut [usize] where usize: 'a { vec.cast_ref_mut_usize() }
@@ -251,9 +332,9 @@ impl<'c> VecType<&'c str> for &'c str {
 }
 
 
-pub trait IntVecType<T>: VecType<T> + Into<i64> + IntoUsize + PrimInt + Hash + 'static {}
+pub trait IntVecType<T>: VecType<T> + Into<i64> + IntoUsize + PrimInt + Hash + HeapSizeOf + 'static {}
 
-impl<T> IntVecType<T> for T where T: VecType<T> + Into<i64> + IntoUsize + PrimInt + Copy + Hash + 'static {}
+impl<T> IntVecType<T> for T where T: VecType<T> + Into<i64> + IntoUsize + PrimInt + Copy + Hash + HeapSizeOf + 'static {}
 
 pub trait ConstType<T> {
     fn unwrap(vec: &TypedVec) -> T;
@@ -263,6 +344,10 @@ impl ConstType<i64> for i64 {
     fn unwrap(vec: &TypedVec) -> i64 { vec.cast_i64_const() }
 }
 
+impl ConstType<OrderedFloat<f64>> for OrderedFloat<f64> {
+    fn unwrap(vec: &TypedVec) -> OrderedFloat<f64> { vec.cast_f64_const() }
+}
+
 impl ConstType<String> for String {
     fn unwrap(vec: &TypedVec) -> String { vec.cast_str_const() }
 }
@@ -1,10 +1,11 @@
 use mem_store::column::ColumnCodec;
 
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum EncodingType {
     Str,
     I64,
+    F64,
     USize,
     Val,
     Null,
@@ -20,6 +21,11 @@ pub enum EncodingType {
 pub enum BasicType {
     String,
     Integer,
+    Float,
+    // Fixed point number stored as integer scaled by 10^scale
+    Decimal(u8),
+    // Seconds since the Unix epoch
+    Timestamp,
     Val,
     Null,
     Boolean,
@@ -30,11 +36,21 @@ impl BasicType {
         match *self {
             BasicType::String => EncodingType::Str,
             BasicType::Integer => EncodingType::I64,
+            BasicType::Float => EncodingType::F64,
+            BasicType::Decimal(_) => EncodingType::I64,
+            BasicType::Timestamp => EncodingType::I64,
             BasicType::Val => EncodingType::Val,
             BasicType::Null => EncodingType::Null,
             BasicType::Boolean => EncodingType::BitVec,
         }
     }
+
+    pub fn decimal_scale(&self) -> Option<u8> {
+        match *self {
+            BasicType::Decimal(scale) => Some(scale),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -63,6 +79,14 @@ impl<'a> Type<'a> {
         self.codec.is_some()
     }
 
+    pub fn has_same_codec(&self, other: &Type) -> bool {
+        match (self.codec, other.codec) {
+            (Some(c1), Some(c2)) => c1 as *const ColumnCodec as *const u8 == c2 as *const ColumnCodec as *const u8,
+            (None, None) => self.encoding_type() == other.encoding_type(),
+            _ => false,
+        }
+    }
+
     pub fn is_summation_preserving(&self) -> bool {
         self.codec.map_or(true, |c| c.is_summation_preserving())
     }
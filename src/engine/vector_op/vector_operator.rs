@@ -1,7 +1,9 @@
 use std::cell::{RefCell, Ref, RefMut};
+use std::collections::HashSet;
 use std::fmt;
 use std::marker::PhantomData;
 use std::mem;
+use std::sync::Mutex;
 use std::borrow::BorrowMut;
 
 use bit_vec::BitVec;
@@ -9,8 +11,13 @@ use engine::aggregation_operator::*;
 use engine::typed_vec::TypedVec;
 use engine::types::EncodingType;
 use engine::*;
+use engine::filter::Filter;
+use engine::time_function::TimeFunction;
 use ingest::raw_val::RawVal;
 use mem_store::column::{ColumnData, ColumnCodec};
+use ordered_float::OrderedFloat;
+use regex::Regex;
+use syntax::expression::FuncType;
 
 
 pub type BoxedOperator<'a> = Box<VecOperator<'a> + 'a>;
@@ -22,6 +29,43 @@ pub trait VecOperator<'a>: fmt::Debug {
     fn execute(&mut self, scratchpad: &mut Scratchpad<'a>);
 }
 
+// Owns strings that are computed while a query runs (e.g. by `lower`), which query results borrow until the query
+// completes
+#[derive(Default)]
+pub struct StringArena {
+    strings: Mutex<Vec<String>>,
+}
+
+impl StringArena {
+    pub fn alloc_all(&self, strings: Vec<String>) -> Vec<&str> {
+        // Moving a string into the arena does not move its contents, which are only dropped together with the arena
+        let result = strings.iter().map(|s| unsafe { &*(s.as_str() as *const str) }).collect();
+        self.strings.lock().unwrap().extend(strings);
+        result
+    }
+}
+
+impl fmt::Debug for StringArena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StringArena")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Case {
+    Lower,
+    Upper,
+}
+
+impl Case {
+    pub fn convert(self, string: &str) -> String {
+        match self {
+            Case::Lower => string.to_lowercase(),
+            Case::Upper => string.to_uppercase(),
+        }
+    }
+}
+
 pub struct Scratchpad<'a> {
     buffers: Vec<RefCell<BoxedVec<'a>>>,
 }
@@ -270,6 +314,149 @@ impl<'a> VecOperator<'a> for SortIndices {
     }
 }
 
+#[derive(Debug)]
+pub struct SortBy {
+    input: BufferRef,
+    indices: BufferRef,
+    output: BufferRef,
+    descending: bool,
+}
+
+impl<'a> VecOperator<'a> for SortBy {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let input = scratchpad.get_any(self.input);
+            let mut result = scratchpad.get::<usize>(self.indices).to_vec();
+            if self.descending {
+                input.sort_indices_desc(&mut result);
+            } else {
+                input.sort_indices_asc(&mut result);
+            }
+            TypedVec::owned(result)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+pub struct Gather<T> {
+    input: BufferRef,
+    indices: BufferRef,
+    output: BufferRef,
+    t: PhantomData<T>,
+}
+
+impl<'a, T: VecType<T> + 'a> VecOperator<'a> for Gather<T> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let data = scratchpad.get::<T>(self.input);
+            let indices = scratchpad.get::<usize>(self.indices);
+            let result = indices.iter().map(|&i| data[i]).collect::<Vec<T>>();
+            TypedVec::owned(result)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+pub struct GetPresent<'a> {
+    col: &'a ColumnData,
+    filter: Filter,
+    output: BufferRef,
+}
+
+impl<'a> GetPresent<'a> {
+    pub fn new(col: &'a ColumnData, filter: Filter, output: BufferRef) -> GetPresent<'a> {
+        GetPresent { col, filter, output }
+    }
+}
+
+impl<'a> VecOperator<'a> for GetPresent<'a> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = match (self.col.present(), self.filter) {
+            (Some(present), Filter::None) => present.clone(),
+            (Some(present), Filter::BitVec(filter)) => {
+                let filter = scratchpad.get_bit_vec(filter);
+                present.iter().zip(filter.iter())
+                    .filter(|&(_, selected)| selected)
+                    .map(|(p, _)| p)
+                    .collect()
+            }
+            (Some(present), Filter::Indices(indices)) =>
+                scratchpad.get::<usize>(indices).iter().map(|&i| present[i]).collect(),
+            (None, Filter::None) => BitVec::from_elem(self.col.len(), true),
+            (None, Filter::BitVec(filter)) => {
+                let count = scratchpad.get_bit_vec(filter).iter().filter(|&selected| selected).count();
+                BitVec::from_elem(count, true)
+            }
+            (None, Filter::Indices(indices)) => BitVec::from_elem(scratchpad.get::<usize>(indices).len(), true),
+        };
+        scratchpad.set(self.output, TypedVec::bit_vec(result));
+    }
+}
+
+// One zero for each row selected by the filter, groups all rows of aggregates without GROUP BY
+#[derive(Debug)]
+pub struct Zeros<'a> {
+    col: &'a ColumnData,
+    filter: Filter,
+    output: BufferRef,
+}
+
+impl<'a> Zeros<'a> {
+    pub fn new(col: &'a ColumnData, filter: Filter, output: BufferRef) -> Zeros<'a> {
+        Zeros { col, filter, output }
+    }
+}
+
+impl<'a> VecOperator<'a> for Zeros<'a> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let len = match self.filter {
+            Filter::None => self.col.len(),
+            Filter::BitVec(filter) => scratchpad.get_bit_vec(filter).iter().filter(|&selected| selected).count(),
+            Filter::Indices(indices) => scratchpad.get::<usize>(indices).len(),
+        };
+        scratchpad.set(self.output, TypedVec::owned(vec![0i64; len]));
+    }
+}
+
+#[derive(Debug)]
+pub struct MakeNullable {
+    data: BufferRef,
+    present: BufferRef,
+    output: BufferRef,
+}
+
+impl MakeNullable {
+    pub fn new(data: BufferRef, present: BufferRef, output: BufferRef) -> MakeNullable {
+        MakeNullable { data, present, output }
+    }
+}
+
+impl<'a> VecOperator<'a> for MakeNullable {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let present = scratchpad.get_bit_vec(self.present).clone();
+        let data = scratchpad.collect(self.data);
+        scratchpad.set(self.output, TypedVec::nullable(data, present));
+    }
+}
+
+#[derive(Debug)]
+struct BitVecToI64 {
+    input: BufferRef,
+    output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for BitVecToI64 {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let bits = scratchpad.get_bit_vec(self.input);
+            TypedVec::owned(bits.iter().map(|b| b as i64).collect::<Vec<i64>>())
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
 #[derive(Debug)]
 pub struct Constant {
     val: RawVal,
@@ -310,14 +497,64 @@ impl<'a> fmt::Debug for VectorConstant<'a> {
 impl<'a> VecOperator<'a> {
     pub fn less_than_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
         match t {
+            EncodingType::Str => Box::new(VecConstBoolOperator::<_, _, LessThanString>::new(lhs, rhs, output)),
             EncodingType::U8 => Box::new(VecConstBoolOperator::<u8, i64, LessThanInt<u8>>::new(lhs, rhs, output)),
             EncodingType::U16 => Box::new(VecConstBoolOperator::<u16, i64, LessThanInt<u16>>::new(lhs, rhs, output)),
             EncodingType::U32 => Box::new(VecConstBoolOperator::<u32, i64, LessThanInt<u32>>::new(lhs, rhs, output)),
             EncodingType::I64 => Box::new(VecConstBoolOperator::<i64, i64, LessThanInt<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<OrderedFloat<f64>, OrderedFloat<f64>, LessThan>::new(lhs, rhs, output)),
             _ => panic!("less_than_vs not supported for type {:?}", t),
         }
     }
 
+    pub fn less_than_equals_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::Str => Box::new(VecConstBoolOperator::<_, _, LessThanEqualsString>::new(lhs, rhs, output)),
+            EncodingType::U8 => Box::new(VecConstBoolOperator::<u8, i64, LessThanEqualsInt<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecConstBoolOperator::<u16, i64, LessThanEqualsInt<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecConstBoolOperator::<u32, i64, LessThanEqualsInt<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecConstBoolOperator::<i64, i64, LessThanEqualsInt<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<OrderedFloat<f64>, OrderedFloat<f64>, LessThanEquals>::new(lhs, rhs, output)),
+            _ => panic!("less_than_equals_vs not supported for type {:?}", t),
+        }
+    }
+
+    pub fn greater_than_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::Str => Box::new(VecConstBoolOperator::<_, _, GreaterThanString>::new(lhs, rhs, output)),
+            EncodingType::U8 => Box::new(VecConstBoolOperator::<u8, i64, GreaterThanInt<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecConstBoolOperator::<u16, i64, GreaterThanInt<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecConstBoolOperator::<u32, i64, GreaterThanInt<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecConstBoolOperator::<i64, i64, GreaterThanInt<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<OrderedFloat<f64>, OrderedFloat<f64>, GreaterThan>::new(lhs, rhs, output)),
+            _ => panic!("greater_than_vs not supported for type {:?}", t),
+        }
+    }
+
+    pub fn greater_than_equals_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::Str => Box::new(VecConstBoolOperator::<_, _, GreaterThanEqualsString>::new(lhs, rhs, output)),
+            EncodingType::U8 => Box::new(VecConstBoolOperator::<u8, i64, GreaterThanEqualsInt<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecConstBoolOperator::<u16, i64, GreaterThanEqualsInt<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecConstBoolOperator::<u32, i64, GreaterThanEqualsInt<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecConstBoolOperator::<i64, i64, GreaterThanEqualsInt<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<OrderedFloat<f64>, OrderedFloat<f64>, GreaterThanEquals>::new(lhs, rhs, output)),
+            _ => panic!("greater_than_equals_vs not supported for type {:?}", t),
+        }
+    }
+
+    pub fn not_equals_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::Str => Box::new(VecConstBoolOperator::<_, _, NotEqualsString>::new(lhs, rhs, output)),
+            EncodingType::U8 => Box::new(VecConstBoolOperator::<_, _, NotEqualsInt<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecConstBoolOperator::<_, _, NotEqualsInt<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecConstBoolOperator::<_, _, NotEqualsInt<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecConstBoolOperator::<_, _, NotEqualsInt<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<OrderedFloat<f64>, OrderedFloat<f64>, NotEqualsVV>::new(lhs, rhs, output)),
+            _ => panic!("not_equals_vs not supported for type {:?}", t),
+        }
+    }
+
     pub fn equals_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
         match t {
             EncodingType::Str => Box::new(VecConstBoolOperator::<_, _, EqualsString>::new(lhs, rhs, output)),
@@ -325,10 +562,185 @@ impl<'a> VecOperator<'a> {
             EncodingType::U16 => Box::new(VecConstBoolOperator::<_, _, EqualsInt<u16>>::new(lhs, rhs, output)),
             EncodingType::U32 => Box::new(VecConstBoolOperator::<_, _, EqualsInt<u32>>::new(lhs, rhs, output)),
             EncodingType::I64 => Box::new(VecConstBoolOperator::<_, _, Equals<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<_, _, Equals<OrderedFloat<f64>>>::new(lhs, rhs, output)),
             _ => panic!("equals_vs not supported for type {:?}", t),
         }
     }
 
+    pub fn less_than_vv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::comparison_vv::<LessThan>(t, lhs, rhs, output)
+    }
+
+    pub fn less_than_equals_vv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::comparison_vv::<LessThanEquals>(t, lhs, rhs, output)
+    }
+
+    pub fn equals_vv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::comparison_vv::<EqualsVV>(t, lhs, rhs, output)
+    }
+
+    pub fn not_equals_vv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::comparison_vv::<NotEqualsVV>(t, lhs, rhs, output)
+    }
+
+    fn comparison_vv<Op>(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a>
+        where Op: BoolOperation<u8, u8> + BoolOperation<u16, u16> + BoolOperation<u32, u32> +
+                  BoolOperation<i64, i64> + BoolOperation<OrderedFloat<f64>, OrderedFloat<f64>> +
+                  BoolOperation<&'a str, &'a str> + fmt::Debug + 'a {
+        match t {
+            EncodingType::U8 => Box::new(VecVecBoolOperator::<u8, Op>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecVecBoolOperator::<u16, Op>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecVecBoolOperator::<u32, Op>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecVecBoolOperator::<i64, Op>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecVecBoolOperator::<OrderedFloat<f64>, Op>::new(lhs, rhs, output)),
+            EncodingType::Str => Box::new(VecVecBoolOperator::<&str, Op>::new(lhs, rhs, output)),
+            _ => panic!("comparison not supported for type {:?}", t),
+        }
+    }
+
+    pub fn time_function(t: EncodingType, input: BufferRef, output: BufferRef, function: TimeFunction) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(VecTimeFunction::<u8> { input, output, function, t: PhantomData }),
+            EncodingType::U16 => Box::new(VecTimeFunction::<u16> { input, output, function, t: PhantomData }),
+            EncodingType::U32 => Box::new(VecTimeFunction::<u32> { input, output, function, t: PhantomData }),
+            EncodingType::I64 => Box::new(VecTimeFunction::<i64> { input, output, function, t: PhantomData }),
+            _ => panic!("time_function not supported for type {:?}", t),
+        }
+    }
+
+    pub fn compare_runs(op: FuncType, codec: &'a ColumnCodec, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match op {
+            FuncType::LT => Box::new(CompareRuns::<LessThanInt<i64>>::new(codec, rhs, output)),
+            FuncType::LTE => Box::new(CompareRuns::<LessThanEqualsInt<i64>>::new(codec, rhs, output)),
+            FuncType::GT => Box::new(CompareRuns::<GreaterThanInt<i64>>::new(codec, rhs, output)),
+            FuncType::GTE => Box::new(CompareRuns::<GreaterThanEqualsInt<i64>>::new(codec, rhs, output)),
+            FuncType::Equals => Box::new(CompareRuns::<EqualsInt<i64>>::new(codec, rhs, output)),
+            FuncType::NotEquals => Box::new(CompareRuns::<NotEqualsInt<i64>>::new(codec, rhs, output)),
+            _ => panic!("compare_runs not supported for {:?}", op),
+        }
+    }
+
+    pub fn regex_match(input: BufferRef, output: BufferRef, regex: Regex) -> BoxedOperator<'a> {
+        Box::new(VecRegexMatch { input, output, regex })
+    }
+
+    pub fn regex_match_dictionary(output: BufferRef, regex: Regex, codec: &'a ColumnCodec) -> BoxedOperator<'a> {
+        Box::new(RegexMatchDictionary { output, regex, codec })
+    }
+
+    pub fn convert_case(input: BufferRef, output: BufferRef, case: Case, strings: &'a StringArena) -> BoxedOperator<'a> {
+        Box::new(VecConvertCase { input, output, case, strings })
+    }
+
+    pub fn convert_case_dictionary(t: EncodingType,
+                                   codes: BufferRef,
+                                   output: BufferRef,
+                                   case: Case,
+                                   codec: &'a ColumnCodec,
+                                   strings: &'a StringArena) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(ConvertCaseDictionary::<u8>::new(codes, output, case, codec, strings)),
+            EncodingType::U16 => Box::new(ConvertCaseDictionary::<u16>::new(codes, output, case, codec, strings)),
+            EncodingType::U32 => Box::new(ConvertCaseDictionary::<u32>::new(codes, output, case, codec, strings)),
+            _ => panic!("convert_case_dictionary not supported for type {:?}", t),
+        }
+    }
+
+    pub fn in_set_dictionary(output: BufferRef, set: HashSet<String>, codec: &'a ColumnCodec) -> BoxedOperator<'a> {
+        Box::new(InSetDictionary { output, set, codec })
+    }
+
+    pub fn code_in_set(t: EncodingType, codes: BufferRef, set: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(CodeInSet::<u8>::new(codes, set, output)),
+            EncodingType::U16 => Box::new(CodeInSet::<u16>::new(codes, set, output)),
+            EncodingType::U32 => Box::new(CodeInSet::<u32>::new(codes, set, output)),
+            _ => panic!("code_in_set not supported for type {:?}", t),
+        }
+    }
+
+    pub fn addition_vv(lhs_type: EncodingType, rhs_type: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vv::<Addition>(lhs_type, rhs_type, lhs, rhs, output)
+    }
+
+    pub fn subtraction_vv(lhs_type: EncodingType, rhs_type: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vv::<Subtraction>(lhs_type, rhs_type, lhs, rhs, output)
+    }
+
+    pub fn multiplication_vv(lhs_type: EncodingType, rhs_type: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vv::<Multiplication>(lhs_type, rhs_type, lhs, rhs, output)
+    }
+
+    pub fn division_vv(lhs_type: EncodingType, rhs_type: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vv::<Division>(lhs_type, rhs_type, lhs, rhs, output)
+    }
+
+    pub fn addition_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vs::<Addition>(t, lhs, rhs, output)
+    }
+
+    pub fn subtraction_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vs::<Subtraction>(t, lhs, rhs, output)
+    }
+
+    pub fn subtraction_sv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vs::<Reversed<Subtraction>>(t, rhs, lhs, output)
+    }
+
+    pub fn multiplication_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vs::<Multiplication>(t, lhs, rhs, output)
+    }
+
+    pub fn division_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vs::<Division>(t, lhs, rhs, output)
+    }
+
+    pub fn division_sv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::arithmetic_vs::<Reversed<Division>>(t, rhs, lhs, output)
+    }
+
+    fn arithmetic_vv<Op: ArithmeticOperation + fmt::Debug + 'a>(lhs_type: EncodingType,
+                                                                 rhs_type: EncodingType,
+                                                                 lhs: BufferRef,
+                                                                 rhs: BufferRef,
+                                                                 output: BufferRef) -> BoxedOperator<'a> {
+        use self::EncodingType::*;
+        match (lhs_type, rhs_type) {
+            (U8, U8) => Box::new(VecVecArithmeticOperator::<u8, u8, Op>::new(lhs, rhs, output)),
+            (U8, U16) => Box::new(VecVecArithmeticOperator::<u8, u16, Op>::new(lhs, rhs, output)),
+            (U8, U32) => Box::new(VecVecArithmeticOperator::<u8, u32, Op>::new(lhs, rhs, output)),
+            (U8, I64) => Box::new(VecVecArithmeticOperator::<u8, i64, Op>::new(lhs, rhs, output)),
+            (U16, U8) => Box::new(VecVecArithmeticOperator::<u16, u8, Op>::new(lhs, rhs, output)),
+            (U16, U16) => Box::new(VecVecArithmeticOperator::<u16, u16, Op>::new(lhs, rhs, output)),
+            (U16, U32) => Box::new(VecVecArithmeticOperator::<u16, u32, Op>::new(lhs, rhs, output)),
+            (U16, I64) => Box::new(VecVecArithmeticOperator::<u16, i64, Op>::new(lhs, rhs, output)),
+            (U32, U8) => Box::new(VecVecArithmeticOperator::<u32, u8, Op>::new(lhs, rhs, output)),
+            (U32, U16) => Box::new(VecVecArithmeticOperator::<u32, u16, Op>::new(lhs, rhs, output)),
+            (U32, U32) => Box::new(VecVecArithmeticOperator::<u32, u32, Op>::new(lhs, rhs, output)),
+            (U32, I64) => Box::new(VecVecArithmeticOperator::<u32, i64, Op>::new(lhs, rhs, output)),
+            (I64, U8) => Box::new(VecVecArithmeticOperator::<i64, u8, Op>::new(lhs, rhs, output)),
+            (I64, U16) => Box::new(VecVecArithmeticOperator::<i64, u16, Op>::new(lhs, rhs, output)),
+            (I64, U32) => Box::new(VecVecArithmeticOperator::<i64, u32, Op>::new(lhs, rhs, output)),
+            (I64, I64) => Box::new(VecVecArithmeticOperator::<i64, i64, Op>::new(lhs, rhs, output)),
+            (F64, F64) => Box::new(VecVecFloatArithmeticOperator::<Op>::new(lhs, rhs, output)),
+            (lt, rt) => panic!("arithmetic not supported for types {:?}, {:?}", lt, rt),
+        }
+    }
+
+    fn arithmetic_vs<Op: ArithmeticOperation + fmt::Debug + 'a>(t: EncodingType,
+                                                                 lhs: BufferRef,
+                                                                 rhs: BufferRef,
+                                                                 output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(VecConstArithmeticOperator::<u8, Op>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecConstArithmeticOperator::<u16, Op>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecConstArithmeticOperator::<u32, Op>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecConstArithmeticOperator::<i64, Op>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstFloatArithmeticOperator::<Op>::new(lhs, rhs, output)),
+            _ => panic!("arithmetic not supported for type {:?}", t),
+        }
+    }
+
     pub fn bit_shift_left_add(lhs: BufferRef, rhs: BufferRef, output: BufferRef, shift_amount: i64) -> BoxedOperator<'a> {
         Box::new(ParameterizedVecVecIntegerOperator::<BitShiftLeftAdd>::new(lhs, rhs, output, shift_amount))
     }
@@ -356,6 +768,13 @@ impl<'a> VecOperator<'a> {
             (I64, U16) => Box::new(TypeConversionOperator::<i64, u16>::new(inner, output)),
             (I64, U32) => Box::new(TypeConversionOperator::<i64, u32>::new(inner, output)),
 
+            (U8, F64) => Box::new(TypeConversionOperator::<u8, OrderedFloat<f64>>::new(inner, output)),
+            (U16, F64) => Box::new(TypeConversionOperator::<u16, OrderedFloat<f64>>::new(inner, output)),
+            (U32, F64) => Box::new(TypeConversionOperator::<u32, OrderedFloat<f64>>::new(inner, output)),
+            (I64, F64) => Box::new(TypeConversionOperator::<i64, OrderedFloat<f64>>::new(inner, output)),
+
+            (BitVec, I64) => Box::new(BitVecToI64 { input: inner, output }),
+
             (U8, U8) | (U16, U16) | (U32, U32) | (I64, I64) => panic!("type_conversion from type {:?} to itself", initial_type),
             _ => panic!("type_conversion not supported for types {:?} -> {:?}", initial_type, target_type)
         }
@@ -363,6 +782,7 @@ impl<'a> VecOperator<'a> {
 
     pub fn summation(input: BufferRef,
                      grouping: BufferRef,
+                     present: Option<BufferRef>,
                      output: BufferRef,
                      input_type: EncodingType,
                      grouping_type: EncodingType,
@@ -370,32 +790,228 @@ impl<'a> VecOperator<'a> {
                      dense_grouping: bool) -> BoxedOperator<'a> {
         use self::EncodingType::*;
         match (input_type, grouping_type) {
-            (U8, U8) => VecSum::<u8, u8>::boxed(input, grouping, output, max_index, dense_grouping),
-            (U8, U16) => VecSum::<u8, u16>::boxed(input, grouping, output, max_index, dense_grouping),
-            (U8, U32) => VecSum::<u8, u32>::boxed(input, grouping, output, max_index, dense_grouping),
-            // (U8, I64) => VecSum::<u8, u64>::boxed(input, grouping, output, max_index, dense_grouping),
-            (U16, U8) => VecSum::<u16, u8>::boxed(input, grouping, output, max_index, dense_grouping),
-            (U16, U16) => VecSum::<u16, u16>::boxed(input, grouping, output, max_index, dense_grouping),
-            (U16, U32) => VecSum::<u16, u32>::boxed(input, grouping, output, max_index, dense_grouping),
-            // (U16, I64) => VecSum::<u16, u64>::boxed(input, grouping, output, max_index, dense_grouping),
-            (U32, U8) => VecSum::<u32, u8>::boxed(input, grouping, output, max_index, dense_grouping),
-            (U32, U16) => VecSum::<u32, u16>::boxed(input, grouping, output, max_index, dense_grouping),
-            (U32, U32) => VecSum::<u32, u32>::boxed(input, grouping, output, max_index, dense_grouping),
-            // (U32, I64) => VecSum::<u32, u64>::boxed(input, grouping, output, max_index, dense_grouping),
-            (I64, U8) => VecSum::<i64, u8>::boxed(input, grouping, output, max_index, dense_grouping),
-            (I64, U16) => VecSum::<i64, u16>::boxed(input, grouping, output, max_index, dense_grouping),
-            (I64, U32) => VecSum::<i64, u32>::boxed(input, grouping, output, max_index, dense_grouping),
-            // (I64, I64) => VecSum::<i64, u64>::boxed(input, grouping, output, max_index, dense_grouping),
+            (U8, U8) => VecSum::<u8, u8>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U8, U16) => VecSum::<u8, u16>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U8, U32) => VecSum::<u8, u32>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U8, I64) => VecSum::<u8, i64>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U16, U8) => VecSum::<u16, u8>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U16, U16) => VecSum::<u16, u16>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U16, U32) => VecSum::<u16, u32>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U16, I64) => VecSum::<u16, i64>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U32, U8) => VecSum::<u32, u8>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U32, U16) => VecSum::<u32, u16>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U32, U32) => VecSum::<u32, u32>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U32, I64) => VecSum::<u32, i64>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (I64, U8) => VecSum::<i64, u8>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (I64, U16) => VecSum::<i64, u16>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (I64, U32) => VecSum::<i64, u32>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (I64, I64) => VecSum::<i64, i64>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (F64, U8) => VecSum::<OrderedFloat<f64>, u8>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (F64, U16) => VecSum::<OrderedFloat<f64>, u16>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (F64, U32) => VecSum::<OrderedFloat<f64>, u32>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (F64, I64) => VecSum::<OrderedFloat<f64>, i64>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (pt, gt) => panic!("invalid aggregation types {:?}, {:?}", pt, gt),
+        }
+    }
+
+    pub fn summation_runs(codec: &'a ColumnCodec,
+                          grouping: BufferRef,
+                          output: BufferRef,
+                          grouping_type: EncodingType,
+                          max_index: usize,
+                          dense_grouping: bool) -> BoxedOperator<'a> {
+        match grouping_type {
+            EncodingType::U8 => VecSumRuns::<u8>::boxed(codec, grouping, output, max_index, dense_grouping),
+            EncodingType::U16 => VecSumRuns::<u16>::boxed(codec, grouping, output, max_index, dense_grouping),
+            EncodingType::U32 => VecSumRuns::<u32>::boxed(codec, grouping, output, max_index, dense_grouping),
+            EncodingType::I64 => VecSumRuns::<i64>::boxed(codec, grouping, output, max_index, dense_grouping),
+            t => panic!("unsupported type {:?} for grouping key", t),
+        }
+    }
+
+    pub fn minimum(input: BufferRef,
+                   grouping: BufferRef,
+                   present: Option<BufferRef>,
+                   output: BufferRef,
+                   input_type: EncodingType,
+                   grouping_type: EncodingType,
+                   max_index: usize,
+                   dense_grouping: bool) -> BoxedOperator<'a> {
+        VecOperator::min_max::<Minimum>(input, grouping, present, output, input_type, grouping_type, max_index, dense_grouping)
+    }
+
+    pub fn maximum(input: BufferRef,
+                   grouping: BufferRef,
+                   present: Option<BufferRef>,
+                   output: BufferRef,
+                   input_type: EncodingType,
+                   grouping_type: EncodingType,
+                   max_index: usize,
+                   dense_grouping: bool) -> BoxedOperator<'a> {
+        VecOperator::min_max::<Maximum>(input, grouping, present, output, input_type, grouping_type, max_index, dense_grouping)
+    }
+
+    fn min_max<Op: MinMaxOperation + fmt::Debug + 'static>(input: BufferRef,
+                                                           grouping: BufferRef,
+                                                           present: Option<BufferRef>,
+                                                           output: BufferRef,
+                                                           input_type: EncodingType,
+                                                           grouping_type: EncodingType,
+                                                           max_index: usize,
+                                                           dense_grouping: bool) -> BoxedOperator<'a> {
+        use self::EncodingType::*;
+        match (input_type, grouping_type) {
+            (U8, U8) => VecMinMax::<u8, u8, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U8, U16) => VecMinMax::<u8, u16, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U8, U32) => VecMinMax::<u8, u32, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U8, I64) => VecMinMax::<u8, i64, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U16, U8) => VecMinMax::<u16, u8, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U16, U16) => VecMinMax::<u16, u16, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U16, U32) => VecMinMax::<u16, u32, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U16, I64) => VecMinMax::<u16, i64, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U32, U8) => VecMinMax::<u32, u8, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U32, U16) => VecMinMax::<u32, u16, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U32, U32) => VecMinMax::<u32, u32, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (U32, I64) => VecMinMax::<u32, i64, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (I64, U8) => VecMinMax::<i64, u8, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (I64, U16) => VecMinMax::<i64, u16, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (I64, U32) => VecMinMax::<i64, u32, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (I64, I64) => VecMinMax::<i64, i64, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (F64, U8) => VecMinMax::<OrderedFloat<f64>, u8, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (F64, U16) => VecMinMax::<OrderedFloat<f64>, u16, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (F64, U32) => VecMinMax::<OrderedFloat<f64>, u32, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
+            (F64, I64) => VecMinMax::<OrderedFloat<f64>, i64, Op>::boxed(input, grouping, present, output, max_index, dense_grouping),
             (pt, gt) => panic!("invalid aggregation types {:?}, {:?}", pt, gt),
         }
     }
 
-    pub fn count(grouping: BufferRef, output: BufferRef, grouping_type: EncodingType, max_index: usize, dense_grouping: bool) -> BoxedOperator<'a> {
+    pub fn count(grouping: BufferRef, present: Option<BufferRef>, output: BufferRef, grouping_type: EncodingType, max_index: usize, dense_grouping: bool) -> BoxedOperator<'a> {
+        match grouping_type {
+            EncodingType::U8 => Box::new(VecCount::<u8>::new(grouping, present, output, max_index, dense_grouping)),
+            EncodingType::U16 => Box::new(VecCount::<u16>::new(grouping, present, output, max_index, dense_grouping)),
+            EncodingType::U32 => Box::new(VecCount::<u32>::new(grouping, present, output, max_index, dense_grouping)),
+            EncodingType::I64 => Box::new(VecCount::<i64>::new(grouping, present, output, max_index, dense_grouping)),
+            t => panic!("unsupported type {:?} for grouping key", t),
+        }
+    }
+
+    pub fn distinct_codes(input: BufferRef,
+                          grouping: BufferRef,
+                          present: Option<BufferRef>,
+                          output: BufferRef,
+                          input_type: EncodingType,
+                          grouping_type: EncodingType,
+                          max_index: usize,
+                          dictionary_len: usize,
+                          dense_grouping: bool) -> BoxedOperator<'a> {
+        match input_type {
+            EncodingType::U8 => VecOperator::grouped_aggregate(DistinctCodes::<u8>::new(dictionary_len), vec![input], grouping, present, vec![output], grouping_type, max_index, dense_grouping),
+            EncodingType::U16 => VecOperator::grouped_aggregate(DistinctCodes::<u16>::new(dictionary_len), vec![input], grouping, present, vec![output], grouping_type, max_index, dense_grouping),
+            EncodingType::U32 => VecOperator::grouped_aggregate(DistinctCodes::<u32>::new(dictionary_len), vec![input], grouping, present, vec![output], grouping_type, max_index, dense_grouping),
+            t => panic!("unsupported type {:?} for dictionary codes", t),
+        }
+    }
+
+    pub fn distinct_values(input: BufferRef,
+                           grouping: BufferRef,
+                           present: Option<BufferRef>,
+                           output: BufferRef,
+                           grouping_type: EncodingType,
+                           max_index: usize,
+                           dense_grouping: bool) -> BoxedOperator<'a> {
+        VecOperator::grouped_aggregate(DistinctValues, vec![input], grouping, present, vec![output], grouping_type, max_index, dense_grouping)
+    }
+
+    pub fn hyper_log_log(input: BufferRef,
+                         grouping: BufferRef,
+                         present: Option<BufferRef>,
+                         output: BufferRef,
+                         input_type: EncodingType,
+                         grouping_type: EncodingType,
+                         max_index: usize,
+                         dense_grouping: bool) -> BoxedOperator<'a> {
+        match input_type {
+            EncodingType::Str => VecOperator::grouped_aggregate(HyperLogLog::<&'a str>::new(), vec![input], grouping, present, vec![output], grouping_type, max_index, dense_grouping),
+            EncodingType::I64 => VecOperator::grouped_aggregate(HyperLogLog::<i64>::new(), vec![input], grouping, present, vec![output], grouping_type, max_index, dense_grouping),
+            EncodingType::F64 => VecOperator::grouped_aggregate(HyperLogLog::<OrderedFloat<f64>>::new(), vec![input], grouping, present, vec![output], grouping_type, max_index, dense_grouping),
+            t => panic!("unsupported type {:?} for approx_count_distinct", t),
+        }
+    }
+
+    pub fn t_digest(input: BufferRef,
+                    grouping: BufferRef,
+                    present: Option<BufferRef>,
+                    output: BufferRef,
+                    grouping_type: EncodingType,
+                    max_index: usize,
+                    dense_grouping: bool) -> BoxedOperator<'a> {
+        VecOperator::grouped_aggregate(TDigestSketch, vec![input], grouping, present, vec![output], grouping_type, max_index, dense_grouping)
+    }
+
+    pub fn moments(input_x: BufferRef,
+                   input_y: BufferRef,
+                   grouping: BufferRef,
+                   present: Option<BufferRef>,
+                   output: BufferRef,
+                   grouping_type: EncodingType,
+                   max_index: usize,
+                   dense_grouping: bool) -> BoxedOperator<'a> {
+        VecOperator::grouped_aggregate(MomentsSketch, vec![input_x, input_y], grouping, present, vec![output], grouping_type, max_index, dense_grouping)
+    }
+
+    pub fn arg_minimum(input: BufferRef,
+                       grouping: BufferRef,
+                       present: Option<BufferRef>,
+                       outputs: (BufferRef, BufferRef, BufferRef),
+                       input_type: EncodingType,
+                       grouping_type: EncodingType,
+                       max_index: usize,
+                       dense_grouping: bool) -> BoxedOperator<'a> {
+        VecOperator::arg_min_max::<Minimum>(input, grouping, present, outputs, input_type, grouping_type, max_index, dense_grouping)
+    }
+
+    pub fn arg_maximum(input: BufferRef,
+                       grouping: BufferRef,
+                       present: Option<BufferRef>,
+                       outputs: (BufferRef, BufferRef, BufferRef),
+                       input_type: EncodingType,
+                       grouping_type: EncodingType,
+                       max_index: usize,
+                       dense_grouping: bool) -> BoxedOperator<'a> {
+        VecOperator::arg_min_max::<Maximum>(input, grouping, present, outputs, input_type, grouping_type, max_index, dense_grouping)
+    }
+
+    // `outputs` receive the key, row index and presence of the selected row of each group
+    fn arg_min_max<Op: MinMaxOperation + fmt::Debug + 'static>(input: BufferRef,
+                                                               grouping: BufferRef,
+                                                               present: Option<BufferRef>,
+                                                               (keys_out, indices_out, present_out): (BufferRef, BufferRef, BufferRef),
+                                                               input_type: EncodingType,
+                                                               grouping_type: EncodingType,
+                                                               max_index: usize,
+                                                               dense_grouping: bool) -> BoxedOperator<'a> {
+        let outputs = vec![keys_out, indices_out, present_out];
+        match input_type {
+            EncodingType::I64 => VecOperator::grouped_aggregate(ArgMinMax::<i64, Op>::new(), vec![input], grouping, present, outputs, grouping_type, max_index, dense_grouping),
+            EncodingType::F64 => VecOperator::grouped_aggregate(ArgMinMax::<OrderedFloat<f64>, Op>::new(), vec![input], grouping, present, outputs, grouping_type, max_index, dense_grouping),
+            t => panic!("unsupported type {:?} for arg_min_max", t),
+        }
+    }
+
+    // Aggregates the rows of each group with `accumulator`, dispatching on the type of the grouping key
+    fn grouped_aggregate<A: GroupAccumulator<'a>>(accumulator: A,
+                                                  inputs: Vec<BufferRef>,
+                                                  grouping: BufferRef,
+                                                  present: Option<BufferRef>,
+                                                  outputs: Vec<BufferRef>,
+                                                  grouping_type: EncodingType,
+                                                  max_index: usize,
+                                                  dense_grouping: bool) -> BoxedOperator<'a> {
         match grouping_type {
-            EncodingType::U8 => Box::new(VecCount::<u8>::new(grouping, output, max_index, dense_grouping)),
-            EncodingType::U16 => Box::new(VecCount::<u16>::new(grouping, output, max_index, dense_grouping)),
-            EncodingType::U32 => Box::new(VecCount::<u32>::new(grouping, output, max_index, dense_grouping)),
-            EncodingType::I64 => Box::new(VecCount::<i64>::new(grouping, output, max_index, dense_grouping)),
+            EncodingType::U8 => VecGroupedAggregate::<A, u8>::boxed(accumulator, inputs, grouping, present, outputs, max_index, dense_grouping),
+            EncodingType::U16 => VecGroupedAggregate::<A, u16>::boxed(accumulator, inputs, grouping, present, outputs, max_index, dense_grouping),
+            EncodingType::U32 => VecGroupedAggregate::<A, u32>::boxed(accumulator, inputs, grouping, present, outputs, max_index, dense_grouping),
+            EncodingType::I64 => VecGroupedAggregate::<A, i64>::boxed(accumulator, inputs, grouping, present, outputs, max_index, dense_grouping),
             t => panic!("unsupported type {:?} for grouping key", t),
         }
     }
@@ -428,9 +1044,31 @@ impl<'a> VecOperator<'a> {
         }
     }
 
+    pub fn hash_map_grouping_byte_slices(inputs: Vec<BufferRef>,
+                                         grouping_key_out: BufferRef,
+                                         first_rows_out: BufferRef) -> BoxedOperator<'a> {
+        HashMapGroupingByteSlices::boxed(inputs, grouping_key_out, first_rows_out)
+    }
+
+    pub fn gather(input: BufferRef, indices: BufferRef, output: BufferRef, t: EncodingType) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(Gather::<u8> { input, indices, output, t: PhantomData }),
+            EncodingType::U16 => Box::new(Gather::<u16> { input, indices, output, t: PhantomData }),
+            EncodingType::U32 => Box::new(Gather::<u32> { input, indices, output, t: PhantomData }),
+            EncodingType::I64 => Box::new(Gather::<i64> { input, indices, output, t: PhantomData }),
+            EncodingType::F64 => Box::new(Gather::<OrderedFloat<f64>> { input, indices, output, t: PhantomData }),
+            EncodingType::Str => Box::new(Gather::<&'a str> { input, indices, output, t: PhantomData }),
+            _ => panic!("gather not supported for type {:?}", t),
+        }
+    }
+
     pub fn sort_indices(input: BufferRef, output: BufferRef, descending: bool) -> BoxedOperator<'a> {
         Box::new(SortIndices { input, output, descending })
     }
+
+    pub fn sort_by(input: BufferRef, indices: BufferRef, output: BufferRef, descending: bool) -> BoxedOperator<'a> {
+        Box::new(SortBy { input, indices, output, descending })
+    }
 }
 
 
@@ -474,22 +1112,114 @@ impl<'a, T: 'a, U, Op> VecOperator<'a> for VecConstBoolOperator<T, U, Op> where
     }
 }
 
-trait BoolOperation<T, U> {
-    fn perform(lhs: &T, rhs: &U) -> bool;
+#[derive(Debug)]
+struct CompareRuns<'a, Op> {
+    codec: &'a ColumnCodec,
+    rhs: BufferRef,
+    output: BufferRef,
+    op: PhantomData<Op>,
 }
 
-#[derive(Debug)]
-struct LessThanInt<T> { t: PhantomData<T> }
+impl<'a, Op: BoolOperation<i64, i64>> CompareRuns<'a, Op> {
+    fn new(codec: &'a ColumnCodec, rhs: BufferRef, output: BufferRef) -> CompareRuns<'a, Op> {
+        CompareRuns { codec, rhs, output, op: PhantomData }
+    }
+}
 
-impl<T: Into<i64> + Copy> BoolOperation<T, i64> for LessThanInt<T> {
-    #[inline]
-    fn perform(l: &T, r: &i64) -> bool { Into::<i64>::into(*l) < *r }
+impl<'a, Op: BoolOperation<i64, i64> + fmt::Debug> VecOperator<'a> for CompareRuns<'a, Op> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let (values, ends) = self.codec.runs().unwrap();
+            let c = &scratchpad.get_const::<i64>(self.rhs);
+            let mut output = BitVec::with_capacity(self.codec.len());
+            let mut start = 0;
+            for (value, &end) in values.iter().zip(ends.iter()) {
+                output.grow((end - start) as usize, Op::perform(value, c));
+                start = end;
+            }
+            TypedVec::bit_vec(output)
+        };
+        scratchpad.set(self.output, result);
+    }
 }
 
 #[derive(Debug)]
-struct Equals<T> { t: PhantomData<T> }
-
-impl<T: PartialEq> BoolOperation<T, T> for Equals<T> {
+struct VecVecBoolOperator<T, Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    t: PhantomData<T>,
+    op: PhantomData<Op>,
+}
+
+impl<T, Op> VecVecBoolOperator<T, Op> {
+    fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecVecBoolOperator<T, Op> {
+        VecVecBoolOperator {
+            lhs,
+            rhs,
+            output,
+            t: PhantomData,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: 'a, Op> VecOperator<'a> for VecVecBoolOperator<T, Op> where
+    T: VecType<T>, Op: BoolOperation<T, T> + fmt::Debug {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let lhs = scratchpad.get::<T>(self.lhs);
+            let rhs = scratchpad.get::<T>(self.rhs);
+            let mut output = BitVec::with_capacity(lhs.len());
+            for (l, r) in lhs.iter().zip(rhs.iter()) {
+                output.push(Op::perform(l, r));
+            }
+            TypedVec::bit_vec(output)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+trait BoolOperation<T, U> {
+    fn perform(lhs: &T, rhs: &U) -> bool;
+}
+
+#[derive(Debug)]
+struct LessThanInt<T> { t: PhantomData<T> }
+
+impl<T: Into<i64> + Copy> BoolOperation<T, i64> for LessThanInt<T> {
+    #[inline]
+    fn perform(l: &T, r: &i64) -> bool { Into::<i64>::into(*l) < *r }
+}
+
+#[derive(Debug)]
+struct LessThanEqualsInt<T> { t: PhantomData<T> }
+
+impl<T: Into<i64> + Copy> BoolOperation<T, i64> for LessThanEqualsInt<T> {
+    #[inline]
+    fn perform(l: &T, r: &i64) -> bool { Into::<i64>::into(*l) <= *r }
+}
+
+#[derive(Debug)]
+struct GreaterThanInt<T> { t: PhantomData<T> }
+
+impl<T: Into<i64> + Copy> BoolOperation<T, i64> for GreaterThanInt<T> {
+    #[inline]
+    fn perform(l: &T, r: &i64) -> bool { Into::<i64>::into(*l) > *r }
+}
+
+#[derive(Debug)]
+struct GreaterThanEqualsInt<T> { t: PhantomData<T> }
+
+impl<T: Into<i64> + Copy> BoolOperation<T, i64> for GreaterThanEqualsInt<T> {
+    #[inline]
+    fn perform(l: &T, r: &i64) -> bool { Into::<i64>::into(*l) >= *r }
+}
+
+#[derive(Debug)]
+struct Equals<T> { t: PhantomData<T> }
+
+impl<T: PartialEq> BoolOperation<T, T> for Equals<T> {
     #[inline]
     fn perform(l: &T, r: &T) -> bool { l == r }
 }
@@ -510,6 +1240,277 @@ impl<'a> BoolOperation<&'a str, String> for EqualsString {
     fn perform(l: &&'a str, r: &String) -> bool { l == r }
 }
 
+#[derive(Debug)]
+struct LessThanString;
+
+impl<'a> BoolOperation<&'a str, String> for LessThanString {
+    #[inline]
+    fn perform(l: &&'a str, r: &String) -> bool { *l < r.as_str() }
+}
+
+#[derive(Debug)]
+struct LessThanEqualsString;
+
+impl<'a> BoolOperation<&'a str, String> for LessThanEqualsString {
+    #[inline]
+    fn perform(l: &&'a str, r: &String) -> bool { *l <= r.as_str() }
+}
+
+#[derive(Debug)]
+struct GreaterThanString;
+
+impl<'a> BoolOperation<&'a str, String> for GreaterThanString {
+    #[inline]
+    fn perform(l: &&'a str, r: &String) -> bool { *l > r.as_str() }
+}
+
+#[derive(Debug)]
+struct GreaterThanEqualsString;
+
+impl<'a> BoolOperation<&'a str, String> for GreaterThanEqualsString {
+    #[inline]
+    fn perform(l: &&'a str, r: &String) -> bool { *l >= r.as_str() }
+}
+
+#[derive(Debug)]
+struct LessThan;
+
+impl<T: PartialOrd> BoolOperation<T, T> for LessThan {
+    #[inline]
+    fn perform(l: &T, r: &T) -> bool { l < r }
+}
+
+#[derive(Debug)]
+struct LessThanEquals;
+
+impl<T: PartialOrd> BoolOperation<T, T> for LessThanEquals {
+    #[inline]
+    fn perform(l: &T, r: &T) -> bool { l <= r }
+}
+
+#[derive(Debug)]
+struct GreaterThan;
+
+impl<T: PartialOrd> BoolOperation<T, T> for GreaterThan {
+    #[inline]
+    fn perform(l: &T, r: &T) -> bool { l > r }
+}
+
+#[derive(Debug)]
+struct GreaterThanEquals;
+
+impl<T: PartialOrd> BoolOperation<T, T> for GreaterThanEquals {
+    #[inline]
+    fn perform(l: &T, r: &T) -> bool { l >= r }
+}
+
+#[derive(Debug)]
+struct EqualsVV;
+
+impl<T: PartialEq> BoolOperation<T, T> for EqualsVV {
+    #[inline]
+    fn perform(l: &T, r: &T) -> bool { l == r }
+}
+
+#[derive(Debug)]
+struct NotEqualsVV;
+
+impl<T: PartialEq> BoolOperation<T, T> for NotEqualsVV {
+    #[inline]
+    fn perform(l: &T, r: &T) -> bool { l != r }
+}
+
+#[derive(Debug)]
+struct NotEqualsInt<T> { t: PhantomData<T> }
+
+impl<T: Into<i64> + Copy> BoolOperation<T, i64> for NotEqualsInt<T> {
+    #[inline]
+    fn perform(l: &T, r: &i64) -> bool { Into::<i64>::into(*l) != *r }
+}
+
+#[derive(Debug)]
+struct NotEqualsString;
+
+impl<'a> BoolOperation<&'a str, String> for NotEqualsString {
+    #[inline]
+    fn perform(l: &&'a str, r: &String) -> bool { l != r }
+}
+
+#[derive(Debug)]
+struct VecRegexMatch {
+    input: BufferRef,
+    output: BufferRef,
+    regex: Regex,
+}
+
+impl<'a> VecOperator<'a> for VecRegexMatch {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let data = scratchpad.get::<&str>(self.input);
+            let mut output = BitVec::with_capacity(data.len());
+            for d in data.iter() {
+                output.push(self.regex.is_match(d));
+            }
+            TypedVec::bit_vec(output)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+struct VecConvertCase<'a> {
+    input: BufferRef,
+    output: BufferRef,
+    case: Case,
+    strings: &'a StringArena,
+}
+
+impl<'a> VecOperator<'a> for VecConvertCase<'a> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let data = scratchpad.get::<&str>(self.input);
+            let converted = data.iter().map(|d| self.case.convert(d)).collect();
+            TypedVec::owned(self.strings.alloc_all(converted))
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+// Converts the dictionary entry of each code, entries are converted at most once per batch
+#[derive(Debug)]
+struct ConvertCaseDictionary<'a, T> {
+    codes: BufferRef,
+    output: BufferRef,
+    case: Case,
+    codec: &'a ColumnCodec,
+    strings: &'a StringArena,
+    t: PhantomData<T>,
+}
+
+impl<'a, T> ConvertCaseDictionary<'a, T> {
+    fn new(codes: BufferRef, output: BufferRef, case: Case, codec: &'a ColumnCodec, strings: &'a StringArena) -> ConvertCaseDictionary<'a, T> {
+        ConvertCaseDictionary { codes, output, case, codec, strings, t: PhantomData }
+    }
+}
+
+impl<'a, T: IntVecType<T>> VecOperator<'a> for ConvertCaseDictionary<'a, T> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let case = self.case;
+            let dictionary = self.codec.dictionary().unwrap();
+            let codes = scratchpad.get::<T>(self.codes);
+            // Index into `converted` for each code that occurs in the batch
+            let mut indices = vec![None; dictionary.len()];
+            let mut converted = Vec::new();
+            let rows = codes.iter()
+                .map(|code| {
+                    let code = code.cast_usize();
+                    *indices[code].get_or_insert_with(|| {
+                        converted.push(case.convert(dictionary[code].as_ref().unwrap()));
+                        converted.len() - 1
+                    })
+                })
+                .collect::<Vec<_>>();
+            let converted = self.strings.alloc_all(converted);
+            TypedVec::owned(rows.iter().map(|&i| converted[i]).collect::<Vec<_>>())
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+struct VecTimeFunction<T> {
+    input: BufferRef,
+    output: BufferRef,
+    function: TimeFunction,
+    t: PhantomData<T>,
+}
+
+impl<'a, T: IntVecType<T>> VecOperator<'a> for VecTimeFunction<T> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let data = scratchpad.get::<T>(self.input);
+            let mut output = Vec::with_capacity(data.len());
+            for &d in data.iter() {
+                output.push(self.function.apply(d.into()));
+            }
+            TypedVec::owned(output)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+struct RegexMatchDictionary<'a> {
+    output: BufferRef,
+    regex: Regex,
+    codec: &'a ColumnCodec,
+}
+
+impl<'a> VecOperator<'a> for RegexMatchDictionary<'a> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let dictionary = self.codec.dictionary().unwrap();
+            let mut matches = BitVec::with_capacity(dictionary.len());
+            for entry in dictionary {
+                matches.push(entry.as_ref().map_or(false, |s| self.regex.is_match(s)));
+            }
+            TypedVec::bit_vec(matches)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+struct InSetDictionary<'a> {
+    output: BufferRef,
+    set: HashSet<String>,
+    codec: &'a ColumnCodec,
+}
+
+impl<'a> VecOperator<'a> for InSetDictionary<'a> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let dictionary = self.codec.dictionary().unwrap();
+            let mut matches = BitVec::with_capacity(dictionary.len());
+            for entry in dictionary {
+                matches.push(entry.as_ref().map_or(false, |s| self.set.contains(s)));
+            }
+            TypedVec::bit_vec(matches)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+struct CodeInSet<T> {
+    codes: BufferRef,
+    set: BufferRef,
+    output: BufferRef,
+    t: PhantomData<T>,
+}
+
+impl<T> CodeInSet<T> {
+    fn new(codes: BufferRef, set: BufferRef, output: BufferRef) -> CodeInSet<T> {
+        CodeInSet { codes, set, output, t: PhantomData }
+    }
+}
+
+impl<'a, T: IntVecType<T>> VecOperator<'a> for CodeInSet<T> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let codes = scratchpad.get::<T>(self.codes);
+            let set = scratchpad.get_bit_vec(self.set);
+            let mut output = BitVec::with_capacity(codes.len());
+            for code in codes.iter() {
+                output.push(set.get(code.cast_usize()).unwrap_or(false));
+            }
+            TypedVec::bit_vec(output)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
 #[derive(Debug)]
 struct BooleanOperator<T> {
     lhs: BufferRef,
@@ -537,6 +1538,21 @@ impl Boolean {
     pub fn and<'a>(lhs: BufferRef, rhs: BufferRef) -> BoxedOperator<'a> {
         BooleanOperator::<BooleanAnd>::compare(lhs, rhs)
     }
+
+    pub fn not<'a>(input: BufferRef) -> BoxedOperator<'a> {
+        Box::new(BooleanNot { input })
+    }
+}
+
+#[derive(Debug)]
+struct BooleanNot {
+    input: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for BooleanNot {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.get_mut_bit_vec(self.input).negate();
+    }
 }
 
 impl<'a, T: BooleanOp + fmt::Debug> VecOperator<'a> for BooleanOperator<T> {
@@ -705,6 +1721,208 @@ impl ParameterizedIntegerOperation for BitShiftLeftAdd {
 }
 
 
+#[derive(Debug)]
+struct VecVecArithmeticOperator<T, U, Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    t: PhantomData<T>,
+    u: PhantomData<U>,
+    op: PhantomData<Op>,
+}
+
+impl<T, U, Op> VecVecArithmeticOperator<T, U, Op> {
+    fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecVecArithmeticOperator<T, U, Op> {
+        VecVecArithmeticOperator {
+            lhs,
+            rhs,
+            output,
+            t: PhantomData,
+            u: PhantomData,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, U, Op> VecOperator<'a> for VecVecArithmeticOperator<T, U, Op> where
+    T: IntVecType<T>, U: IntVecType<U>, Op: ArithmeticOperation + fmt::Debug {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let lhs = scratchpad.get::<T>(self.lhs);
+            let rhs = scratchpad.get::<U>(self.rhs);
+            let mut output = Vec::with_capacity(lhs.len());
+            for (l, r) in lhs.iter().zip(rhs.iter()) {
+                output.push(Op::perform(Into::<i64>::into(*l), Into::<i64>::into(*r)));
+            }
+            TypedVec::owned(output)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+struct VecConstArithmeticOperator<T, Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    t: PhantomData<T>,
+    op: PhantomData<Op>,
+}
+
+impl<T, Op> VecConstArithmeticOperator<T, Op> {
+    fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecConstArithmeticOperator<T, Op> {
+        VecConstArithmeticOperator {
+            lhs,
+            rhs,
+            output,
+            t: PhantomData,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, Op> VecOperator<'a> for VecConstArithmeticOperator<T, Op> where
+    T: IntVecType<T>, Op: ArithmeticOperation + fmt::Debug {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let data = scratchpad.get::<T>(self.lhs);
+            let c = scratchpad.get_const::<i64>(self.rhs);
+            let mut output = Vec::with_capacity(data.len());
+            for d in data.iter() {
+                output.push(Op::perform(Into::<i64>::into(*d), c));
+            }
+            TypedVec::owned(output)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+struct VecVecFloatArithmeticOperator<Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    op: PhantomData<Op>,
+}
+
+impl<Op> VecVecFloatArithmeticOperator<Op> {
+    fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecVecFloatArithmeticOperator<Op> {
+        VecVecFloatArithmeticOperator {
+            lhs,
+            rhs,
+            output,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, Op: ArithmeticOperation + fmt::Debug> VecOperator<'a> for VecVecFloatArithmeticOperator<Op> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let lhs = scratchpad.get::<OrderedFloat<f64>>(self.lhs);
+            let rhs = scratchpad.get::<OrderedFloat<f64>>(self.rhs);
+            let mut output = Vec::with_capacity(lhs.len());
+            for (l, r) in lhs.iter().zip(rhs.iter()) {
+                output.push(OrderedFloat(Op::perform_f64(l.0, r.0)));
+            }
+            TypedVec::owned(output)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+#[derive(Debug)]
+struct VecConstFloatArithmeticOperator<Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    op: PhantomData<Op>,
+}
+
+impl<Op> VecConstFloatArithmeticOperator<Op> {
+    fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecConstFloatArithmeticOperator<Op> {
+        VecConstFloatArithmeticOperator {
+            lhs,
+            rhs,
+            output,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, Op: ArithmeticOperation + fmt::Debug> VecOperator<'a> for VecConstFloatArithmeticOperator<Op> {
+    fn execute(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let data = scratchpad.get::<OrderedFloat<f64>>(self.lhs);
+            let c = scratchpad.get_const::<OrderedFloat<f64>>(self.rhs).0;
+            let mut output = Vec::with_capacity(data.len());
+            for d in data.iter() {
+                output.push(OrderedFloat(Op::perform_f64(d.0, c)));
+            }
+            TypedVec::owned(output)
+        };
+        scratchpad.set(self.output, result);
+    }
+}
+
+trait ArithmeticOperation {
+    fn perform(lhs: i64, rhs: i64) -> i64;
+    fn perform_f64(lhs: f64, rhs: f64) -> f64;
+}
+
+#[derive(Debug)]
+struct Addition;
+
+impl ArithmeticOperation for Addition {
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { l.wrapping_add(r) }
+    #[inline]
+    fn perform_f64(l: f64, r: f64) -> f64 { l + r }
+}
+
+#[derive(Debug)]
+struct Subtraction;
+
+impl ArithmeticOperation for Subtraction {
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { l.wrapping_sub(r) }
+    #[inline]
+    fn perform_f64(l: f64, r: f64) -> f64 { l - r }
+}
+
+#[derive(Debug)]
+struct Multiplication;
+
+impl ArithmeticOperation for Multiplication {
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { l.wrapping_mul(r) }
+    #[inline]
+    fn perform_f64(l: f64, r: f64) -> f64 { l * r }
+}
+
+#[derive(Debug)]
+struct Division;
+
+impl ArithmeticOperation for Division {
+    // Rows with a zero divisor are masked as null by `compile_present`
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { if r == 0 { 0 } else { l.wrapping_div(r) } }
+    #[inline]
+    fn perform_f64(l: f64, r: f64) -> f64 { l / r }
+}
+
+// Swaps operands so that `scalar op vector` can reuse the vector/scalar operators
+#[derive(Debug)]
+struct Reversed<Op> { op: PhantomData<Op> }
+
+impl<Op: ArithmeticOperation> ArithmeticOperation for Reversed<Op> {
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { Op::perform(r, l) }
+    #[inline]
+    fn perform_f64(l: f64, r: f64) -> f64 { Op::perform_f64(r, l) }
+}
+
+
 #[derive(Debug)]
 struct TypeConversionOperator<T, U> {
     input: BufferRef,
@@ -784,3 +2002,10 @@ impl Cast<i64> for u16 { fn cast(self) -> i64 { i64::from(self) } }
 
 impl Cast<i64> for u32 { fn cast(self) -> i64 { i64::from(self) } }
 
+impl Cast<OrderedFloat<f64>> for u8 { fn cast(self) -> OrderedFloat<f64> { OrderedFloat(f64::from(self)) } }
+
+impl Cast<OrderedFloat<f64>> for u16 { fn cast(self) -> OrderedFloat<f64> { OrderedFloat(f64::from(self)) } }
+
+impl Cast<OrderedFloat<f64>> for u32 { fn cast(self) -> OrderedFloat<f64> { OrderedFloat(f64::from(self)) } }
+
+impl Cast<OrderedFloat<f64>> for i64 { fn cast(self) -> OrderedFloat<f64> { OrderedFloat(self as f64) } }
@@ -5,12 +5,18 @@ use std::collections::HashMap;
 use std::ops::BitOr;
 use std::sync::Arc;
 
+use bit_vec::BitVec;
+
+use engine::types::BasicType;
 use mem_store::batch::Batch;
 use mem_store::column::*;
 use mem_store::column_builder::*;
-use mem_store::null_column::NullColumn;
+use mem_store::logical_column::LogicalColumn;
+use mem_store::nullable_column::NullableColumn;
+use mem_store::strings::SharedDictionaryBuilder;
 use scheduler::*;
 use super::extractor;
+use super::extractor::Extractor;
 
 type IngestionTransform = HashMap<String, extractor::Extractor>;
 
@@ -29,6 +35,13 @@ fn auto_ingest<T: Iterator<Item=Vec<String>>>(records: T,
                                               -> Vec<Batch> {
     let num_columns = colnames.len();
     let mut batches = Vec::new();
+    // Columns with a shared dictionary can only be created once all batches have been read
+    let mut shared_dictionaries = colnames.iter()
+        .map(|name| match extractors.get(name) {
+            Some(&Extractor::SharedDictionary) => Some(SharedDictionaryBuilder::new()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
 
     let mut raw_cols = (0..num_columns).map(|_| RawCol::new()).collect::<Vec<_>>();
     let mut row_num = 0usize;
@@ -38,29 +51,53 @@ fn auto_ingest<T: Iterator<Item=Vec<String>>>(records: T,
         }
 
         if row_num % batch_size == batch_size - 1 {
-            batches.push(create_batch(raw_cols, colnames, extractors));
+            batches.push(create_batch(raw_cols, colnames, extractors, &mut shared_dictionaries));
             raw_cols = (0..num_columns).map(|_| RawCol::new()).collect::<Vec<_>>();
         }
         row_num += 1;
     }
 
     if row_num % batch_size != 0 {
-        batches.push(create_batch(raw_cols, colnames, extractors));
+        batches.push(create_batch(raw_cols, colnames, extractors, &mut shared_dictionaries));
+    }
+
+    for (i, builder) in shared_dictionaries.into_iter().enumerate() {
+        if let Some(builder) = builder {
+            for (batch, data) in batches.iter_mut().zip(builder.finalize()) {
+                batch[i] = Some(Column::new(colnames[i].clone(), data));
+            }
+        }
     }
 
-    batches
+    batches.into_iter()
+        .map(|cols| Batch::from(cols.into_iter().map(Option::unwrap).collect::<Vec<_>>()))
+        .collect()
 }
 
-fn create_batch(cols: Vec<RawCol>, colnames: &[String], extractors: &IngestionTransform) -> Batch {
+// Returns `None` for columns with a shared dictionary, which are pushed to their dictionary builder instead
+fn create_batch(cols: Vec<RawCol>,
+                colnames: &[String],
+                extractors: &IngestionTransform,
+                shared_dictionaries: &mut [Option<SharedDictionaryBuilder>]) -> Vec<Option<Column>> {
     let mut mem_store = Vec::new();
     for (i, col) in cols.into_iter().enumerate() {
         let new_column = match extractors.get(&colnames[i]) {
-            Some(extractor) => Column::new(colnames[i].clone(), col.extract(extractor)),
+            Some(&Extractor::Int(extractor)) => Column::new(colnames[i].clone(), col.extract(extractor)),
+            Some(&Extractor::Decimal(scale)) => Column::new(
+                colnames[i].clone(),
+                col.parse(|s| extractor::parse_decimal(s, scale), BasicType::Decimal(scale))),
+            Some(&Extractor::Timestamp) =>
+                Column::new(colnames[i].clone(), col.parse(extractor::parse_timestamp, BasicType::Timestamp)),
+            Some(&Extractor::SharedDictionary) => {
+                shared_dictionaries[i].as_mut().unwrap().push_batch(col.data);
+                mem_store.push(None);
+                continue;
+            }
             None => Column::new(colnames[i].clone(), col.finalize()),
         };
-        mem_store.push(new_column);
+        mem_store.push(Some(new_column));
     }
-    Batch::from(mem_store)
+    mem_store
 }
 
 pub struct CSVIngestionTask {
@@ -120,39 +157,71 @@ impl RawCol {
     }
 
     fn finalize(self) -> Box<ColumnData> {
-        if self.types.contains_string {
+        let present = if self.types.contains_null {
+            Some(self.data.iter().map(|s| !s.is_empty()).collect::<BitVec>())
+        } else {
+            None
+        };
+        // Nulls are stored as some other value in the column to avoid extending its range
+        let placeholder = self.data.iter().find(|s| !s.is_empty()).cloned()
+            .unwrap_or_else(|| if self.types.contains_string { String::new() } else { "0".to_string() });
+        let data = if self.types.contains_string {
             let mut builder = StringColBuilder::new();
             for s in self.data {
-                builder.push(&s);
+                builder.push(if s.is_empty() { &placeholder } else { &s });
+            }
+            builder.finalize()
+        } else if self.types.contains_float {
+            let mut builder = FloatColBuilder::new();
+            for s in self.data {
+                let s = if s.is_empty() { &placeholder } else { &s };
+                let float = s.parse::<f64>()
+                    .unwrap_or_else(|_| unreachable!("{} should be parseable as float", s));
+                builder.push(&float);
             }
             builder.finalize()
-        } else if self.types.contains_int {
+        } else {
+            // Columns without any values are stored as integers that are all null
             let mut builder = IntColBuilder::new();
             for s in self.data {
-                let int = if s.is_empty() {
-                    0
-                } else if let Ok(int) = s.parse::<i64>() {
-                    int
-                } else if let Ok(float) = s.parse::<f64>() {
-                    float as i64
-                } else {
-                    unreachable!("{} should be parseable as int or float", s)
-                };
+                let s = if s.is_empty() { &placeholder } else { &s };
+                let int = s.parse::<i64>()
+                    .unwrap_or_else(|_| unreachable!("{} should be parseable as int", s));
                 builder.push(&int);
             }
             builder.finalize()
-        } else {
-            Box::new(NullColumn::new(self.data.len()))
+        };
+        match present {
+            Some(present) => NullableColumn::new_boxed(data, present),
+            None => data,
         }
     }
 
-    fn extract(self, extractor: &extractor::Extractor) -> Box<ColumnData> {
+    fn extract(self, extractor: fn(&str) -> i64) -> Box<ColumnData> {
         let mut builder = IntColBuilder::new();
         for s in self.data {
             builder.push(&extractor(&s));
         }
         builder.finalize()
     }
+
+    // Stores parsed values in an integer column of the given logical type, values that fail to parse become null
+    fn parse<F: Fn(&str) -> Option<i64>>(self, parse: F, basic_type: BasicType) -> Box<ColumnData> {
+        let values = self.data.iter()
+            .map(|s| parse(s))
+            .collect::<Vec<_>>();
+        let placeholder = values.iter().filter_map(|&v| v).next().unwrap_or(0);
+        let mut builder = IntColBuilder::new();
+        for value in &values {
+            builder.push(&value.unwrap_or(placeholder));
+        }
+        let data = LogicalColumn::new_boxed(builder.finalize(), basic_type);
+        if values.iter().all(|v| v.is_some()) {
+            data
+        } else {
+            NullableColumn::new_boxed(data, values.iter().map(|v| v.is_some()).collect())
+        }
+    }
 }
 
 
@@ -160,35 +229,42 @@ impl RawCol {
 struct ColType {
     contains_string: bool,
     contains_int: bool,
+    contains_float: bool,
     contains_null: bool,
 }
 
 impl ColType {
-    fn new(string: bool, int: bool, null: bool) -> ColType {
-        ColType { contains_string: string, contains_int: int, contains_null: null }
+    fn new(string: bool, int: bool, float: bool, null: bool) -> ColType {
+        ColType { contains_string: string, contains_int: int, contains_float: float, contains_null: null }
     }
 
     fn string() -> ColType {
-        ColType::new(true, false, false)
+        ColType::new(true, false, false, false)
     }
 
     fn int() -> ColType {
-        ColType::new(false, true, false)
+        ColType::new(false, true, false, false)
+    }
+
+    fn float() -> ColType {
+        ColType::new(false, false, true, false)
     }
 
     fn null() -> ColType {
-        ColType::new(false, false, true)
+        ColType::new(false, false, false, true)
     }
 
     fn nothing() -> ColType {
-        ColType::new(false, false, false)
+        ColType::new(false, false, false, false)
     }
 
     fn determine(s: &str) -> ColType {
         if s.is_empty() {
             ColType::null()
-        } else if s.parse::<i64>().is_ok() || s.parse::<f64>().is_ok() {
+        } else if s.parse::<i64>().is_ok() {
             ColType::int()
+        } else if s.parse::<f64>().is_ok() {
+            ColType::float()
         } else {
             ColType::string()
         }
@@ -201,6 +277,7 @@ impl BitOr for ColType {
         ColType {
             contains_string: self.contains_string | rhs.contains_string,
             contains_int: self.contains_int | rhs.contains_int,
+            contains_float: self.contains_float | rhs.contains_float,
             contains_null: self.contains_null | rhs.contains_null,
         }
     }
@@ -1,19 +1,52 @@
-use ingest::chrono::prelude::*;
+use std::iter;
 
-pub type Extractor = fn(&str) -> i64;
+use chrono::prelude::*;
 
-pub fn multiply_by_100(field: &str) -> i64 {
-    if let Ok(int) = field.parse::<i64>() {
-        int * 100
-    } else if let Ok(float) = field.parse::<f64>() {
-        (float as i64) * 100
-    } else {
-        panic!("invalid field {}", &field)
-    }
+#[derive(Clone, Copy)]
+pub enum Extractor {
+    // Converts each field to an integer with a custom function
+    Int(fn(&str) -> i64),
+    // Fixed point number with the given number of digits after the decimal point.
+    // Fields that are empty or not a valid number are loaded as null.
+    Decimal(u8),
+    // Date and time in UTC, stored as seconds since the Unix epoch. Fields that cannot be parsed are loaded as null.
+    Timestamp,
+    // Strings encoded with a single sorted dictionary shared by all batches, which allows batches to be merged by
+    // comparing dictionary codes. Empty fields are loaded as null.
+    SharedDictionary,
+}
+
+// Parses `YYYY-MM-DD HH:MM:SS`, `YYYY-MM-DDTHH:MM:SS` or `YYYY-MM-DD` into seconds since the Unix epoch
+pub fn parse_timestamp(field: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(field, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(field, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| NaiveDate::parse_from_str(field, "%Y-%m-%d").map(|date| date.and_hms(0, 0, 0)))
+        .ok()
+        .map(|date_time| date_time.timestamp())
 }
 
-pub fn date_time(field: &str) -> i64 {
-    Utc.datetime_from_str(field, "%Y-%m-%d %H:%M:%S")
-        .expect(&format!("Failed to parse {} as date time", &field))
-        .timestamp()
-}
\ No newline at end of file
+// Parses a decimal number into an integer scaled by 10^scale, rounding half away from zero any additional digits
+pub fn parse_decimal(field: &str, scale: u8) -> Option<i64> {
+    let (negative, digits) = match field.chars().next() {
+        Some('-') => (true, &field[1..]),
+        Some('+') => (false, &field[1..]),
+        _ => (false, field),
+    };
+    let (integer, fraction) = match digits.find('.') {
+        Some(index) => (&digits[..index], &digits[index + 1..]),
+        None => (digits, ""),
+    };
+    if integer.is_empty() && fraction.is_empty() ||
+        !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let padded_fraction = fraction.bytes().chain(iter::repeat(b'0')).take(scale as usize);
+    let mut value = 0i64;
+    for digit in integer.bytes().chain(padded_fraction) {
+        value = value.checked_mul(10)?.checked_add(i64::from(digit - b'0'))?;
+    }
+    if fraction.as_bytes().get(scale as usize).map_or(false, |&digit| digit >= b'5') {
+        value = value.checked_add(1)?;
+    }
+    Some(if negative { -value } else { value })
+}
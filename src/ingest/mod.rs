@@ -1,5 +1,3 @@
-extern crate chrono;
-
 pub mod csv_loader;
 pub mod raw_val;
 pub mod input_column;
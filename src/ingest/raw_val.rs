@@ -1,11 +1,13 @@
 use std::fmt;
 use engine::types::BasicType;
+use ordered_float::OrderedFloat;
 
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 pub enum RawVal {
     Int(i64),
     Str(String),
+    Float(OrderedFloat<f64>),
     Null,
 }
 
@@ -14,9 +16,18 @@ impl RawVal {
         match *self {
             RawVal::Int(_) => BasicType::Integer,
             RawVal::Str(_) => BasicType::String,
+            RawVal::Float(_) => BasicType::Float,
             RawVal::Null => BasicType::Null,
         }
     }
+
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            RawVal::Int(i) => i == 0,
+            RawVal::Float(f) => f.into_inner() == 0.0,
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Display for RawVal {
@@ -25,6 +36,7 @@ impl fmt::Display for RawVal {
             RawVal::Null => write!(f, "null"),
             RawVal::Int(i) => write!(f, "{}", i),
             RawVal::Str(ref s) => write!(f, "\"{}\"", s),
+            RawVal::Float(x) => write!(f, "{}", x),
         }
     }
 }
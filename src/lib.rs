@@ -8,6 +8,7 @@ extern crate failure_derive;
 extern crate log;
 extern crate bincode;
 extern crate bit_vec;
+extern crate chrono;
 extern crate failure;
 extern crate futures;
 extern crate futures_channel;
@@ -15,6 +16,7 @@ extern crate heapsize;
 extern crate itertools;
 extern crate num;
 extern crate num_cpus;
+extern crate ordered_float;
 extern crate regex;
 extern crate seahash;
 extern crate serde;
@@ -0,0 +1,178 @@
+// Stream of values with arbitrary bit width, packed into 64 bit words
+pub struct BitWriter {
+    data: Vec<u64>,
+    // Number of bits used in the last word
+    offset: u32,
+}
+
+impl BitWriter {
+    pub fn new() -> BitWriter {
+        BitWriter { data: Vec::new(), offset: 64 }
+    }
+
+    // Appends the lowest `bits` bits of `value`, most significant bit first
+    pub fn write(&mut self, value: u64, bits: u32) {
+        if bits == 0 {
+            return;
+        }
+        let value = if bits == 64 { value } else { value & ((1 << bits) - 1) };
+        if self.offset == 64 {
+            self.data.push(0);
+            self.offset = 0;
+        }
+        let free = 64 - self.offset;
+        let last = self.data.len() - 1;
+        if bits <= free {
+            self.data[last] |= value << (free - bits);
+            self.offset += bits;
+        } else {
+            self.data[last] |= value >> (bits - free);
+            self.data.push(value << (64 - (bits - free)));
+            self.offset = bits - free;
+        }
+    }
+
+    pub fn into_vec(mut self) -> Vec<u64> {
+        self.data.shrink_to_fit();
+        self.data
+    }
+}
+
+
+pub struct BitReader<'a> {
+    data: &'a [u64],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &[u64]) -> BitReader {
+        BitReader { data, position: 0 }
+    }
+
+    pub fn read(&mut self, bits: u32) -> u64 {
+        if bits == 0 {
+            return 0;
+        }
+        let word = self.position / 64;
+        let offset = (self.position % 64) as u32;
+        self.position += bits as usize;
+        let available = 64 - offset;
+        let high = self.data[word] << offset;
+        if bits <= available {
+            high >> (64 - bits)
+        } else {
+            (high >> (64 - bits)) | (self.data[word + 1] >> (64 - (bits - available)))
+        }
+    }
+}
+
+
+// Integer types that fixed width bit-packed values are unpacked into
+pub trait PackedInt: Copy {
+    fn from_u64(value: u64) -> Self;
+}
+
+impl PackedInt for u8 {
+    #[inline]
+    fn from_u64(value: u64) -> u8 { value as u8 }
+}
+
+impl PackedInt for u16 {
+    #[inline]
+    fn from_u64(value: u64) -> u16 { value as u16 }
+}
+
+impl PackedInt for u32 {
+    #[inline]
+    fn from_u64(value: u64) -> u32 { value as u32 }
+}
+
+impl PackedInt for i64 {
+    #[inline]
+    fn from_u64(value: u64) -> i64 { value as i64 }
+}
+
+// Number of words required to pack `len` values of `width` bits
+pub fn packed_len(len: usize, width: u32) -> usize {
+    // Trailing word allows any value to be read from two adjacent words without bounds checks
+    (len * width as usize + 63) / 64 + 1
+}
+
+// Packs `value - offset` for each value into `width` bits, least significant bits first
+pub fn pack(values: &[i64], offset: i64, width: u32) -> Vec<u64> {
+    let mut data = vec![0u64; packed_len(values.len(), width)];
+    if width == 0 {
+        return data;
+    }
+    for (i, &value) in values.iter().enumerate() {
+        let value = value.wrapping_sub(offset) as u64;
+        let bit = i * width as usize;
+        let (word, shift) = (bit / 64, (bit % 64) as u32);
+        data[word] |= value << shift;
+        if shift + width > 64 {
+            data[word + 1] |= value >> (64 - shift);
+        }
+    }
+    data
+}
+
+// Unpacks the value at `index`
+#[inline]
+pub fn unpack_one(data: &[u64], width: u32, index: usize) -> u64 {
+    if width == 0 {
+        return 0;
+    }
+    let bit = index * width as usize;
+    let (word, shift) = (bit / 64, bit % 64);
+    let combined = u128::from(data[word]) | (u128::from(data[word + 1]) << 64);
+    (combined >> shift) as u64 & mask(width)
+}
+
+// Unpacks the first `len` values. Blocks of 64 values occupy exactly `width` words and are unpacked by a loop with a
+// fixed number of iterations and no branches, which the compiler unrolls and vectorizes.
+pub fn unpack<T: PackedInt>(data: &[u64], width: u32, len: usize) -> Vec<T> {
+    let mut result = Vec::with_capacity(len);
+    if width == 0 {
+        result.resize(len, T::from_u64(0));
+        return result;
+    }
+    let mask = mask(width);
+    let width = width as usize;
+    let mut block = [T::from_u64(0); 64];
+    for b in 0..len / 64 {
+        let words = &data[b * width..];
+        for (i, value) in block.iter_mut().enumerate() {
+            let bit = i * width;
+            let combined = u128::from(words[bit / 64]) | (u128::from(words[bit / 64 + 1]) << 64);
+            *value = T::from_u64((combined >> (bit % 64)) as u64 & mask);
+        }
+        result.extend_from_slice(&block);
+    }
+    for i in len / 64 * 64..len {
+        result.push(T::from_u64(unpack_one(data, width as u32, i)));
+    }
+    result
+}
+
+fn mask(width: u32) -> u64 {
+    if width == 64 { !0 } else { (1 << width) - 1 }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        for &width in &[0, 1, 3, 9, 17, 31, 32, 63] {
+            let mask = mask(width) as i64;
+            let values = (0..200).map(|i: i64| (i.wrapping_mul(0x9E37_79B9_7F4A_7C15u64 as i64) & mask) - 1000).collect::<Vec<i64>>();
+            let data = pack(&values, -1000, width);
+            assert_eq!(data.len(), packed_len(values.len(), width));
+            let unpacked = unpack::<i64>(&data, width, values.len());
+            assert_eq!(unpacked.iter().map(|v| v - 1000).collect::<Vec<_>>(), values);
+            assert_eq!(unpack_one(&data, width, 131) as i64 - 1000, values[131]);
+        }
+    }
+}
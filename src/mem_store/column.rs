@@ -38,6 +38,11 @@ pub trait ColumnData: HeapSizeOf + Send + Sync {
     fn basic_type(&self) -> BasicType;
     fn to_codec(&self) -> Option<&ColumnCodec> { None }
     fn len(&self) -> usize;
+    // Bitmap that is unset for null values, `None` if the column does not contain nulls
+    fn present(&self) -> Option<&BitVec> { None }
+    // Copy of a column with compressed data that decompresses it as it is accessed and holds on to the decompressed
+    // data until it is dropped, `None` if the column can be decoded as is
+    fn decompressing_copy(&self) -> Option<Box<ColumnData>> { None }
 
     fn full_type(&self) -> Type {
         Type::new(self.basic_type(), self.to_codec())
@@ -62,6 +67,14 @@ pub trait ColumnCodec: ColumnData {
     fn encoding_range(&self) -> Option<(i64, i64)>;
     fn unwrap_decode<'a>(&'a self, data: &TypedVec<'a>) -> BoxedVec<'a>;
 
+    fn dictionary(&self) -> Option<&[Option<String>]> { None }
+
+    // Whether all batches of the column were encoded with the same sorted dictionary
+    fn has_shared_dictionary(&self) -> bool { false }
+
+    // Value of each run of identical values and the index of the first row after it, for run-length encoded columns
+    fn runs(&self) -> Option<(&[i64], &[u32])> { None }
+
     fn encode_str(&self, _: &str) -> RawVal {
         panic!("encode_str not supported")
     }
@@ -1,4 +1,7 @@
+use mem_store::floats::*;
 use mem_store::integers::*;
+use mem_store::delta::*;
+use mem_store::run_length::*;
 use mem_store::column::*;
 use mem_store::strings::*;
 use std::cmp;
@@ -66,7 +69,38 @@ impl ColumnBuilder<i64> for IntColBuilder {
     }
 
     fn finalize(self) -> Box<ColumnData> {
-        IntegerColumn::new_boxed(self.data, self.min, self.max)
+        // Pick whichever encoding results in the smallest column, preferring offset encoding which is fastest to query
+        let offset_size = IntegerColumn::encoded_size(self.data.len(), self.min, self.max);
+        let run_length_size = RunLengthColumn::encoded_size(&self.data);
+        let delta_size = DeltaColumn::encoded_size(&self.data);
+        if run_length_size < offset_size && run_length_size <= delta_size {
+            RunLengthColumn::new_boxed(&self.data, self.min, self.max)
+        } else if delta_size < offset_size {
+            DeltaColumn::new_boxed(&self.data, self.min, self.max)
+        } else {
+            IntegerColumn::new_boxed(self.data, self.min, self.max)
+        }
+    }
+}
+
+
+pub struct FloatColBuilder {
+    data: Vec<f64>,
+}
+
+impl FloatColBuilder {
+    pub fn new() -> FloatColBuilder {
+        FloatColBuilder { data: Vec::new() }
+    }
+}
+
+impl ColumnBuilder<f64> for FloatColBuilder {
+    fn push(&mut self, elem: &f64) {
+        self.data.push(*elem);
+    }
+
+    fn finalize(self) -> Box<ColumnData> {
+        FloatColumn::new_boxed(&self.data)
     }
 }
 
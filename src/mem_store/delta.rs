@@ -0,0 +1,152 @@
+use std::mem;
+
+use bit_vec::BitVec;
+use engine::typed_vec::{BoxedVec, TypedVec};
+use engine::types::*;
+use heapsize::HeapSizeOf;
+use ingest::raw_val::RawVal;
+use mem_store::bits::{BitReader, BitWriter};
+use mem_store::column::{ColumnData, ColumnCodec};
+
+
+// Stores the difference between consecutive values, minus the smallest difference, with the minimum number of bits.
+// Timestamps of monotonic event streams usually need only a handful of bits per value.
+pub struct DeltaColumn {
+    first: i64,
+    min_delta: i64,
+    width: u32,
+    data: Vec<u64>,
+    len: usize,
+    min: i64,
+    max: i64,
+}
+
+impl DeltaColumn {
+    pub fn new_boxed(values: &[i64], min: i64, max: i64) -> Box<ColumnData> {
+        Box::new(DeltaColumn::new(values, min, max))
+    }
+
+    fn new(values: &[i64], min: i64, max: i64) -> DeltaColumn {
+        let (min_delta, width) = delta_width(values);
+        let mut writer = BitWriter::new();
+        for window in values.windows(2) {
+            // Wrapping arithmetic yields the correct bits even if the difference overflows i64
+            writer.write(window[1].wrapping_sub(window[0]).wrapping_sub(min_delta) as u64, width);
+        }
+        DeltaColumn {
+            first: values.first().cloned().unwrap_or(0),
+            min_delta,
+            width,
+            data: writer.into_vec(),
+            len: values.len(),
+            min,
+            max,
+        }
+    }
+
+    // Number of bytes required to store `values`
+    pub fn encoded_size(values: &[i64]) -> usize {
+        let (_, width) = delta_width(values);
+        let bits = values.len().saturating_sub(1) * width as usize;
+        (bits + 63) / 64 * mem::size_of::<u64>()
+    }
+
+    fn decode(&self) -> Vec<i64> {
+        let mut result = Vec::with_capacity(self.len);
+        if self.len == 0 {
+            return result;
+        }
+        let mut reader = BitReader::new(&self.data);
+        let mut previous = self.first;
+        result.push(previous);
+        for _ in 1..self.len {
+            previous = previous.wrapping_add(self.min_delta).wrapping_add(reader.read(self.width) as i64);
+            result.push(previous);
+        }
+        result
+    }
+}
+
+// Smallest difference between consecutive values and number of bits required to store all differences relative to it
+fn delta_width(values: &[i64]) -> (i64, u32) {
+    let mut min_delta = i64::max_value();
+    let mut max_delta = i64::min_value();
+    for window in values.windows(2) {
+        let delta = window[1].wrapping_sub(window[0]);
+        min_delta = min_delta.min(delta);
+        max_delta = max_delta.max(delta);
+    }
+    if min_delta > max_delta {
+        return (0, 0);
+    }
+    (min_delta, 64 - (max_delta.wrapping_sub(min_delta) as u64).leading_zeros())
+}
+
+impl ColumnData for DeltaColumn {
+    fn collect_decoded(&self) -> BoxedVec {
+        TypedVec::owned(self.decode())
+    }
+
+    fn filter_decode(&self, filter: &BitVec) -> BoxedVec {
+        let result = self.decode().into_iter().zip(filter.iter())
+            .filter(|&(_, select)| select)
+            .map(|(value, _)| value)
+            .collect::<Vec<_>>();
+        TypedVec::owned(result)
+    }
+
+    fn index_decode(&self, filter: &[usize]) -> BoxedVec {
+        let decoded = self.decode();
+        TypedVec::owned(filter.iter().map(|&i| decoded[i]).collect::<Vec<_>>())
+    }
+
+    fn basic_type(&self) -> BasicType { BasicType::Integer }
+
+    fn to_codec(&self) -> Option<&ColumnCodec> { Some(self as &ColumnCodec) }
+
+    fn len(&self) -> usize { self.len }
+}
+
+// Encoded values are the decompressed integers, which allows range based optimizations like packed grouping keys
+impl ColumnCodec for DeltaColumn {
+    fn get_encoded(&self) -> BoxedVec { self.collect_decoded() }
+    fn filter_encoded(&self, filter: &BitVec) -> BoxedVec { self.filter_decode(filter) }
+    fn index_encoded(&self, filter: &[usize]) -> BoxedVec { self.index_decode(filter) }
+
+    fn unwrap_decode<'a>(&'a self, data: &TypedVec<'a>) -> BoxedVec<'a> {
+        TypedVec::owned(data.cast_ref_i64().to_vec())
+    }
+
+    fn encode_int(&self, val: i64) -> RawVal { RawVal::Int(val) }
+
+    fn is_summation_preserving(&self) -> bool { true }
+    fn is_order_preserving(&self) -> bool { true }
+    fn is_positive_integer(&self) -> bool { self.min >= 0 }
+    fn encoding_type(&self) -> EncodingType { EncodingType::I64 }
+    fn encoding_range(&self) -> Option<(i64, i64)> { Some((self.min, self.max)) }
+}
+
+impl HeapSizeOf for DeltaColumn {
+    fn heap_size_of_children(&self) -> usize {
+        self.data.heap_size_of_children()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let values = vec![1520000000, 1520000015, 1520000030, 1520000031, 1520000046, 1520000046, 1520000060];
+        let column = DeltaColumn::new(&values, 1520000000, 1520000060);
+        assert_eq!(column.decode(), values);
+        assert_eq!(column.width, 4);
+        assert_eq!(DeltaColumn::encoded_size(&values), 8);
+
+        let extremes = vec![i64::max_value(), i64::min_value(), 0, -1, i64::max_value()];
+        let column = DeltaColumn::new(&extremes, i64::min_value(), i64::max_value());
+        assert_eq!(column.decode(), extremes);
+    }
+}
@@ -0,0 +1,129 @@
+use bit_vec::BitVec;
+use engine::typed_vec::{BoxedVec, TypedVec};
+use engine::types::*;
+use heapsize::HeapSizeOf;
+use mem_store::bits::{BitReader, BitWriter};
+use mem_store::column::ColumnData;
+use ordered_float::OrderedFloat;
+
+
+// Floats compressed with the XOR scheme from Facebook's Gorilla paper (http://www.vldb.org/pvldb/vol8/p1816-teller.pdf).
+// Each value is XORed with its predecessor and only the meaningful bits of the result are stored.
+pub struct FloatColumn {
+    data: Vec<u64>,
+    len: usize,
+}
+
+impl FloatColumn {
+    pub fn new_boxed(values: &[f64]) -> Box<ColumnData> {
+        Box::new(FloatColumn::new(values))
+    }
+
+    fn new(values: &[f64]) -> FloatColumn {
+        let mut writer = BitWriter::new();
+        let mut previous = 0u64;
+        // Leading and trailing zeros of the currently stored window of meaningful bits
+        let mut window: Option<(u32, u32)> = None;
+        for (i, value) in values.iter().enumerate() {
+            let bits = value.to_bits();
+            if i == 0 {
+                writer.write(bits, 64);
+                previous = bits;
+                continue;
+            }
+            let xor = bits ^ previous;
+            previous = bits;
+            if xor == 0 {
+                writer.write(0, 1);
+                continue;
+            }
+            writer.write(1, 1);
+            // Leading zero count is stored in 5 bits
+            let leading = xor.leading_zeros().min(31);
+            let trailing = xor.trailing_zeros();
+            match window {
+                Some((prev_leading, prev_trailing)) if leading >= prev_leading && trailing >= prev_trailing => {
+                    writer.write(0, 1);
+                    writer.write(xor >> prev_trailing, 64 - prev_leading - prev_trailing);
+                }
+                _ => {
+                    let meaningful = 64 - leading - trailing;
+                    writer.write(1, 1);
+                    writer.write(u64::from(leading), 5);
+                    writer.write(u64::from(meaningful - 1), 6);
+                    writer.write(xor >> trailing, meaningful);
+                    window = Some((leading, trailing));
+                }
+            }
+        }
+        FloatColumn { data: writer.into_vec(), len: values.len() }
+    }
+
+    fn decode(&self) -> Vec<OrderedFloat<f64>> {
+        let mut result = Vec::with_capacity(self.len);
+        if self.len == 0 {
+            return result;
+        }
+        let mut reader = BitReader::new(&self.data);
+        let mut previous = reader.read(64);
+        result.push(OrderedFloat(f64::from_bits(previous)));
+        let mut leading = 0;
+        let mut trailing = 0;
+        for _ in 1..self.len {
+            if reader.read(1) == 1 {
+                if reader.read(1) == 1 {
+                    leading = reader.read(5) as u32;
+                    let meaningful = reader.read(6) as u32 + 1;
+                    trailing = 64 - leading - meaningful;
+                }
+                previous ^= reader.read(64 - leading - trailing) << trailing;
+            }
+            result.push(OrderedFloat(f64::from_bits(previous)));
+        }
+        result
+    }
+}
+
+impl ColumnData for FloatColumn {
+    fn collect_decoded(&self) -> BoxedVec {
+        TypedVec::owned(self.decode())
+    }
+
+    fn filter_decode(&self, filter: &BitVec) -> BoxedVec {
+        let result = self.decode().into_iter().zip(filter.iter())
+            .filter(|&(_, select)| select)
+            .map(|(value, _)| value)
+            .collect::<Vec<_>>();
+        TypedVec::owned(result)
+    }
+
+    fn index_decode(&self, filter: &[usize]) -> BoxedVec {
+        let decoded = self.decode();
+        TypedVec::owned(filter.iter().map(|&i| decoded[i]).collect::<Vec<_>>())
+    }
+
+    fn basic_type(&self) -> BasicType { BasicType::Float }
+
+    fn len(&self) -> usize { self.len }
+}
+
+impl HeapSizeOf for FloatColumn {
+    fn heap_size_of_children(&self) -> usize {
+        self.data.heap_size_of_children()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let values = vec![1.0, 1.0, 1.5, -3.25, 1e300, 0.1, 0.2, 0.30000000000000004, 0.0, -0.0, 12.0, 24.0, 24.5];
+        let column = FloatColumn::new(&values);
+        let decoded = column.decode().into_iter().map(|f| f.into_inner().to_bits()).collect::<Vec<_>>();
+        assert_eq!(decoded, values.iter().map(|f| f.to_bits()).collect::<Vec<_>>());
+        assert!(column.data.len() < values.len());
+    }
+}
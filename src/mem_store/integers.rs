@@ -1,5 +1,8 @@
+use std::cmp;
 use std::convert::From;
-use std::{u8, u16, u32};
+use std::marker::PhantomData;
+use std::mem;
+use std::{u8, u16, u32, usize};
 
 use bit_vec::BitVec;
 use engine::typed_vec::{BoxedVec, TypedVec};
@@ -7,6 +10,7 @@ use engine::types::*;
 use engine::*;
 use heapsize::HeapSizeOf;
 use ingest::raw_val::RawVal;
+use mem_store::bits::{self, PackedInt};
 use mem_store::column::{ColumnData, ColumnCodec};
 use mem_store::point_codec::PointCodec;
 
@@ -19,7 +23,17 @@ impl IntegerColumn {
     // TODO(clemens): do not subtract offset if it does not change encoding size
     pub fn new_boxed(mut values: Vec<i64>, min: i64, max: i64) -> Box<ColumnData> {
         let maximum = (max - min) as usize;
-        if max - min <= From::from(u8::MAX) {
+        if BitPackedColumn::<i64>::encoded_size(values.len(), min, max) < IntegerColumn::offset_size(values.len(), min, max) {
+            if max - min <= From::from(u8::MAX) {
+                Box::new(BitPackedColumn::<u8>::new(&values, min, maximum))
+            } else if max - min <= From::from(u16::MAX) {
+                Box::new(BitPackedColumn::<u16>::new(&values, min, maximum))
+            } else if max - min <= From::from(u32::MAX) {
+                Box::new(BitPackedColumn::<u32>::new(&values, min, maximum))
+            } else {
+                Box::new(BitPackedColumn::<i64>::new(&values, min, maximum))
+            }
+        } else if max - min <= From::from(u8::MAX) {
             Box::new(IntegerOffsetColumn::<u8>::new(values, min, maximum))
         } else if max - min <= From::from(u16::MAX) {
             Box::new(IntegerOffsetColumn::<u16>::new(values, min, maximum))
@@ -30,6 +44,24 @@ impl IntegerColumn {
             Box::new(IntegerColumn { values })
         }
     }
+
+    // Number of bytes required to store `len` values between `min` and `max`
+    pub fn encoded_size(len: usize, min: i64, max: i64) -> usize {
+        cmp::min(IntegerColumn::offset_size(len, min, max), BitPackedColumn::<i64>::encoded_size(len, min, max))
+    }
+
+    fn offset_size(len: usize, min: i64, max: i64) -> usize {
+        let bytes = if max - min <= From::from(u8::MAX) {
+            1
+        } else if max - min <= From::from(u16::MAX) {
+            2
+        } else if max - min <= From::from(u32::MAX) {
+            4
+        } else {
+            8
+        };
+        len * bytes
+    }
 }
 
 impl ColumnData for IntegerColumn {
@@ -170,6 +202,118 @@ impl<'a, T: IntVecType<T> + 'a> ColumnCodec for IntegerOffsetColumn<T> {
     fn encoding_range(&self) -> Option<(i64, i64)> { Some((0, self.maximum as i64)) }
 }
 
+// Frame of reference encoding which stores the difference to the minimum value with the smallest possible number of
+// bits. Encoded values are unpacked into the smallest integer type that fits.
+struct BitPackedColumn<T> {
+    data: Vec<u64>,
+    width: u32,
+    len: usize,
+    offset: i64,
+    maximum: usize,
+    t: PhantomData<T>,
+}
+
+impl<T: IntVecType<T> + PackedInt> BitPackedColumn<T> {
+    fn new(values: &[i64], offset: i64, maximum: usize) -> BitPackedColumn<T> {
+        let width = 64 - (maximum as u64).leading_zeros();
+        BitPackedColumn {
+            data: bits::pack(values, offset, width),
+            width,
+            len: values.len(),
+            offset,
+            maximum,
+            t: PhantomData,
+        }
+    }
+
+    // Number of bytes required to store `len` values between `min` and `max`
+    fn encoded_size(len: usize, min: i64, max: i64) -> usize {
+        match max.checked_sub(min) {
+            Some(range) => bits::packed_len(len, 64 - range.leading_zeros()) * mem::size_of::<u64>(),
+            None => usize::MAX,
+        }
+    }
+
+    fn unpack(&self) -> Vec<T> {
+        bits::unpack(&self.data, self.width, self.len)
+    }
+
+    fn decode_one(&self, index: usize) -> i64 {
+        bits::unpack_one(&self.data, self.width, index) as i64 + self.offset
+    }
+}
+
+impl<T: IntVecType<T> + PackedInt> ColumnData for BitPackedColumn<T> {
+    fn collect_decoded(&self) -> BoxedVec {
+        let mut result = bits::unpack::<i64>(&self.data, self.width, self.len);
+        for value in &mut result {
+            *value += self.offset;
+        }
+        TypedVec::owned(result)
+    }
+
+    fn filter_decode(&self, filter: &BitVec) -> BoxedVec {
+        let mut result = Vec::new();
+        for (i, select) in filter.iter().enumerate() {
+            if select {
+                result.push(self.decode_one(i));
+            }
+        }
+        TypedVec::owned(result)
+    }
+
+    fn index_decode(&self, filter: &[usize]) -> BoxedVec {
+        TypedVec::owned(filter.iter().map(|&i| self.decode_one(i)).collect::<Vec<_>>())
+    }
+
+    fn basic_type(&self) -> BasicType { BasicType::Integer }
+
+    fn to_codec(&self) -> Option<&ColumnCodec> { Some(self as &ColumnCodec) }
+
+    fn len(&self) -> usize { self.len }
+}
+
+impl<T: IntVecType<T> + PackedInt> ColumnCodec for BitPackedColumn<T> {
+    fn get_encoded(&self) -> BoxedVec {
+        TypedVec::owned(self.unpack())
+    }
+
+    fn unwrap_decode<'a>(&'a self, data: &TypedVec<'a>) -> BoxedVec<'a> {
+        let mut result = Vec::with_capacity(data.len());
+        for value in T::unwrap(data) {
+            result.push(value.to_i64().unwrap() + self.offset);
+        }
+        TypedVec::owned(result)
+    }
+
+    fn filter_encoded(&self, filter: &BitVec) -> BoxedVec {
+        let mut result = Vec::new();
+        for (i, select) in filter.iter().enumerate() {
+            if select {
+                result.push(T::from_u64(bits::unpack_one(&self.data, self.width, i)));
+            }
+        }
+        TypedVec::owned(result)
+    }
+
+    fn index_encoded(&self, filter: &[usize]) -> BoxedVec {
+        let result = filter.iter()
+            .map(|&i| T::from_u64(bits::unpack_one(&self.data, self.width, i)))
+            .collect::<Vec<_>>();
+        TypedVec::owned(result)
+    }
+
+    fn encode_int(&self, val: i64) -> RawVal {
+        RawVal::Int(val - self.offset)
+    }
+
+    fn is_summation_preserving(&self) -> bool { self.offset == 0 }
+    fn is_order_preserving(&self) -> bool { true }
+    fn is_positive_integer(&self) -> bool { true }
+    fn encoding_type(&self) -> EncodingType { T::t() }
+    fn encoding_range(&self) -> Option<(i64, i64)> { Some((0, self.maximum as i64)) }
+}
+
 impl HeapSizeOf for IntegerColumn {
     fn heap_size_of_children(&self) -> usize {
         self.values.heap_size_of_children()
@@ -182,3 +326,9 @@ impl<T:     HeapSizeOf> HeapSizeOf for IntegerOffsetColumn<T> {
     }
 }
 
+
+impl<T> HeapSizeOf for BitPackedColumn<T> {
+    fn heap_size_of_children(&self) -> usize {
+        self.data.heap_size_of_children()
+    }
+}
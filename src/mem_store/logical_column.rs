@@ -0,0 +1,46 @@
+use bit_vec::BitVec;
+use engine::typed_vec::BoxedVec;
+use engine::types::*;
+use heapsize::HeapSizeOf;
+use mem_store::column::*;
+
+
+// Wraps integer column data whose values represent some other type, e.g. decimals scaled by 10^scale or timestamps
+pub struct LogicalColumn {
+    data: Box<ColumnData>,
+    basic_type: BasicType,
+}
+
+impl LogicalColumn {
+    pub fn new_boxed(data: Box<ColumnData>, basic_type: BasicType) -> Box<ColumnData> {
+        Box::new(LogicalColumn { data, basic_type })
+    }
+}
+
+impl ColumnData for LogicalColumn {
+    fn collect_decoded(&self) -> BoxedVec {
+        self.data.collect_decoded()
+    }
+
+    fn filter_decode(&self, filter: &BitVec) -> BoxedVec {
+        self.data.filter_decode(filter)
+    }
+
+    fn index_decode(&self, indices: &[usize]) -> BoxedVec {
+        self.data.index_decode(indices)
+    }
+
+    fn basic_type(&self) -> BasicType { self.basic_type }
+
+    fn to_codec(&self) -> Option<&ColumnCodec> { self.data.to_codec() }
+
+    fn len(&self) -> usize { self.data.len() }
+
+    fn present(&self) -> Option<&BitVec> { self.data.present() }
+}
+
+impl HeapSizeOf for LogicalColumn {
+    fn heap_size_of_children(&self) -> usize {
+        self.data.heap_size_of_children()
+    }
+}
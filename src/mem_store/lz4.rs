@@ -0,0 +1,132 @@
+use std::cmp::min;
+
+// Compression in the LZ4 block format: a sequence of literal runs, each followed by a reference to a previous occurrence
+// of the subsequent bytes. Favours decompression speed over compression ratio.
+
+const MIN_MATCH: usize = 4;
+// Matches must not start within the last 12 bytes and the last 5 bytes are always literals
+const MATCH_LIMIT: usize = 12;
+const LAST_LITERALS: usize = 5;
+const MAX_OFFSET: usize = 0xFFFF;
+const HASH_BITS: u32 = 12;
+
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2);
+    // Position + 1 of the last occurrence of each hashed 4 byte sequence
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i + MATCH_LIMIT < input.len() {
+        let sequence = read_u32(input, i);
+        let hash = (sequence.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+        let candidate = table[hash];
+        table[hash] = i + 1;
+        if candidate == 0 || i - (candidate - 1) > MAX_OFFSET || read_u32(input, candidate - 1) != sequence {
+            i += 1;
+            continue;
+        }
+        let candidate = candidate - 1;
+        let max_len = input.len() - LAST_LITERALS - i;
+        let mut match_len = MIN_MATCH;
+        while match_len < max_len && input[candidate + match_len] == input[i + match_len] {
+            match_len += 1;
+        }
+        write_sequence(&mut output, &input[literal_start..i], Some((i - candidate, match_len)));
+        i += match_len;
+        literal_start = i;
+    }
+    write_sequence(&mut output, &input[literal_start..], None);
+    output.shrink_to_fit();
+    output
+}
+
+// Appends the decompressed bytes to `output`
+pub fn decompress(input: &[u8], output: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < input.len() {
+        let token = input[i];
+        i += 1;
+        let mut literal_len = (token >> 4) as usize;
+        if literal_len == 15 {
+            literal_len += read_length(input, &mut i);
+        }
+        output.extend_from_slice(&input[i..i + literal_len]);
+        i += literal_len;
+        // Last sequence consists only of literals
+        if i == input.len() {
+            break;
+        }
+
+        let offset = input[i] as usize | (input[i + 1] as usize) << 8;
+        i += 2;
+        let mut match_len = (token & 15) as usize;
+        if match_len == 15 {
+            match_len += read_length(input, &mut i);
+        }
+        // Matches may overlap with the bytes they produce, which requires copying byte by byte
+        let start = output.len() - offset;
+        for j in start..start + match_len + MIN_MATCH {
+            let byte = output[j];
+            output.push(byte);
+        }
+    }
+}
+
+fn write_sequence(output: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_code = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    output.push((min(literals.len(), 15) << 4 | min(match_code, 15)) as u8);
+    if literals.len() >= 15 {
+        write_length(output, literals.len() - 15);
+    }
+    output.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        output.push(offset as u8);
+        output.push((offset >> 8) as u8);
+        if match_code >= 15 {
+            write_length(output, match_code - 15);
+        }
+    }
+}
+
+fn write_length(output: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        output.push(255);
+        len -= 255;
+    }
+    output.push(len as u8);
+}
+
+fn read_length(input: &[u8], i: &mut usize) -> usize {
+    let mut len = 0;
+    loop {
+        let byte = input[*i];
+        *i += 1;
+        len += byte as usize;
+        if byte != 255 {
+            return len;
+        }
+    }
+}
+
+fn read_u32(data: &[u8], i: usize) -> u32 {
+    u32::from(data[i]) | u32::from(data[i + 1]) << 8 | u32::from(data[i + 2]) << 16 | u32::from(data[i + 3]) << 24
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let repetitive = "{\"firstname\":\"Victor\",\"lastname\":\"Simpson\"},".repeat(40);
+        let long_literals = (0..2000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect::<Vec<_>>();
+        for input in &[repetitive.as_bytes(), &long_literals[..], b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", b"abc", b""] {
+            let compressed = compress(input);
+            let mut output = Vec::new();
+            decompress(&compressed, &mut output);
+            assert_eq!(&output[..], *input);
+        }
+        assert!(compress(repetitive.as_bytes()).len() < repetitive.len() / 10);
+    }
+}
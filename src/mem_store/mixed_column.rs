@@ -53,6 +53,7 @@ impl RawVal {
         match *self {
             RawVal::Null => Val::Null,
             RawVal::Int(i) => Val::Integer(i),
+            RawVal::Float(x) => Val::Float(x),
             RawVal::Str(ref string) => Val::Str(string),
         }
     }
@@ -61,7 +62,7 @@ impl RawVal {
 impl HeapSizeOf for RawVal {
     fn heap_size_of_children(&self) -> usize {
         match *self {
-            RawVal::Null | RawVal::Int(_) => 0,
+            RawVal::Null | RawVal::Int(_) | RawVal::Float(_) => 0,
             RawVal::Str(ref r) => r.heap_size_of_children(),
         }
     }
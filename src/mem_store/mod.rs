@@ -5,7 +5,14 @@ pub mod value;
 pub mod column_builder;
 pub mod table;
 pub mod null_column;
+pub mod nullable_column;
+pub mod logical_column;
 pub mod raw_col;
 mod integers;
-mod strings;
+mod delta;
+mod run_length;
+mod bits;
+mod lz4;
+mod floats;
+pub mod strings;
 mod mixed_column;
@@ -0,0 +1,50 @@
+use bit_vec::BitVec;
+use engine::typed_vec::BoxedVec;
+use engine::types::*;
+use heapsize::HeapSizeOf;
+use mem_store::column::*;
+
+
+// Wraps column data that contains nulls. Null values are stored as an arbitrary placeholder in `data`.
+pub struct NullableColumn {
+    data: Box<ColumnData>,
+    present: BitVec,
+}
+
+impl NullableColumn {
+    pub fn new_boxed(data: Box<ColumnData>, present: BitVec) -> Box<ColumnData> {
+        Box::new(NullableColumn { data, present })
+    }
+}
+
+impl ColumnData for NullableColumn {
+    fn collect_decoded(&self) -> BoxedVec {
+        self.data.collect_decoded()
+    }
+
+    fn filter_decode(&self, filter: &BitVec) -> BoxedVec {
+        self.data.filter_decode(filter)
+    }
+
+    fn index_decode(&self, indices: &[usize]) -> BoxedVec {
+        self.data.index_decode(indices)
+    }
+
+    fn basic_type(&self) -> BasicType { self.data.basic_type() }
+
+    fn to_codec(&self) -> Option<&ColumnCodec> { self.data.to_codec() }
+
+    fn len(&self) -> usize { self.data.len() }
+
+    fn present(&self) -> Option<&BitVec> { Some(&self.present) }
+
+    fn decompressing_copy(&self) -> Option<Box<ColumnData>> {
+        self.data.decompressing_copy().map(|data| NullableColumn::new_boxed(data, self.present.clone()))
+    }
+}
+
+impl HeapSizeOf for NullableColumn {
+    fn heap_size_of_children(&self) -> usize {
+        self.data.heap_size_of_children() + self.present.capacity() / 8
+    }
+}
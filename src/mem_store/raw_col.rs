@@ -1,10 +1,12 @@
+use bit_vec::BitVec;
 use heapsize::HeapSizeOf;
 use mem_store::column::ColumnData;
 use mem_store::column_builder::*;
 use std::ops::BitOr;
 use std::iter::repeat;
 use ingest::raw_val::RawVal;
-use mem_store::null_column::NullColumn;
+use ordered_float::OrderedFloat;
+use mem_store::nullable_column::NullableColumn;
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct RawCol {
@@ -51,28 +53,65 @@ impl RawCol {
     }
 
     pub fn finalize(self) -> Box<ColumnData> {
-        if self.types.contains_string {
+        let present = if self.types.contains_null {
+            Some(self.data.iter().map(|v| *v != RawVal::Null).collect::<BitVec>())
+        } else {
+            None
+        };
+        let data = if self.types.contains_string {
             let mut builder = StringColBuilder::new();
+            // Nulls are stored as some other value in the column to avoid extending its range
+            let placeholder = self.data.iter().filter_map(|v| match *v {
+                RawVal::Str(ref s) => Some(s.clone()),
+                RawVal::Int(i) => Some(i.to_string()),
+                RawVal::Float(f) => Some(f.to_string()),
+                RawVal::Null => None,
+            }).next().unwrap_or_default();
             for v in self.data {
                 match v {
                     RawVal::Str(s) => builder.push(&s),
                     RawVal::Int(i) => builder.push(&i.to_string()),
-                    RawVal::Null => builder.push(""),
+                    RawVal::Float(f) => builder.push(&f.to_string()),
+                    RawVal::Null => builder.push(&placeholder),
+                }
+            }
+            builder.finalize()
+        } else if self.types.contains_float {
+            let mut builder = FloatColBuilder::new();
+            let placeholder = self.data.iter().filter_map(|v| match *v {
+                RawVal::Float(f) => Some(f.into_inner()),
+                RawVal::Int(i) => Some(i as f64),
+                _ => None,
+            }).next().unwrap_or(0.0);
+            for v in self.data {
+                match v {
+                    RawVal::Str(_) => panic!("Unexpected string in float column!"),
+                    RawVal::Float(f) => builder.push(&f.into_inner()),
+                    RawVal::Int(i) => builder.push(&(i as f64)),
+                    RawVal::Null => builder.push(&placeholder),
                 }
             }
             builder.finalize()
-        } else if self.types.contains_int {
+        } else {
+            // Columns without any values are stored as integers that are all null
             let mut builder = IntColBuilder::new();
+            let placeholder = self.data.iter().filter_map(|v| match *v {
+                RawVal::Int(i) => Some(i),
+                _ => None,
+            }).next().unwrap_or(0);
             for v in self.data {
                 match v {
                     RawVal::Str(_) => panic!("Unexpected string in int column!"),
+                    RawVal::Float(_) => panic!("Unexpected float in int column!"),
                     RawVal::Int(i) => builder.push(&i),
-                    RawVal::Null => builder.push(&0),
+                    RawVal::Null => builder.push(&placeholder),
                 }
             }
             builder.finalize()
-        } else {
-            Box::new(NullColumn::new(self.data.len()))
+        };
+        match present {
+            Some(present) => NullableColumn::new_boxed(data, present),
+            None => data,
         }
     }
 }
@@ -87,35 +126,41 @@ impl HeapSizeOf for RawCol {
 struct ColType {
     contains_string: bool,
     contains_int: bool,
+    contains_float: bool,
     contains_null: bool,
 }
 
 impl ColType {
-    fn new(string: bool, int: bool, null: bool) -> ColType {
-        ColType { contains_string: string, contains_int: int, contains_null: null }
+    fn new(string: bool, int: bool, float: bool, null: bool) -> ColType {
+        ColType { contains_string: string, contains_int: int, contains_float: float, contains_null: null }
     }
 
     fn string() -> ColType {
-        ColType::new(true, false, false)
+        ColType::new(true, false, false, false)
     }
 
     fn int() -> ColType {
-        ColType::new(false, true, false)
+        ColType::new(false, true, false, false)
+    }
+
+    fn float() -> ColType {
+        ColType::new(false, false, true, false)
     }
 
     fn null() -> ColType {
-        ColType::new(false, false, true)
+        ColType::new(false, false, false, true)
     }
 
     fn nothing() -> ColType {
-        ColType::new(false, false, false)
+        ColType::new(false, false, false, false)
     }
 
     fn determine(v: &RawVal) -> ColType {
         match *v {
             RawVal::Null => ColType::null(),
             RawVal::Str(_) => ColType::string(),
-            RawVal::Int(_) => ColType::int()
+            RawVal::Int(_) => ColType::int(),
+            RawVal::Float(_) => ColType::float(),
         }
     }
 }
@@ -126,6 +171,7 @@ impl BitOr for ColType {
         ColType {
             contains_string: self.contains_string | rhs.contains_string,
             contains_int: self.contains_int | rhs.contains_int,
+            contains_float: self.contains_float | rhs.contains_float,
             contains_null: self.contains_null | rhs.contains_null,
         }
     }
@@ -139,3 +185,7 @@ impl<'a> From<&'a str> for RawVal {
 impl From<i64> for RawVal {
     fn from(val: i64) -> RawVal { RawVal::Int(val) }
 }
+
+impl From<f64> for RawVal {
+    fn from(val: f64) -> RawVal { RawVal::Float(OrderedFloat(val)) }
+}
@@ -0,0 +1,140 @@
+use std::mem;
+
+use bit_vec::BitVec;
+use engine::typed_vec::{BoxedVec, TypedVec};
+use engine::types::*;
+use heapsize::HeapSizeOf;
+use ingest::raw_val::RawVal;
+use mem_store::column::{ColumnData, ColumnCodec};
+
+
+// Stores each run of identical values once, together with the index of the first row after the run.
+// Filters and sums over runs can be evaluated without expanding them to individual rows.
+pub struct RunLengthColumn {
+    values: Vec<i64>,
+    ends: Vec<u32>,
+    min: i64,
+    max: i64,
+}
+
+impl RunLengthColumn {
+    pub fn new_boxed(values: &[i64], min: i64, max: i64) -> Box<ColumnData> {
+        Box::new(RunLengthColumn::new(values, min, max))
+    }
+
+    fn new(values: &[i64], min: i64, max: i64) -> RunLengthColumn {
+        let mut run_values = Vec::new();
+        let mut ends = Vec::new();
+        for (i, &value) in values.iter().enumerate() {
+            if run_values.last() == Some(&value) {
+                *ends.last_mut().unwrap() = i as u32 + 1;
+            } else {
+                run_values.push(value);
+                ends.push(i as u32 + 1);
+            }
+        }
+        run_values.shrink_to_fit();
+        ends.shrink_to_fit();
+        RunLengthColumn { values: run_values, ends, min, max }
+    }
+
+    // Number of bytes required to store `values`
+    pub fn encoded_size(values: &[i64]) -> usize {
+        let runs = values.windows(2).filter(|window| window[0] != window[1]).count() + values.len().min(1);
+        runs * (mem::size_of::<i64>() + mem::size_of::<u32>())
+    }
+
+    fn decode(&self) -> Vec<i64> {
+        let mut result = Vec::with_capacity(self.len());
+        let mut start = 0;
+        for (&value, &end) in self.values.iter().zip(self.ends.iter()) {
+            for _ in start..end {
+                result.push(value);
+            }
+            start = end;
+        }
+        result
+    }
+}
+
+impl ColumnData for RunLengthColumn {
+    fn collect_decoded(&self) -> BoxedVec {
+        TypedVec::owned(self.decode())
+    }
+
+    fn filter_decode(&self, filter: &BitVec) -> BoxedVec {
+        let mut result = Vec::new();
+        let mut start = 0;
+        for (&value, &end) in self.values.iter().zip(self.ends.iter()) {
+            for i in start..end {
+                if filter[i as usize] {
+                    result.push(value);
+                }
+            }
+            start = end;
+        }
+        TypedVec::owned(result)
+    }
+
+    fn index_decode(&self, filter: &[usize]) -> BoxedVec {
+        let mut result = Vec::with_capacity(filter.len());
+        for &i in filter {
+            // Row `i` belongs to the first run that ends after it
+            let run = match self.ends.binary_search(&(i as u32)) {
+                Ok(run) => run + 1,
+                Err(run) => run,
+            };
+            result.push(self.values[run]);
+        }
+        TypedVec::owned(result)
+    }
+
+    fn basic_type(&self) -> BasicType { BasicType::Integer }
+
+    fn to_codec(&self) -> Option<&ColumnCodec> { Some(self as &ColumnCodec) }
+
+    fn len(&self) -> usize { self.ends.last().map_or(0, |&end| end as usize) }
+}
+
+// Encoded values are the expanded integers, which allows range based optimizations like packed grouping keys
+impl ColumnCodec for RunLengthColumn {
+    fn get_encoded(&self) -> BoxedVec { self.collect_decoded() }
+    fn filter_encoded(&self, filter: &BitVec) -> BoxedVec { self.filter_decode(filter) }
+    fn index_encoded(&self, filter: &[usize]) -> BoxedVec { self.index_decode(filter) }
+
+    fn unwrap_decode<'a>(&'a self, data: &TypedVec<'a>) -> BoxedVec<'a> {
+        TypedVec::owned(data.cast_ref_i64().to_vec())
+    }
+
+    fn encode_int(&self, val: i64) -> RawVal { RawVal::Int(val) }
+
+    fn is_summation_preserving(&self) -> bool { true }
+    fn is_order_preserving(&self) -> bool { true }
+    fn is_positive_integer(&self) -> bool { self.min >= 0 }
+    fn encoding_type(&self) -> EncodingType { EncodingType::I64 }
+    fn encoding_range(&self) -> Option<(i64, i64)> { Some((self.min, self.max)) }
+
+    fn runs(&self) -> Option<(&[i64], &[u32])> { Some((&self.values, &self.ends)) }
+}
+
+impl HeapSizeOf for RunLengthColumn {
+    fn heap_size_of_children(&self) -> usize {
+        self.values.heap_size_of_children() + self.ends.heap_size_of_children()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs() {
+        let values = vec![3, 3, 3, 1, 1, 3, 7, 7, 7, 7];
+        let column = RunLengthColumn::new(&values, 1, 7);
+        assert_eq!(column.decode(), values);
+        assert_eq!(column.runs(), Some((&[3, 1, 3, 7][..], &[3, 5, 6, 10][..])));
+        assert_eq!(column.index_decode(&[9, 0, 3, 5, 2]).cast_ref_i64(), &[7, 3, 1, 3, 3]);
+        assert_eq!(RunLengthColumn::encoded_size(&values), 48);
+    }
+}
@@ -2,68 +2,205 @@ use bit_vec::BitVec;
 use ingest::raw_val::RawVal;
 use mem_store::column::{ColumnData, ColumnCodec};
 use mem_store::column_builder::UniqueValues;
+use mem_store::lz4;
 use mem_store::point_codec::PointCodec;
 use heapsize::HeapSizeOf;
-use std::collections::hash_set::HashSet;
+use mem_store::null_column::NullColumn;
+use mem_store::nullable_column::NullableColumn;
+use std::cmp;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::str;
-use std::{u8, u16};
+use std::sync::{Arc, Mutex};
 use engine::types::*;
-use engine::typed_vec::{BoxedVec, TypedVec};
+use engine::typed_vec::{BoxedVec, TypedVec, IntVecType};
 
 
-pub const MAX_UNIQUE_STRINGS: usize = 10000;
+pub const MAX_UNIQUE_STRINGS: usize = 1 << 22;
 
 pub fn build_string_column(values: &[Option<Rc<String>>],
                            unique_values: UniqueValues<Option<Rc<String>>>)
                            -> Box<ColumnData> {
     if let Some(u) = unique_values.get_values() {
-        Box::new(DictEncodedStrings::from_strings(values, u))
+        // Dictionary encoding only pays off if values repeat
+        if u.len() * 2 <= values.len() {
+            let mut mapping: Vec<Option<String>> =
+                u.into_iter().map(|o| o.map(|s| s.as_str().to_owned())).collect();
+            mapping.sort();
+            let codes = {
+                let reverse_mapping: HashMap<Option<&str>, u32> =
+                    mapping.iter().map(|o| o.as_ref().map(|s| s.as_str())).zip(0..).collect();
+                values.iter().map(|o| reverse_mapping[&o.as_ref().map(|s| s.as_str())]).collect::<Vec<_>>()
+            };
+            return dictionary_column(Arc::new(mapping), codes.into_iter(), false);
+        }
+    }
+    Box::new(StringPacker::from_strings(values))
+}
+
+// Number of bytes required per code to index into a dictionary with `len` entries
+fn code_size(len: usize) -> usize {
+    if len <= 1 << 8 {
+        1
+    } else if len <= 1 << 16 {
+        2
     } else {
-        Box::new(StringPacker::from_strings(values))
+        4
     }
 }
 
-struct StringPacker {
-    data: Vec<u8>,
+// Builds string columns for multiple batches that share a single dictionary. Codes are assigned once all batches have
+// been pushed so that the dictionary is sorted, which makes codes comparable across batches.
+pub struct SharedDictionaryBuilder {
+    codes: HashMap<String, u32>,
+    values: Vec<String>,
+    // Code of each value in each batch, `None` for nulls
+    batches: Vec<Vec<Option<u32>>>,
 }
 
-// TODO(clemens): encode using variable size length + special value to represent null
-impl StringPacker {
-    pub fn new() -> StringPacker {
-        StringPacker { data: Vec::new() }
+impl SharedDictionaryBuilder {
+    pub fn new() -> SharedDictionaryBuilder {
+        SharedDictionaryBuilder {
+            codes: HashMap::new(),
+            values: Vec::new(),
+            batches: Vec::new(),
+        }
     }
 
-    pub fn from_strings(strings: &[Option<Rc<String>>]) -> StringPacker {
-        let mut sp = StringPacker::new();
-        for string in strings {
-            match *string {
-                Some(ref string) => sp.push(string),
-                None => sp.push(""),
+    // Empty strings are stored as null
+    pub fn push_batch(&mut self, data: Vec<String>) {
+        let mut batch = Vec::with_capacity(data.len());
+        for string in data {
+            if string.is_empty() {
+                batch.push(None);
+                continue;
             }
+            let next_code = self.values.len() as u32;
+            let code = *self.codes.entry(string.clone()).or_insert(next_code);
+            if code == next_code {
+                self.values.push(string);
+            }
+            batch.push(Some(code));
+        }
+        self.batches.push(batch);
+    }
+
+    // Returns the column for each pushed batch
+    pub fn finalize(self) -> Vec<Box<ColumnData>> {
+        let SharedDictionaryBuilder { values, batches, .. } = self;
+        let mut order = (0..values.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| values[a].cmp(&values[b]));
+        let mut sorted_codes = vec![0; values.len()];
+        for (sorted_code, &code) in order.iter().enumerate() {
+            sorted_codes[code] = sorted_code as u32;
         }
-        sp.shrink_to_fit();
-        sp
+        let mut values = values.into_iter().map(Some).collect::<Vec<_>>();
+        let mapping = Arc::new(order.iter().map(|&code| values[code].take()).collect::<Vec<_>>());
+
+        batches.into_iter().map(|batch| {
+            if mapping.is_empty() {
+                return Box::new(NullColumn::new(batch.len())) as Box<ColumnData>;
+            }
+            // Nulls are stored as the first dictionary entry
+            let codes = batch.iter().map(|code| code.map_or(0, |c| sorted_codes[c as usize]));
+            let data = dictionary_column(mapping.clone(), codes, true);
+            if batch.iter().all(|code| code.is_some()) {
+                data
+            } else {
+                NullableColumn::new_boxed(data, batch.iter().map(|code| code.is_some()).collect())
+            }
+        }).collect()
     }
+}
+
+// Number of strings that are compressed together
+const BLOCK_ROWS: usize = 512;
+
+// Stores strings back to back in blocks of `BLOCK_ROWS` strings, blocks that compress well are stored compressed.
+// Compressed blocks are decompressed when they are first accessed and kept until the packer is dropped. Queries decode
+// a copy created by `decompressing_copy`, which shares the blocks and is dropped once the query completes.
+struct StringPacker {
+    // End of each string relative to the start of its block
+    ends: Arc<Vec<u32>>,
+    blocks: Arc<Vec<Block>>,
+    decompressed: Vec<Mutex<Option<Box<[u8]>>>>,
+}
+
+enum Block {
+    Raw(Vec<u8>),
+    Compressed(Vec<u8>),
+}
 
-    pub fn push(&mut self, string: &str) {
-        for &byte in string.as_bytes().iter() {
-            self.data.push(byte);
+impl StringPacker {
+    pub fn from_strings(strings: &[Option<Rc<String>>]) -> StringPacker {
+        let mut ends = Vec::with_capacity(strings.len());
+        let mut blocks = Vec::with_capacity((strings.len() + BLOCK_ROWS - 1) / BLOCK_ROWS);
+        for block_strings in strings.chunks(BLOCK_ROWS) {
+            let mut data = Vec::new();
+            for string in block_strings {
+                if let Some(ref string) = *string {
+                    data.extend_from_slice(string.as_bytes());
+                }
+                ends.push(data.len() as u32);
+            }
+            let compressed = lz4::compress(&data);
+            // Decompression is only worth it if it saves a significant amount of memory
+            if compressed.len() < data.len() * 3 / 4 {
+                blocks.push(Block::Compressed(compressed));
+                continue;
+            }
+            data.shrink_to_fit();
+            blocks.push(Block::Raw(data));
         }
-        self.data.push(0);
+        StringPacker::with_blocks(Arc::new(ends), Arc::new(blocks))
     }
 
-    pub fn shrink_to_fit(&mut self) {
-        self.data.shrink_to_fit();
+    fn with_blocks(ends: Arc<Vec<u32>>, blocks: Arc<Vec<Block>>) -> StringPacker {
+        let decompressed = blocks.iter().map(|_| Mutex::new(None)).collect();
+        StringPacker { ends, blocks, decompressed }
     }
 
     pub fn iter(&self) -> StringPackerIterator {
         StringPackerIterator {
-            data: &self.data,
-            curr_index: 0,
+            packer: self,
+            block: &[],
+            index: 0,
+        }
+    }
+
+    fn block(&self, index: usize) -> &[u8] {
+        match self.blocks[index] {
+            Block::Raw(ref data) => data,
+            Block::Compressed(ref compressed) => {
+                let mut decompressed = self.decompressed[index].lock().unwrap();
+                if decompressed.is_none() {
+                    let mut data = Vec::with_capacity(self.block_len(index));
+                    lz4::decompress(compressed, &mut data);
+                    *decompressed = Some(data.into_boxed_slice());
+                }
+                let data: *const [u8] = &**decompressed.as_ref().unwrap();
+                // Decompressed blocks are neither modified nor dropped before the packer
+                unsafe { &*data }
+            }
         }
     }
+
+    fn is_compressed(&self) -> bool {
+        self.blocks.iter().any(|block| match *block {
+            Block::Compressed(_) => true,
+            Block::Raw(_) => false,
+        })
+    }
+
+    fn block_len(&self, index: usize) -> usize {
+        let last = cmp::min((index + 1) * BLOCK_ROWS, self.ends.len()) - 1;
+        self.ends[last] as usize
+    }
+
+    fn get_from_block<'a>(&self, block: &'a [u8], index: usize) -> &'a str {
+        let start = if index % BLOCK_ROWS == 0 { 0 } else { self.ends[index - 1] as usize };
+        unsafe { str::from_utf8_unchecked(&block[start..self.ends[index] as usize]) }
+    }
 }
 
 impl ColumnData for StringPacker {
@@ -72,86 +209,97 @@ impl ColumnData for StringPacker {
     }
 
     fn filter_decode<'a>(&'a self, filter: &BitVec) -> BoxedVec {
-        let mut result = Vec::new();
-        for (s, select) in self.iter().zip(filter.iter()) {
-            if select {
-                result.push(s);
-            }
-        }
+        let result = self.iter().zip(filter.iter())
+            .filter(|&(_, selected)| selected)
+            .map(|(s, _)| s)
+            .collect::<Vec<_>>();
         TypedVec::owned(result)
     }
 
     fn index_decode<'a>(&'a self, filter: &[usize]) -> BoxedVec {
-        let decoded = self.iter().collect::<Vec<_>>();
-        let mut result = Vec::with_capacity(filter.len());
-        for &i in filter {
-            result.push(decoded[i]);
-        }
+        let result = filter.iter()
+            .map(|&i| self.get_from_block(self.block(i / BLOCK_ROWS), i))
+            .collect::<Vec<_>>();
         TypedVec::owned(result)
     }
 
     fn basic_type(&self) -> BasicType { BasicType::String }
 
-    fn len(&self) -> usize { self.iter().count() } // FIXME(clemens): O(n)
+    fn len(&self) -> usize { self.ends.len() }
+
+    fn decompressing_copy(&self) -> Option<Box<ColumnData>> {
+        if self.is_compressed() {
+            Some(Box::new(StringPacker::with_blocks(self.ends.clone(), self.blocks.clone())))
+        } else {
+            None
+        }
+    }
 }
 
 impl HeapSizeOf for StringPacker {
     fn heap_size_of_children(&self) -> usize {
-        self.data.heap_size_of_children()
+        let blocks = self.blocks.iter()
+            .map(|block| match *block {
+                Block::Raw(ref data) | Block::Compressed(ref data) => data.heap_size_of_children(),
+            })
+            .sum::<usize>();
+        let decompressed = self.decompressed.iter()
+            .map(|block| block.lock().unwrap().as_ref().map_or(0, |data| data.len()))
+            .sum::<usize>();
+        self.ends.as_ref().heap_size_of_children() + blocks + decompressed
     }
 }
 
 pub struct StringPackerIterator<'a> {
-    data: &'a Vec<u8>,
-    curr_index: usize,
+    packer: &'a StringPacker,
+    block: &'a [u8],
+    index: usize,
 }
 
 impl<'a> Iterator for StringPackerIterator<'a> {
     type Item = &'a str;
 
     fn next(&mut self) -> Option<&'a str> {
-        if self.curr_index >= self.data.len() {
+        if self.index >= self.packer.ends.len() {
             return None;
         }
-
-        let mut index = self.curr_index;
-        while self.data[index] != 0 {
-            index += 1;
+        if self.index % BLOCK_ROWS == 0 {
+            self.block = self.packer.block(self.index / BLOCK_ROWS);
         }
-        let result = unsafe { str::from_utf8_unchecked(&self.data[self.curr_index..index]) };
-        self.curr_index = index + 1;
+        let result = self.packer.get_from_block(self.block, self.index);
+        self.index += 1;
         Some(result)
     }
 }
 
-struct DictEncodedStrings {
-    mapping: Vec<Option<String>>,
-    encoded_values: Vec<u16>,
+struct DictEncodedStrings<T> {
+    mapping: Arc<Vec<Option<String>>>,
+    encoded_values: Vec<T>,
+    // Whether `mapping` is shared with other batches
+    shared: bool,
 }
 
-impl DictEncodedStrings {
-    pub fn from_strings(strings: &[Option<Rc<String>>],
-                        unique_values: HashSet<Option<Rc<String>>>)
-                        -> DictEncodedStrings {
-        assert!(unique_values.len() <= u16::MAX as usize);
-
-        let mut mapping: Vec<Option<String>> =
-            unique_values.into_iter().map(|o| o.map(|s| s.as_str().to_owned())).collect();
-        mapping.sort();
-        let encoded_values: Vec<u16> = {
-            let reverse_mapping: HashMap<Option<&String>, u16> =
-                mapping.iter().map(Option::as_ref).zip(0..).collect();
-            strings.iter().map(|o| reverse_mapping[&o.as_ref().map(|x| &**x)]).collect()
-        };
+fn dictionary_column<I: Iterator<Item=u32>>(mapping: Arc<Vec<Option<String>>>, codes: I, shared: bool) -> Box<ColumnData> {
+    match code_size(mapping.len()) {
+        1 => Box::new(DictEncodedStrings::<u8>::new(mapping, codes, shared)),
+        2 => Box::new(DictEncodedStrings::<u16>::new(mapping, codes, shared)),
+        _ => Box::new(DictEncodedStrings::<u32>::new(mapping, codes, shared)),
+    }
+}
 
+impl<T: IntVecType<T>> DictEncodedStrings<T> {
+    fn new<I: Iterator<Item=u32>>(mapping: Arc<Vec<Option<String>>>, codes: I, shared: bool) -> DictEncodedStrings<T> {
+        let mut encoded_values = codes.map(|code| T::from(code).unwrap()).collect::<Vec<T>>();
+        encoded_values.shrink_to_fit();
         DictEncodedStrings {
             mapping,
             encoded_values,
+            shared,
         }
     }
 }
 
-impl ColumnData for DictEncodedStrings {
+impl<T: IntVecType<T>> ColumnData for DictEncodedStrings<T> {
     fn collect_decoded(&self) -> BoxedVec {
         self.decode(&self.encoded_values)
     }
@@ -160,7 +308,7 @@ impl ColumnData for DictEncodedStrings {
         let mut result = Vec::<&str>::with_capacity(self.encoded_values.len());
         for (encoded_value, selected) in self.encoded_values.iter().zip(filter) {
             if selected {
-                result.push(self.mapping[*encoded_value as usize].as_ref().unwrap());
+                result.push(self.mapping[encoded_value.cast_usize()].as_ref().unwrap());
             }
         }
         TypedVec::owned(result)
@@ -175,38 +323,38 @@ impl ColumnData for DictEncodedStrings {
     fn len(&self) -> usize { self.encoded_values.len() }
 }
 
-impl PointCodec<u16> for DictEncodedStrings {
-    fn decode(&self, data: &[u16]) -> BoxedVec {
+impl<T: IntVecType<T>> PointCodec<T> for DictEncodedStrings<T> {
+    fn decode(&self, data: &[T]) -> BoxedVec {
         let mut result = Vec::<&str>::with_capacity(self.encoded_values.len());
         for encoded_value in data {
-            result.push(self.mapping[*encoded_value as usize].as_ref().unwrap());
+            result.push(self.mapping[encoded_value.cast_usize()].as_ref().unwrap());
         }
         TypedVec::owned(result)
     }
 
-    fn index_decode(&self, data: &[u16], filter: &[usize]) -> BoxedVec {
+    fn index_decode(&self, data: &[T], filter: &[usize]) -> BoxedVec {
         let mut result = Vec::<&str>::with_capacity(filter.len());
         for &i in filter {
             let encoded_value = data[i];
-            result.push(self.mapping[encoded_value as usize].as_ref().unwrap());
+            result.push(self.mapping[encoded_value.cast_usize()].as_ref().unwrap());
         }
         TypedVec::owned(result)
     }
 
-    fn to_raw(&self, elem: u16) -> RawVal {
-        RawVal::Str(self.mapping[elem as usize].as_ref().unwrap().to_string())
+    fn to_raw(&self, elem: T) -> RawVal {
+        RawVal::Str(self.mapping[elem.cast_usize()].as_ref().unwrap().to_string())
     }
 
     fn max_cardinality(&self) -> usize { self.mapping.len() }
 }
 
-impl ColumnCodec for DictEncodedStrings {
+impl<T: IntVecType<T>> ColumnCodec for DictEncodedStrings<T> {
     fn get_encoded(&self) -> BoxedVec {
         TypedVec::borrowed(&self.encoded_values)
     }
 
     fn unwrap_decode<'a>(&'a self, data: &TypedVec<'a>) -> BoxedVec<'a> {
-        self.decode(data.cast_ref_u16())
+        self.decode(T::unwrap(data))
     }
 
     fn filter_encoded(&self, filter: &BitVec) -> BoxedVec {
@@ -227,15 +375,17 @@ impl ColumnCodec for DictEncodedStrings {
         TypedVec::owned(result)
     }
 
-    fn encoding_type(&self) -> EncodingType { EncodingType::U16 }
+    fn encoding_type(&self) -> EncodingType { T::t() }
+
+    fn dictionary(&self) -> Option<&[Option<String>]> { Some(&self.mapping) }
+
+    fn has_shared_dictionary(&self) -> bool { self.shared }
 
     fn encode_str(&self, s: &str) -> RawVal {
-        for (i, val) in self.mapping.iter().enumerate() {
-            if val.as_ref().unwrap() == s {
-                return RawVal::Int(i as i64);
-            }
+        match self.mapping.binary_search_by(|val| val.as_ref().map(|x| x.as_str()).cmp(&Some(s))) {
+            Ok(i) => RawVal::Int(i as i64),
+            Err(_) => RawVal::Int(-1),
         }
-        RawVal::Int(-1)
     }
 
     fn is_summation_preserving(&self) -> bool { false }
@@ -244,8 +394,51 @@ impl ColumnCodec for DictEncodedStrings {
     fn encoding_range(&self) -> Option<(i64, i64)> { Some((0, self.mapping.len() as i64)) }
 }
 
-impl HeapSizeOf for DictEncodedStrings {
+impl<T: HeapSizeOf> HeapSizeOf for DictEncodedStrings<T> {
     fn heap_size_of_children(&self) -> usize {
-        self.mapping.heap_size_of_children() + self.encoded_values.heap_size_of_children()
+        // Shared dictionaries are accounted for once per table
+        let mapping_size = if self.shared { 0 } else { self.mapping.heap_size_of_children() };
+        mapping_size + self.encoded_values.heap_size_of_children()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dictionary_code_width() {
+        for &(len, encoding_type) in &[(200, EncodingType::U8), (300, EncodingType::U16), (70000, EncodingType::U32)] {
+            let mapping = Arc::new((0..len).map(|i| Some(format!("{:05}", i))).collect::<Vec<_>>());
+            let column = dictionary_column(mapping, 0..len as u32, false);
+            let codec = column.to_codec().unwrap();
+            assert_eq!(codec.encoding_type(), encoding_type);
+            assert_eq!(codec.encode_str("00123"), RawVal::Int(123));
+            assert_eq!(column.index_decode(&[len - 1]).get_raw(0), RawVal::Str(format!("{:05}", len - 1)));
+        }
+    }
+
+    #[test]
+    fn test_string_packer() {
+        let strings = (0..1200)
+            .map(|i| match i % 3 {
+                0 => Some(Rc::new(format!("{{\"id\":{},\"name\":\"Victor\"}}", i))),
+                1 => Some(Rc::new(format!("nul\0{}", i))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let packer = StringPacker::from_strings(&strings);
+        assert_eq!(packer.len(), 1200);
+        assert!(packer.blocks.iter().all(|block| match *block { Block::Compressed(_) => true, _ => false }));
+
+        let expected = strings.iter().map(|s| s.as_ref().map_or("", |s| s.as_str())).collect::<Vec<_>>();
+        assert_eq!(packer.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(packer.index_decode(&[1199, 4, 600]).cast_ref_str(), &["", "nul\04", "{\"id\":600,\"name\":\"Victor\"}"]);
+        let filter = (0..1200).map(|i| i == 1000).collect::<BitVec>();
+        assert_eq!(packer.filter_decode(&filter).cast_ref_str(), &["nul\01000"]);
+
+        let copy = packer.decompressing_copy().unwrap();
+        assert_eq!(copy.index_decode(&[600]).cast_ref_str(), &["{\"id\":600,\"name\":\"Victor\"}"]);
     }
 }
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::mem;
 use std::ops::DerefMut;
 use std::str;
 use std::sync::{Mutex, RwLock};
@@ -113,7 +114,7 @@ impl Table {
             name: self.name().to_string(),
             rows: batches.iter().map(|b| b.cols().get(0).map_or(0, |c| c.len())).sum(),
             batches: batches.len(),
-            batches_bytes: batches.heap_size_of_children(),
+            batches_bytes: Table::batches_size(&batches),
             buffer_length: buffer.len(),
             buffer_bytes: buffer.heap_size_of_children(),
             size_per_column,
@@ -128,8 +129,39 @@ impl Table {
                 *sizes.entry(col.name()).or_insert(0) += heapsize;
             }
         }
+        for (name, size) in Table::shared_dictionary_sizes(batches) {
+            *sizes.entry(name).or_insert(0) += size;
+        }
         sizes.iter().map(|(name, size)| (name.to_string(), *size)).collect()
     }
+
+    fn batches_size(batches: &[Batch]) -> usize {
+        batches.heap_size_of_children() + Table::shared_dictionary_sizes(batches).values().sum::<usize>()
+    }
+
+    // Shared dictionaries are referenced by all batches of a column but only stored once, so they are not included in
+    // the size of the individual columns
+    fn shared_dictionary_sizes(batches: &[Batch]) -> HashMap<&str, usize> {
+        let mut dictionaries: HashMap<*const Option<String>, (&str, usize)> = HashMap::default();
+        for batch in batches {
+            for col in batch.cols() {
+                let dictionary = col.data().to_codec()
+                    .and_then(|codec| if codec.has_shared_dictionary() { codec.dictionary() } else { None });
+                if let Some(dictionary) = dictionary {
+                    dictionaries.entry(dictionary.as_ptr()).or_insert_with(|| {
+                        let size = mem::size_of_val(dictionary)
+                            + dictionary.iter().map(|entry| entry.heap_size_of_children()).sum::<usize>();
+                        (col.name(), size)
+                    });
+                }
+            }
+        }
+        let mut sizes: HashMap<&str, usize> = HashMap::default();
+        for &(name, size) in dictionaries.values() {
+            *sizes.entry(name).or_insert(0) += size;
+        }
+        sizes
+    }
 }
 
 fn batch_size_override(batch_size: usize, tablename: &str) -> usize {
@@ -140,7 +172,7 @@ impl HeapSizeOf for Table {
     fn heap_size_of_children(&self) -> usize {
         let batches_size = {
             let batches = self.batches.read().unwrap();
-            batches.heap_size_of_children()
+            Table::batches_size(&batches)
         };
         let buffer_size = {
             let buffer = self.buffer.lock().unwrap();
@@ -60,7 +60,10 @@ impl Ruba {
         // TODO(clemens): A table may not exist on all nodes, so querying empty table is valid and should return empty result.
         let data = self.inner_ruba.snapshot(&query.table)
             .expect(&format!("Table {} does not exist!", &query.table));
-        let task = QueryTask::new(query, data, SharedSender::new(sender));
+        let task = match QueryTask::new(query, data, SharedSender::new(sender)) {
+            Ok(task) => task,
+            Err(err) => return Box::new(future::ok((Err(err), TraceBuilder::new("empty".to_owned()).finalize()))),
+        };
         let trace_receiver = self.schedule(task);
         Box::new(receiver.join(trace_receiver))
     }
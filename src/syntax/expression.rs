@@ -1,28 +1,54 @@
 use std::collections::HashSet;
 
+use engine::aggregator::Aggregator;
 use ingest::raw_val::RawVal;
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     ColName(String),
     Func(FuncType, Box<Expr>, Box<Expr>),
     Const(RawVal),
+    In(Box<Expr>, Vec<RawVal>),
+    // Only valid in clauses that are evaluated after aggregation (HAVING)
+    Aggregate(Aggregator, Box<Expr>),
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum FuncType {
     Equals,
+    NotEquals,
     LT,
+    LTE,
     GT,
+    GTE,
     And,
     Or,
+    Not,
+    IsNull,
+    IsNotNull,
     Add,
     Subtract,
     Multiply,
     Divide,
     RegexMatch,
+    Like,
+    ILike,
+    Lower,
+    Upper,
     Negate,
+    TimeBucket,
+    DateTrunc,
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Weekday,
+    // Arguments of aggregates with two arguments, e.g. covar(a, b)
+    Pair,
+    // Aggregate argument and the condition rows must satisfy to contribute, e.g. sum(a) FILTER (WHERE b > 0)
+    AggregateFilter,
 }
 
 use self::Expr::*;
@@ -37,10 +63,30 @@ impl Expr {
                 expr1.add_colnames(result);
                 expr2.add_colnames(result);
             }
+            Aggregate(_, ref expr) | In(ref expr, _) => expr.add_colnames(result),
             _ => (),
         }
     }
 
+    // Collects the columns and divisors that make the expression null when they are null or zero. Constant divisors
+    // are only collected if they are zero. IS NULL and IS NOT NULL are never null, so their operands are skipped.
+    pub fn add_null_sources(&self, colnames: &mut HashSet<String>, divisors: &mut Vec<Expr>) {
+        match *self {
+            Func(FuncType::IsNull, _, _) | Func(FuncType::IsNotNull, _, _) => (),
+            Func(ftype, ref expr1, ref expr2) => {
+                expr1.add_null_sources(colnames, divisors);
+                expr2.add_null_sources(colnames, divisors);
+                match (ftype, &**expr2) {
+                    (FuncType::Divide, &Const(ref value)) if !value.is_zero() => (),
+                    (FuncType::Divide, divisor) => divisors.push(divisor.clone()),
+                    _ => (),
+                }
+            }
+            In(ref expr, _) => expr.add_null_sources(colnames, divisors),
+            _ => self.add_colnames(colnames),
+        }
+    }
+
     pub fn func(ftype: FuncType, expr1: Expr, expr2: Expr) -> Expr {
         Func(ftype, Box::new(expr1), Box::new(expr2))
     }
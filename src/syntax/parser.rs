@@ -8,11 +8,23 @@ use syntax::expression::*;
 use syntax::limit::LimitClause;
 use engine::query::*;
 use engine::aggregator::Aggregator;
+use engine::time_function::TimeUnit;
 use ingest::raw_val::RawVal;
+use ordered_float::OrderedFloat;
 use std::boxed::Box;
 use time;
 
 
+// Parses `<whitespace> op operand` and returns both
+macro_rules! infix {
+    ($i:expr, $op:ident!( $($args:tt)* ), $operand:ident) => (
+        do_parse!($i, opt!(multispace) >> op: $op!($($args)*) >> e: $operand >> ((op, e)))
+    );
+    ($i:expr, $op:ident, $operand:ident) => (
+        infix!($i, call!($op), $operand)
+    );
+}
+
 named!(pub parse_query<&[u8], Query>, alt_complete!(full_query | simple_query));
 
 named!(full_query<&[u8], Query>,
@@ -27,12 +39,17 @@ named!(full_query<&[u8], Query>,
         multispace >>
         filter: expr >>
         opt!(multispace) >>
+        group_by: opt!(group_by_clause) >>
+        opt!(multispace) >>
+        having: opt!(having_clause) >>
+        opt!(multispace) >>
         order_by: opt!(order_by_clause) >>
         opt!(multispace) >>
         limit: opt!(limit_clause) >>
         opt!(multispace) >>
         char!(';') >>
-        (construct_query(select, table, filter, order_by, limit))
+        query: expr_res!(construct_query(select, table, filter, group_by, having, order_by, limit)) >>
+        (query)
     )
 );
 
@@ -45,56 +62,143 @@ named!(simple_query<&[u8], Query>,
         opt!(multispace) >>
         table: from_clause >>
         opt!(multispace) >>
+        group_by: opt!(group_by_clause) >>
+        opt!(multispace) >>
+        having: opt!(having_clause) >>
+        opt!(multispace) >>
         order_by: opt!(order_by_clause) >>
         opt!(multispace) >>
         limit: opt!(limit_clause) >>
         opt!(multispace) >>
         opt!(char!(';')) >>
-        (construct_query(select, table, Expr::Const(RawVal::Int(1)), order_by, limit))
+        query: expr_res!(construct_query(select, table, Expr::Const(RawVal::Int(1)), group_by, having, order_by, limit)) >>
+        (query)
     )
 );
 
-fn construct_query(select_clauses: Vec<AggregateOrSelect>,
+fn construct_query(select_clauses: Vec<(AggregateOrSelect, Option<String>)>,
                        table: &str,
                        filter: Expr,
-                       order_by: Option<(String, bool)>,
+                       group_by: Option<Vec<Expr>>,
+                       having: Option<Expr>,
+                       order_by: Option<Vec<(Expr, bool)>>,
                        limit: Option<LimitClause>)
-                       -> Query {
-    let (select, aggregate) = partition(select_clauses);
-    let order_desc = order_by.as_ref().map(|x| x.1).unwrap_or(false);
-    Query {
+                       -> Result<Query, String> {
+    let explicit_grouping = group_by.is_some() || having.is_some();
+    // Without GROUP BY clause, all non-aggregate select expressions are used as grouping keys
+    let select = match group_by {
+        Some(group_by) => group_by,
+        None => select_clauses.iter()
+            .filter_map(|x| match x.0 {
+                AggregateOrSelect::Select(ref expr) => Some(expr.clone()),
+                _ => None,
+            })
+            .collect(),
+    };
+
+    let mut aggregate = Vec::new();
+    let mut projection = Vec::with_capacity(select_clauses.len());
+    let mut aliases = Vec::with_capacity(select_clauses.len());
+    let mut aliased_exprs = Vec::new();
+    for (clause, alias) in select_clauses {
+        if let Some(ref alias) = alias {
+            aliased_exprs.push((alias.clone(), match clause {
+                AggregateOrSelect::Select(ref expr) => expr.clone(),
+                AggregateOrSelect::Aggregate((aggregator, ref expr)) => Expr::Aggregate(aggregator, Box::new(expr.clone())),
+                AggregateOrSelect::Aggregates(_) => return Err(format!("Aggregate returning multiple columns cannot be aliased as {}", alias)),
+            }));
+        }
+        aliases.push(alias);
+        match clause {
+            AggregateOrSelect::Select(expr) => match select.iter().position(|e| *e == expr) {
+                Some(index) => projection.push(index),
+                None => return Err(format!("{:?} must appear in GROUP BY clause", expr)),
+            },
+            AggregateOrSelect::Aggregate(agg) => {
+                projection.push(select.len() + aggregate.len());
+                aggregate.push(agg);
+            }
+            AggregateOrSelect::Aggregates(aggs) => {
+                for (i, agg) in aggs.into_iter().enumerate() {
+                    if i > 0 {
+                        aliases.push(None);
+                    }
+                    projection.push(select.len() + aggregate.len());
+                    aggregate.push(agg);
+                }
+            }
+        }
+    }
+    let having = having.map(|having| replace_aliases(having, &aliased_exprs));
+    if let Some(ref having) = having {
+        // Aggregates that only occur in HAVING or ORDER BY are computed but not returned
+        add_having_aggregates(having, &select, &mut aggregate)?;
+    }
+    let order_by = order_by.unwrap_or_default();
+    for &(ref expr, _) in &order_by {
+        if let Expr::Aggregate(aggregator, ref e) = *expr {
+            add_aggregate(aggregator, e, &mut aggregate);
+        }
+    }
+    for &(aggregator, _) in &aggregate {
+        if let Aggregator::Percentile(q) = aggregator {
+            if !(q.0 >= 0.0 && q.0 <= 1.0) {
+                return Err(format!("Percentile {} is not between 0 and 1", q));
+            }
+        }
+    }
+    if explicit_grouping && aggregate.is_empty() {
+        aggregate.push((Aggregator::Count, Expr::Const(RawVal::Int(1))));
+    }
+
+    Ok(Query {
+        projection: if aggregate.is_empty() { None } else { Some(projection) },
         select,
         table: table.to_string(),
         filter,
         aggregate,
-        order_by: order_by.map(|x| x.0),
-        order_desc,
+        having,
+        aliases,
+        order_by,
         limit: limit.unwrap_or(LimitClause { limit: 100, offset: 0 }),
-        order_by_index: None,
+        order_by_index: Vec::with_capacity(0),
+    })
+}
+
+fn add_having_aggregates(expr: &Expr, select: &[Expr], aggregate: &mut Vec<(Aggregator, Expr)>) -> Result<(), String> {
+    if select.contains(expr) {
+        return Ok(());
+    }
+    match *expr {
+        Expr::Aggregate(aggregator, ref e) => add_aggregate(aggregator, e, aggregate),
+        Expr::Func(_, ref lhs, ref rhs) => {
+            add_having_aggregates(lhs, select, aggregate)?;
+            add_having_aggregates(rhs, select, aggregate)?;
+        }
+        Expr::In(ref e, _) => add_having_aggregates(e, select, aggregate)?,
+        Expr::ColName(ref name) => return Err(format!("{} must appear in GROUP BY clause", name)),
+        Expr::Const(_) => {}
     }
+    Ok(())
 }
 
-fn partition(select_or_aggregates: Vec<AggregateOrSelect>)
-                 -> (Vec<Expr>, Vec<(Aggregator, Expr)>) {
-    let (selects, aggregates): (Vec<AggregateOrSelect>, Vec<AggregateOrSelect>) =
-        select_or_aggregates.into_iter()
-            .partition(|x| match *x {
-                AggregateOrSelect::Select(_) => true,
-                _ => false,
-            });
-
-    (selects.into_iter()
-         .filter_map(|x| match x {
-             AggregateOrSelect::Select(expr) => Some(expr),
-             _ => None,
-         })
-         .collect(),
-     aggregates.into_iter()
-         .filter_map(|x| match x {
-             AggregateOrSelect::Aggregate(agg) => Some(agg),
-             _ => None,
-         })
-         .collect())
+fn replace_aliases(expr: Expr, aliased_exprs: &[(String, Expr)]) -> Expr {
+    match expr {
+        Expr::ColName(name) => match aliased_exprs.iter().find(|&&(ref alias, _)| *alias == name) {
+            Some(&(_, ref aliased)) => aliased.clone(),
+            None => Expr::ColName(name),
+        },
+        Expr::Func(func, lhs, rhs) =>
+            Expr::func(func, replace_aliases(*lhs, aliased_exprs), replace_aliases(*rhs, aliased_exprs)),
+        Expr::In(expr, values) => Expr::In(Box::new(replace_aliases(*expr, aliased_exprs)), values),
+        expr => expr,
+    }
+}
+
+fn add_aggregate(aggregator: Aggregator, expr: &Expr, aggregate: &mut Vec<(Aggregator, Expr)>) {
+    if !aggregate.iter().any(|&(a, ref x)| a == aggregator && x == expr) {
+        aggregate.push((aggregator, expr.clone()));
+    }
 }
 
 named!(from_clause<&[u8], &str>,
@@ -106,36 +210,178 @@ named!(from_clause<&[u8], &str>,
     )
 );
 
-named!(select_clauses<&[u8], Vec<AggregateOrSelect>>,
+named!(select_clauses<&[u8], Vec<(AggregateOrSelect, Option<String>)>>,
     alt!(
         do_parse!(
             opt!(multispace) >>
             tag!("*") >>
             opt!(multispace) >>
-            (vec![AggregateOrSelect::Select(Expr::ColName("*".to_string()))])
+            (vec![(AggregateOrSelect::Select(Expr::ColName("*".to_string())), None)])
         ) |
         separated_list!(
             tag!(","),
-            alt_complete!(aggregate_clause | select_clause)
+            aliased_clause
         )
     )
 );
 
+named!(aliased_clause<&[u8], (AggregateOrSelect, Option<String>)>,
+    do_parse!(
+        clause: alt_complete!(aggregate_clause | select_clause) >>
+        alias: opt!(alias) >>
+        (clause, alias)
+    )
+);
+
+named!(alias<&[u8], String>,
+    do_parse!(
+        multispace >>
+        tag_no_case!("as") >>
+        multispace >>
+        name: identifier >>
+        (name.to_string())
+    )
+);
+
 named!(aggregate_clause<&[u8], AggregateOrSelect>,
     do_parse!(
         opt!(multispace) >>
-        atype: aggregate_func >>
+        clause: alt_complete!(
+            map!(percentiles, AggregateOrSelect::Aggregates) |
+            map!(aggregate_call, AggregateOrSelect::Aggregate)
+        ) >>
+        (clause)
+    )
+);
+
+// Expands to one percentile aggregate for each quantile
+named!(percentiles<&[u8], Vec<(Aggregator, Expr)>>,
+    do_parse!(
+        tag_no_case!("percentiles") >>
         char!('(') >>
         e: expr >>
+        quantiles: many1!(complete!(quantile_argument)) >>
         opt!(multispace) >>
         char!(')') >>
-        (AggregateOrSelect::Aggregate((atype, e)))
+        condition: opt!(complete!(filter_clause)) >>
+        (quantiles.into_iter()
+            .map(|q| with_filter((Aggregator::Percentile(OrderedFloat(q)), e.clone()), condition.clone()))
+            .collect())
     )
 );
 
 named!(select_clause<&[u8], AggregateOrSelect>, map!(expr, AggregateOrSelect::Select));
 
-named!(aggregate_func<&[u8], Aggregator>, alt!(count | sum));
+named!(aggregate<&[u8], Expr>,
+    map!(aggregate_call, |(atype, e)| Expr::Aggregate(atype, Box::new(e)))
+);
+
+named!(aggregate_call<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        aggregate: alt_complete!(count_distinct | percentile | bivariate_aggregate | simple_aggregate) >>
+        condition: opt!(complete!(filter_clause)) >>
+        (with_filter(aggregate, condition))
+    )
+);
+
+named!(filter_clause<&[u8], Expr>,
+    do_parse!(
+        multispace >>
+        tag_no_case!("filter") >>
+        opt!(multispace) >>
+        char!('(') >>
+        opt!(multispace) >>
+        tag_no_case!("where") >>
+        multispace >>
+        condition: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        (condition)
+    )
+);
+
+fn with_filter((aggregator, expr): (Aggregator, Expr), condition: Option<Expr>) -> (Aggregator, Expr) {
+    match condition {
+        Some(condition) => (aggregator, Expr::func(FuncType::AggregateFilter, expr, condition)),
+        None => (aggregator, expr),
+    }
+}
+
+named!(count_distinct<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        tag_no_case!("count") >>
+        char!('(') >>
+        opt!(multispace) >>
+        tag_no_case!("distinct") >>
+        multispace >>
+        e: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        (Aggregator::CountDistinct, e)
+    )
+);
+
+named!(percentile<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        tag_no_case!("percentile") >>
+        char!('(') >>
+        e: expr >>
+        q: quantile_argument >>
+        opt!(multispace) >>
+        char!(')') >>
+        (Aggregator::Percentile(OrderedFloat(q)), e)
+    )
+);
+
+named!(bivariate_aggregate<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        atype: alt!(
+            map!(tag_no_case!("covar"), |_| Aggregator::Covar) |
+            map!(tag_no_case!("corr"), |_| Aggregator::Corr) |
+            map!(tag_no_case!("arg_min"), |_| Aggregator::ArgMin) |
+            map!(tag_no_case!("arg_max"), |_| Aggregator::ArgMax) |
+            map!(tag_no_case!("first"), |_| Aggregator::First) |
+            map!(tag_no_case!("last"), |_| Aggregator::Last)
+        ) >>
+        char!('(') >>
+        x: expr >>
+        opt!(multispace) >>
+        char!(',') >>
+        y: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        (atype, Expr::func(FuncType::Pair, x, y))
+    )
+);
+
+named!(quantile_argument<&[u8], f64>,
+    do_parse!(
+        opt!(multispace) >>
+        char!(',') >>
+        opt!(multispace) >>
+        q: alt!(unsigned_float | map!(number, |n| n as f64)) >>
+        (q)
+    )
+);
+
+named!(simple_aggregate<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        atype: aggregate_func >>
+        char!('(') >>
+        e: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        (atype, e)
+    )
+);
+
+named!(aggregate_func<&[u8], Aggregator>,
+    alt!(approx_count_distinct | count | sum | avg | min | max | median | variance | stddev)
+);
+
+named!(approx_count_distinct<&[u8], Aggregator>,
+    map!( tag_no_case!("approx_count_distinct"), |_| Aggregator::ApproxCountDistinct )
+);
 
 named!(count<&[u8], Aggregator>,
     map!( tag_no_case!("count"), |_| Aggregator::Count )
@@ -145,22 +391,182 @@ named!(sum<&[u8], Aggregator>,
     map!( tag_no_case!("sum"), |_| Aggregator::Sum )
 );
 
+named!(avg<&[u8], Aggregator>,
+    map!( tag_no_case!("avg"), |_| Aggregator::Avg )
+);
+
+named!(min<&[u8], Aggregator>,
+    map!( tag_no_case!("min"), |_| Aggregator::Min )
+);
+
+named!(max<&[u8], Aggregator>,
+    map!( tag_no_case!("max"), |_| Aggregator::Max )
+);
+
+named!(median<&[u8], Aggregator>,
+    map!( tag_no_case!("median"), |_| Aggregator::Percentile(OrderedFloat(0.5)) )
+);
+
+named!(variance<&[u8], Aggregator>,
+    map!( tag_no_case!("variance"), |_| Aggregator::Variance )
+);
+
+named!(stddev<&[u8], Aggregator>,
+    map!( tag_no_case!("stddev"), |_| Aggregator::Stddev )
+);
+
+// Operators in order of increasing precedence: OR, AND, NOT, comparisons, + and -, * and /, unary minus.
+// All binary operators are left-associative.
 named!(expr<&[u8], Expr>,
     do_parse!(
         opt!(multispace) >>
-        result: alt!(infix_expr | expr_no_left_recur) >>
+        result: disjunction >>
         (result)
     )
 );
 
-named!(expr_no_left_recur<&[u8], Expr>,
+named!(disjunction<&[u8], Expr>,
+    do_parse!(
+        first: conjunction >>
+        rest: many0!(complete!(infix!(or, conjunction))) >>
+        (fold_infix(first, rest))
+    )
+);
+
+named!(conjunction<&[u8], Expr>,
+    do_parse!(
+        first: negation_or_comparison >>
+        rest: many0!(complete!(infix!(and, negation_or_comparison))) >>
+        (fold_infix(first, rest))
+    )
+);
+
+named!(negation_or_comparison<&[u8], Expr>,
+    do_parse!(
+        opt!(multispace) >>
+        result: alt!(not | comparison) >>
+        (result)
+    )
+);
+
+named!(comparison<&[u8], Expr>,
+    do_parse!(
+        e: term >>
+        predicate: opt!(complete!(predicate)) >>
+        (match predicate {
+            Some((negated, predicate)) => {
+                let e = match predicate {
+                    Predicate::Comparison(op, rhs) => Expr::func(op, e, rhs),
+                    Predicate::Between(lower, upper) => Expr::func(
+                        FuncType::And,
+                        Expr::func(FuncType::GTE, e.clone(), lower),
+                        Expr::func(FuncType::LTE, e, upper)),
+                    Predicate::In(values) => Expr::In(Box::new(e), values),
+                    Predicate::IsNull(false) => Expr::func(FuncType::IsNull, e, Expr::Const(RawVal::Null)),
+                    Predicate::IsNull(true) => Expr::func(FuncType::IsNotNull, e, Expr::Const(RawVal::Null)),
+                };
+                if negated { Expr::func(FuncType::Not, e, Expr::Const(RawVal::Null)) } else { e }
+            }
+            None => e,
+        })
+    )
+);
+
+named!(term<&[u8], Expr>,
+    do_parse!(
+        first: factor >>
+        rest: many0!(complete!(infix!(alt!(add | subtract), factor))) >>
+        (fold_infix(first, rest))
+    )
+);
+
+named!(factor<&[u8], Expr>,
+    do_parse!(
+        first: unary >>
+        rest: many0!(complete!(infix!(alt!(multiply | divide), unary))) >>
+        (fold_infix(first, rest))
+    )
+);
+
+named!(unary<&[u8], Expr>,
     do_parse!(
         opt!(multispace) >>
-        result: alt!(parentheses | template | function | negation | colname | constant) >>
+        result: alt!(negation | primary) >>
         (result)
     )
 );
 
+named!(primary<&[u8], Expr>,
+    alt!(parentheses | template | now | interval | function | unary_function | aggregate | colname | constant)
+);
+
+fn fold_infix(first: Expr, rest: Vec<(FuncType, Expr)>) -> Expr {
+    rest.into_iter().fold(first, |lhs, (op, rhs)| Expr::func(op, lhs, rhs))
+}
+
+named!(predicate<&[u8], (bool, Predicate)>,
+    alt!(
+        do_parse!(
+            opt!(multispace) >>
+            op: comparison_operator >>
+            rhs: term >>
+            (false, Predicate::Comparison(op, rhs))
+        ) |
+        do_parse!(
+            multispace >>
+            negated: opt!(terminated!(keyword_not, multispace)) >>
+            predicate: alt!(between | in_list | like | ilike) >>
+            (negated.is_some(), predicate)
+        ) |
+        do_parse!(
+            multispace >>
+            terminated!(tag_no_case!("is"), end_of_word) >>
+            multispace >>
+            negated: opt!(terminated!(keyword_not, multispace)) >>
+            terminated!(tag_no_case!("null"), end_of_word) >>
+            (false, Predicate::IsNull(negated.is_some()))
+        )
+    )
+);
+
+named!(between<&[u8], Predicate>,
+    do_parse!(
+        terminated!(tag_no_case!("between"), end_of_word) >>
+        lower: term >>
+        multispace >>
+        terminated!(tag_no_case!("and"), end_of_word) >>
+        upper: term >>
+        (Predicate::Between(lower, upper))
+    )
+);
+
+named!(like<&[u8], Predicate>,
+    do_parse!(
+        terminated!(tag_no_case!("like"), end_of_word) >>
+        pattern: term >>
+        (Predicate::Comparison(FuncType::Like, pattern))
+    )
+);
+
+named!(ilike<&[u8], Predicate>,
+    do_parse!(
+        terminated!(tag_no_case!("ilike"), end_of_word) >>
+        pattern: term >>
+        (Predicate::Comparison(FuncType::ILike, pattern))
+    )
+);
+
+named!(in_list<&[u8], Predicate>,
+    do_parse!(
+        terminated!(tag_no_case!("in"), end_of_word) >>
+        opt!(multispace) >>
+        char!('(') >>
+        values: separated_nonempty_list!(tag!(","), delimited!(opt!(multispace), literal, opt!(multispace))) >>
+        char!(')') >>
+        (Predicate::In(values))
+    )
+);
+
 named!(parentheses<&[u8], Expr>,
     do_parse!(
         char!('(') >>
@@ -211,32 +617,106 @@ named!(function<&[u8], Expr>,
     )
 );
 
-named!(infix_expr<&[u8], Expr>,
+named!(unary_function<&[u8], Expr>,
     do_parse!(
-        e1: expr_no_left_recur >>
+        ft: unary_function_name >>
+        char!('(') >>
+        e: expr >>
         opt!(multispace) >>
-        ft: infix_function_name >>
-        e2: expr >>
-        (Expr::func(ft, e1, e2))
+        char!(')') >>
+        (Expr::func(ft, e, Expr::Const(RawVal::Null)))
+    )
+);
+
+// Current time in seconds since the Unix epoch
+named!(now<&[u8], Expr>,
+    do_parse!(
+        tag_no_case!("now") >>
+        opt!(multispace) >>
+        char!('(') >>
+        opt!(multispace) >>
+        char!(')') >>
+        (Expr::Const(RawVal::Int(time::now().to_timespec().sec)))
+    )
+);
+
+// Length of a time interval such as `interval '7 days'` in seconds
+named!(interval<&[u8], Expr>,
+    do_parse!(
+        terminated!(tag_no_case!("interval"), end_of_word) >>
+        multispace >>
+        seconds: map_res!(string, parse_interval) >>
+        (Expr::Const(RawVal::Int(seconds)))
     )
 );
 
+fn parse_interval(interval: RawVal) -> Result<i64, String> {
+    let interval = match interval {
+        RawVal::Str(interval) => interval,
+        _ => unreachable!(),
+    };
+    let mut parts = interval.split_whitespace();
+    let seconds = match (parts.next(), parts.next(), parts.next()) {
+        (Some(count), Some(unit), None) => {
+            let count = count.parse::<i64>().map_err(|_| format!("Invalid interval {:?}", interval))?;
+            let singular = if unit.ends_with('s') { &unit[..unit.len() - 1] } else { unit };
+            let unit = TimeUnit::parse(singular).and_then(|unit| unit.seconds());
+            unit.and_then(|seconds| count.checked_mul(seconds))
+        }
+        _ => None,
+    };
+    seconds.ok_or_else(|| format!("Invalid interval {:?}", interval))
+}
+
 named!(negation<&[u8], Expr>,
     do_parse!(
         char!('-') >>
-        opt!(multispace) >>
-        e: expr >>
+        e: unary >>
         (Expr::func(FuncType::Negate, e, Expr::Const(RawVal::Null)))
     )
 );
 
+named!(not<&[u8], Expr>,
+    do_parse!(
+        keyword_not >>
+        e: negation_or_comparison >>
+        (Expr::func(FuncType::Not, e, Expr::Const(RawVal::Null)))
+    )
+);
+
+named!(keyword_not<&[u8], &[u8]>,
+    terminated!(tag_no_case!("not"), end_of_word)
+);
+
 named!(constant<&[u8], Expr>,
     map!(
-        alt!(integer |  string),
+        alt!(float | integer | string),
         Expr::Const
     )
 );
 
+named!(literal<&[u8], RawVal>,
+    alt!(float | integer | negative_float | negative_integer | string)
+);
+
+named!(negative_float<&[u8], RawVal>,
+    do_parse!(
+        char!('-') >>
+        opt!(multispace) >>
+        f: unsigned_float >>
+        (RawVal::Float(OrderedFloat(-f)))
+    )
+);
+
+named!(negative_integer<&[u8], RawVal>,
+    do_parse!(
+        char!('-') >>
+        opt!(multispace) >>
+        i: number >>
+        (RawVal::Int(-(i as i64)))
+    )
+);
+
 
 named!(integer<&[u8], RawVal>,
     map!(
@@ -251,6 +731,23 @@ named!(integer<&[u8], RawVal>,
     )
 );
 
+named!(float<&[u8], RawVal>,
+    map!(
+        unsigned_float,
+        |f: f64| RawVal::Float(OrderedFloat(f))
+    )
+);
+
+named!(unsigned_float<&[u8], f64>,
+    map_res!(
+        map_res!(
+            recognize!(tuple!(digit, char!('.'), digit)),
+            str::from_utf8
+        ),
+        FromStr::from_str
+    )
+);
+
 named!(number<&[u8], u64>,
     map_res!(
         map_res!(
@@ -262,11 +759,12 @@ named!(number<&[u8], u64>,
 );
 
 named!(string<&[u8], RawVal>,
-    do_parse!(
-        char!('"') >>
-        s: is_not!("\"") >>
-        char!('"') >>
-        (RawVal::Str(str::from_utf8(s).unwrap().to_string()))
+    map!(
+        alt!(
+            delimited!(char!('"'), is_not!("\""), char!('"')) |
+            delimited!(char!('\''), is_not!("'"), char!('\''))
+        ),
+        |s: &[u8]| RawVal::Str(str::from_utf8(s).unwrap().to_string())
     )
 );
 
@@ -278,11 +776,19 @@ named!(colname<&[u8], Expr>,
 );
 
 named!(function_name<&[u8], FuncType>,
-    alt!( infix_function_name | regex )
+    alt!( infix_function_name | regex | time_bucket | date_trunc )
+);
+
+named!(unary_function_name<&[u8], FuncType>,
+    alt!( year | month | day | hour | minute | weekday | lower | upper )
 );
 
 named!(infix_function_name<&[u8], FuncType>,
-    alt!( equals | and | or | greater | less | add | subtract | divide | multiply )
+    alt!( equals | not_equals | less_equals | greater_equals | and | or | greater | less | add | subtract | divide | multiply )
+);
+
+named!(comparison_operator<&[u8], FuncType>,
+    alt!( equals | not_equals | less_equals | greater_equals | greater | less )
 );
 
 named!(divide<&[u8], FuncType>,
@@ -305,6 +811,18 @@ named!(equals<&[u8], FuncType>,
     map!( tag!("="), |_| FuncType::Equals)
 );
 
+named!(not_equals<&[u8], FuncType>,
+    map!( alt!(tag!("<>") | tag!("!=")), |_| FuncType::NotEquals)
+);
+
+named!(less_equals<&[u8], FuncType>,
+    map!( tag!("<="), |_| FuncType::LTE)
+);
+
+named!(greater_equals<&[u8], FuncType>,
+    map!( tag!(">="), |_| FuncType::GTE)
+);
+
 named!(greater<&[u8], FuncType>,
     map!( tag!(">"), |_| FuncType::GT)
 );
@@ -314,17 +832,62 @@ named!(less<&[u8], FuncType>,
 );
 
 named!(and<&[u8], FuncType>,
-    map!( tag_no_case!("and"), |_| FuncType::And)
+    map!( terminated!(tag_no_case!("and"), end_of_word), |_| FuncType::And)
 );
 
 named!(or<&[u8], FuncType>,
-    map!( tag_no_case!("or"), |_| FuncType::Or)
+    map!( terminated!(tag_no_case!("or"), end_of_word), |_| FuncType::Or)
 );
 
+// Prevents keywords from matching prefix of identifiers, e.g. `or` in `order by`
+named!(end_of_word<&[u8], &[u8]>, not!(ident_chars));
+
+named!(ident_chars<&[u8], &[u8]>, take_while1!(is_ident_char));
+
 named!(regex<&[u8], FuncType>,
     map!( tag_no_case!("regex"), |_| FuncType::RegexMatch)
 );
 
+named!(time_bucket<&[u8], FuncType>,
+    map!( tag_no_case!("time_bucket"), |_| FuncType::TimeBucket)
+);
+
+named!(date_trunc<&[u8], FuncType>,
+    map!( tag_no_case!("date_trunc"), |_| FuncType::DateTrunc)
+);
+
+named!(year<&[u8], FuncType>,
+    map!( tag_no_case!("year"), |_| FuncType::Year)
+);
+
+named!(month<&[u8], FuncType>,
+    map!( tag_no_case!("month"), |_| FuncType::Month)
+);
+
+named!(day<&[u8], FuncType>,
+    map!( tag_no_case!("day"), |_| FuncType::Day)
+);
+
+named!(hour<&[u8], FuncType>,
+    map!( tag_no_case!("hour"), |_| FuncType::Hour)
+);
+
+named!(minute<&[u8], FuncType>,
+    map!( tag_no_case!("minute"), |_| FuncType::Minute)
+);
+
+named!(weekday<&[u8], FuncType>,
+    map!( tag_no_case!("weekday"), |_| FuncType::Weekday)
+);
+
+named!(lower<&[u8], FuncType>,
+    map!( tag_no_case!("lower"), |_| FuncType::Lower)
+);
+
+named!(upper<&[u8], FuncType>,
+    map!( tag_no_case!("upper"), |_| FuncType::Upper)
+);
+
 
 named!(identifier<&[u8], &str>,
     map_res!(
@@ -371,28 +934,55 @@ named!(limit_clause<&[u8], LimitClause>,
     )
 );
 
-named!(order_by_clause<&[u8], (String, bool)>,
-    alt!(
-        do_parse!(
-            tag_no_case!("order by") >>
-            multispace >>
-            order_by: identifier >>
-            multispace >>
-            tag_no_case!("desc") >>
-            (order_by.to_string(), true)
-        ) |
-        do_parse!(
-            tag_no_case!("order by") >>
-            multispace >>
-            order_by: identifier >>
-            opt!(preceded!(multispace, tag_no_case!("asc"))) >>
-            (order_by.to_string(), false)
-        )
+named!(group_by_clause<&[u8], Vec<Expr>>,
+    do_parse!(
+        tag_no_case!("group by") >>
+        multispace >>
+        group_by: separated_nonempty_list!(tag!(","), expr) >>
+        (group_by)
+    )
+);
+
+named!(having_clause<&[u8], Expr>,
+    do_parse!(
+        tag_no_case!("having") >>
+        multispace >>
+        having: expr >>
+        (having)
     )
 );
 
+named!(order_by_clause<&[u8], Vec<(Expr, bool)>>,
+    do_parse!(
+        tag_no_case!("order by") >>
+        multispace >>
+        order_by: separated_nonempty_list!(tag!(","), order_by_key) >>
+        (order_by)
+    )
+);
+
+named!(order_by_key<&[u8], (Expr, bool)>,
+    do_parse!(
+        key: expr >>
+        desc: opt!(preceded!(multispace, alt!(
+            map!(tag_no_case!("asc"), |_| false) |
+            map!(tag_no_case!("desc"), |_| true)
+        ))) >>
+        opt!(multispace) >>
+        (key, desc.unwrap_or(false))
+    )
+);
+
+enum Predicate {
+    Comparison(FuncType, Expr),
+    Between(Expr, Expr),
+    In(Vec<RawVal>),
+    IsNull(bool),
+}
+
 enum AggregateOrSelect {
     Aggregate((Aggregator, Expr)),
+    Aggregates(Vec<(Aggregator, Expr)>),
     Select(Expr),
 }
 
@@ -405,7 +995,7 @@ mod tests {
     fn test_select_star() {
         assert_eq!(
             format!("{:?}", parse_query("select * from default;".as_bytes())),
-            "Done([], Query { select: [ColName(\"*\")], table: \"default\", filter: Const(Int(1)), aggregate: [], order_by: None, order_desc: false, limit: LimitClause { limit: 100, offset: 0 }, order_by_index: None })");
+            "Done([], Query { select: [ColName(\"*\")], table: \"default\", filter: Const(Int(1)), aggregate: [], having: None, aliases: [None], projection: None, order_by: [], limit: LimitClause { limit: 100, offset: 0 }, order_by_index: [] })");
     }
 
     #[test]
@@ -415,4 +1005,108 @@ mod tests {
             "Done([], Query { select: [ColName(\"*\")], table: \"default\", filter: Func(GT, ColName(\"timestamp\"), Const(Int(")
         )
     }
+
+    #[test]
+    fn test_between_and_in() {
+        assert_eq!(
+            format!("{:?}", expr("a between 1 and 3 and b not in (\"x\", -2);".as_bytes())),
+            "Done([59], Func(And, Func(And, Func(GTE, ColName(\"a\"), Const(Int(1))), Func(LTE, ColName(\"a\"), Const(Int(3)))), Func(Not, In(ColName(\"b\"), [Str(\"x\"), Int(-2)]), Const(Null))))");
+    }
+
+    #[test]
+    fn test_like() {
+        assert_eq!(
+            format!("{:?}", expr("host like 'db-%' and version not like '1.%';".as_bytes())),
+            "Done([59], Func(And, Func(Like, ColName(\"host\"), Const(Str(\"db-%\"))), Func(Not, Func(Like, ColName(\"version\"), Const(Str(\"1.%\"))), Const(Null))))");
+        assert_eq!(
+            format!("{:?}", expr("lower(name) ilike '%_x%';".as_bytes())),
+            "Done([59], Func(ILike, Func(Lower, ColName(\"name\"), Const(Null)), Const(Str(\"%_x%\"))))");
+    }
+
+    #[test]
+    fn test_count_distinct() {
+        assert!(
+            format!("{:?}", parse_query("select a, count(distinct b), approx_count_distinct(c) from t;".as_bytes())).contains(
+                "aggregate: [(CountDistinct, ColName(\"b\")), (ApproxCountDistinct, ColName(\"c\"))]"
+            )
+        )
+    }
+
+    #[test]
+    fn test_percentiles() {
+        assert!(
+            format!("{:?}", parse_query("select a, median(b), percentiles(c, 0.9, 1) from t;".as_bytes())).contains(
+                "aggregate: [(Percentile(OrderedFloat(0.5)), ColName(\"b\")), (Percentile(OrderedFloat(0.9)), ColName(\"c\")), (Percentile(OrderedFloat(1.0)), ColName(\"c\"))], having: None, aliases: [None, None, None, None], projection: Some([0, 1, 2, 3])"
+            )
+        );
+        assert!(format!("{:?}", parse_query("select a, percentile(b, 2) from t;".as_bytes())).starts_with("Error"));
+    }
+
+    #[test]
+    fn test_statistical_aggregates() {
+        assert!(
+            format!("{:?}", parse_query("select a, stddev(b), corr(b, c * 2) from t;".as_bytes())).contains(
+                "aggregate: [(Stddev, ColName(\"b\")), (Corr, Func(Pair, ColName(\"b\"), Func(Multiply, ColName(\"c\"), Const(Int(2)))))]"
+            )
+        );
+        assert!(
+            format!("{:?}", parse_query("select first_name, last(status, ts) from t;".as_bytes())).contains(
+                "select: [ColName(\"first_name\")], table: \"t\", filter: Const(Int(1)), aggregate: [(Last, Func(Pair, ColName(\"status\"), ColName(\"ts\")))]"
+            )
+        );
+    }
+
+    #[test]
+    fn test_aggregate_filter() {
+        assert!(
+            format!("{:?}", parse_query("select a, count(1) filter (where b = 'error'), sum(c) FILTER(WHERE c > 0) as s from t having s > 1;".as_bytes())).contains(
+                "aggregate: [(Count, Func(AggregateFilter, Const(Int(1)), Func(Equals, ColName(\"b\"), Const(Str(\"error\"))))), (Sum, Func(AggregateFilter, ColName(\"c\"), Func(GT, ColName(\"c\"), Const(Int(0)))))], having: Some(Func(GT, Aggregate(Sum, Func(AggregateFilter, ColName(\"c\"), Func(GT, ColName(\"c\"), Const(Int(0))))), Const(Int(1))))"
+            )
+        );
+    }
+
+    #[test]
+    fn test_is_null() {
+        assert_eq!(
+            format!("{:?}", expr("a is null or b + 1 is not null;".as_bytes())),
+            "Done([59], Func(Or, Func(IsNull, ColName(\"a\"), Const(Null)), Func(IsNotNull, Func(Add, ColName(\"b\"), Const(Int(1))), Const(Null))))");
+    }
+
+    #[test]
+    fn test_float_literals() {
+        assert_eq!(
+            format!("{:?}", expr("a * 1.5 < 2 or a in (-0.25, 3);".as_bytes())),
+            "Done([59], Func(Or, Func(LT, Func(Multiply, ColName(\"a\"), Const(Float(OrderedFloat(1.5)))), Const(Int(2))), In(ColName(\"a\"), [Float(OrderedFloat(-0.25)), Int(3)])))");
+    }
+
+    #[test]
+    fn test_time_functions() {
+        assert_eq!(
+            format!("{:?}", expr("date_trunc('day', ts) = time_bucket(ts, 3600) or hour(ts) < 2;".as_bytes())),
+            "Done([59], Func(Or, Func(Equals, Func(DateTrunc, Const(Str(\"day\")), ColName(\"ts\")), Func(TimeBucket, ColName(\"ts\"), Const(Int(3600)))), Func(LT, Func(Hour, ColName(\"ts\"), Const(Null)), Const(Int(2)))))");
+        assert_eq!(
+            format!("{:?}", expr("ts - interval '2 hours';".as_bytes())),
+            "Done([59], Func(Subtract, ColName(\"ts\"), Const(Int(7200))))");
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        assert_eq!(
+            format!("{:?}", expr("a = 1 and b = 2 or c < 3;".as_bytes())),
+            "Done([59], Func(Or, Func(And, Func(Equals, ColName(\"a\"), Const(Int(1))), Func(Equals, ColName(\"b\"), Const(Int(2)))), Func(LT, ColName(\"c\"), Const(Int(3)))))");
+    }
+
+    #[test]
+    fn test_arithmetic_left_associative() {
+        assert_eq!(
+            format!("{:?}", expr("a - b - c / 2 / d;".as_bytes())),
+            "Done([59], Func(Subtract, Func(Subtract, ColName(\"a\"), ColName(\"b\")), Func(Divide, Func(Divide, ColName(\"c\"), Const(Int(2))), ColName(\"d\"))))");
+    }
+
+    #[test]
+    fn test_arithmetic_binds_tighter_than_comparison() {
+        assert_eq!(
+            format!("{:?}", expr("not a + 2 * -b >= c or d;".as_bytes())),
+            "Done([59], Func(Or, Func(Not, Func(GTE, Func(Add, ColName(\"a\"), Func(Multiply, Const(Int(2)), Func(Negate, ColName(\"b\"), Const(Null)))), ColName(\"c\")), Const(Null)), ColName(\"d\")))");
+    }
 }
@@ -8,17 +8,26 @@ use futures::executor::block_on;
 
 
 fn test_query(query: &str, expected_rows: &[Vec<Value>]) {
-    let _ = env_logger::try_init();
-    let ruba = Ruba::memory_only();
-    let _ = block_on(ruba.load_csv("test_data/tiny.csv", "default", 400, vec![]));
-    let result = block_on(ruba.run_query(query)).unwrap();
-    assert_eq!(result.0.unwrap().rows, expected_rows);
+    test_query_batched(query, 400, expected_rows)
+}
+
+fn test_query_batched(query: &str, batch_size: usize, expected_rows: &[Vec<Value>]) {
+    test_query_file("test_data/tiny.csv", batch_size, &[], query, expected_rows)
 }
 
 fn test_query_ec(query: &str, expected_rows: &[Vec<Value>]) {
+    test_query_ec_batched(query, 20, expected_rows)
+}
+
+fn test_query_ec_batched(query: &str, batch_size: usize, expected_rows: &[Vec<Value>]) {
+    test_query_file("test_data/edge_cases.csv", batch_size, &[], query, expected_rows)
+}
+
+fn test_query_file(path: &str, batch_size: usize, extractors: &[(&str, extractor::Extractor)], query: &str, expected_rows: &[Vec<Value>]) {
     let _ = env_logger::try_init();
     let ruba = Ruba::memory_only();
-    let _ = block_on(ruba.load_csv("test_data/edge_cases.csv", "default", 20, vec![]));
+    let extractors = extractors.iter().map(|&(column, extractor)| (column.to_string(), extractor)).collect();
+    let _ = block_on(ruba.load_csv(path, "default", batch_size, extractors));
     let result = block_on(ruba.run_query(query)).unwrap();
     assert_eq!(result.0.unwrap().rows, expected_rows);
 }
@@ -34,13 +43,24 @@ fn test_select_string() {
     )
 }
 
+// Repetitive strings such as `opaque_json` are stored in compressed blocks
+#[test]
+fn test_select_compressed_string() {
+    let guid = "9952a9e6-2e0c-4c25-9084-6a04e8c6a233";
+    let json = format!("{{\"firstname\":\"Christina\",\"lastname\":\"Reyes\",\"guid\":\"{}\"}}", guid);
+    test_query(
+        "select opaque_json from default where num = 5 limit 1;",
+        &[vec![Value::Str(format!("[{},{},{}]", json, json, json))]],
+    )
+}
+
 #[test]
 fn test_select_integer() {
     test_query(
         "select num from default order by num limit 2;",
         &[
-            vec![0.into()],
-            vec![0.into()]
+            vec![1.into()],
+            vec![1.into()]
         ],
     )
 }
@@ -72,7 +92,6 @@ fn group_by_integer_filter_integer_lt() {
     test_query(
         "select num, count(1) from default where num < 8;",
         &[
-            vec![0.into(), 8.into()],
             vec![1.into(), 49.into()],
             vec![2.into(), 24.into()],
             vec![3.into(), 11.into()],
@@ -154,3 +173,1095 @@ fn test_multiple_group_by_2() {
         ],
     )
 }
+
+#[test]
+fn test_sum_of_difference() {
+    test_query_ec(
+        "select enum, sum(u8_offset_encoded - non_dense_ints) from default;",
+        &[
+            vec!["aa".into(), 1699.into()],
+            vec!["bb".into(), 1024.into()],
+            vec!["cc".into(), 850.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_arithmetic_select_and_filter() {
+    test_query_ec(
+        "select u8_offset_encoded + 1 from default where (non_dense_ints * 2) < 3;",
+        &[
+            vec![257.into()],
+            vec![258.into()],
+            vec![501.into()],
+            vec![433.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_division_by_zero_is_null() {
+    test_query_ec(
+        "select u8_offset_encoded, u8_offset_encoded / non_dense_ints from default where enum = \"aa\";",
+        &[
+            vec![256.into(), Value::Null],
+            vec![258.into(), 129.into()],
+            vec![259.into(), 86.into()],
+            vec![500.into(), Value::Null],
+            vec![432.into(), 432.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_division_by_zero_constant_is_null() {
+    test_query_ec(
+        "select u8_offset_encoded, u8_offset_encoded / 0, u8_offset_encoded / 0.0 from default where enum = \"aa\";",
+        &[
+            vec![256.into(), Value::Null, Value::Null],
+            vec![258.into(), Value::Null, Value::Null],
+            vec![259.into(), Value::Null, Value::Null],
+            vec![500.into(), Value::Null, Value::Null],
+            vec![432.into(), Value::Null, Value::Null],
+        ],
+    )
+}
+
+#[test]
+fn test_constant_division_by_zero_is_null() {
+    test_query_ec(
+        "select count(1) from default where (enum = \"aa\") and ((1 / 0) is null);",
+        &[vec![5.into()]],
+    )
+}
+
+#[test]
+fn test_negation() {
+    test_query_ec(
+        "select -non_dense_ints from default where (u8_offset_encoded - 300) < -40;",
+        &[
+            vec![0.into()],
+            vec![(-2).into()],
+            vec![(-3).into()],
+            vec![(-1).into()],
+        ],
+    )
+}
+
+#[test]
+fn test_regex_dictionary_encoded() {
+    test_query(
+        "select first_name, count(1) from default where regex(first_name, \"^C.*a$\");",
+        &[
+            vec!["Christina".into(), 2.into()],
+            vec!["Cynthia".into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_regex_select() {
+    test_query(
+        "select last_name from default where regex(last_name, \"son$\") limit 3;",
+        &[
+            vec!["Simpson".into()],
+            vec!["Ferguson".into()],
+            vec!["Anderson".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_min_max_avg() {
+    test_query_ec_batched(
+        "select enum, min(u8_offset_encoded), max(u8_offset_encoded), avg(u8_offset_encoded), count(1) from default;",
+        3,
+        &[
+            vec!["aa".into(), 256.into(), 500.into(), 341.0.into(), 5.into()],
+            vec!["bb".into(), 257.into(), 500.into(), 344.0.into(), 3.into()],
+            vec!["cc".into(), 343.into(), 511.into(), 427.0.into(), 2.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_avg_integer_is_float() {
+    test_query_ec(
+        "select enum, avg(non_dense_ints) from default;",
+        &[
+            vec!["aa".into(), (6.0 / 5.0).into()],
+            vec!["bb".into(), (8.0 / 3.0).into()],
+            vec!["cc".into(), 2.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_min_max_string_type_error() {
+    let ruba = Ruba::memory_only();
+    let _ = block_on(ruba.load_csv("test_data/edge_cases.csv", "default", 20, vec![]));
+    for query in &["select min(enum) from default;", "select non_dense_ints, max(enum) from default;"] {
+        match block_on(ruba.run_query(query)).unwrap().0.map(|output| output.rows) {
+            Err(QueryError::TypeError(_)) => {}
+            result => panic!("{}: expected TypeError, got {:?}", query, result),
+        }
+    }
+}
+
+#[test]
+fn test_aggregate_without_group_by() {
+    test_query_ec(
+        "select count(1) from default;",
+        &[vec![10.into()]],
+    )
+}
+
+#[test]
+fn test_aggregate_without_group_by_filtered() {
+    test_query_ec_batched(
+        "select count(1), sum(u8_offset_encoded), max(non_dense_ints), approx_count_distinct(enum) from default where non_dense_ints > 1;",
+        3,
+        &[vec![6.into(), 2146.into(), 4.into(), 3.into()]],
+    )
+}
+
+#[test]
+fn test_aggregate_without_group_by_no_rows() {
+    test_query_ec_batched(
+        "select count(1), sum(non_dense_ints) from default where non_dense_ints > 100;",
+        3,
+        &[vec![0.into(), Value::Null]],
+    )
+}
+
+#[test]
+fn test_group_by_having() {
+    test_query_ec_batched(
+        "select sum(u8_offset_encoded), enum from default group by enum having count(1) > 2;",
+        3,
+        &[
+            vec![1705.into(), "aa".into()],
+            vec![1032.into(), "bb".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_having_on_omitted_grouping_key() {
+    test_query_ec(
+        "select count(1) from default group by non_dense_ints having non_dense_ints > 1;",
+        &[
+            vec![3.into()],
+            vec![2.into()],
+            vec![1.into()],
+        ],
+    )
+}
+
+// The maximum of `value` is null for group `b`
+#[test]
+fn test_having_null_aggregate() {
+    test_query_file(
+        "test_data/nulls.csv",
+        3,
+        &[],
+        "select grp, count(1) from default where id < 5 group by grp having max(value) > 3;",
+        &[vec!["a".into(), 3.into()]],
+    )
+}
+
+#[test]
+fn test_having_null_aggregate_or() {
+    test_query_file(
+        "test_data/nulls.csv",
+        3,
+        &[],
+        "select grp, count(1) from default where id < 5 group by grp having (max(value) > 3) or (max(value) is null);",
+        &[
+            vec!["a".into(), 3.into()],
+            vec!["b".into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_order_by_aggregate() {
+    test_query_ec(
+        "select non_dense_ints, count(1) from default order by count_0 desc, non_dense_ints desc limit 3;",
+        &[
+            vec![2.into(), 3.into()],
+            vec![3.into(), 2.into()],
+            vec![1.into(), 2.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_order_by_unselected_aggregate() {
+    test_query_ec_batched(
+        "select enum from default order by count(1);",
+        3,
+        &[
+            vec!["cc".into()],
+            vec!["bb".into()],
+            vec!["aa".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_order_by_multiple_keys() {
+    test_query_ec_batched(
+        "select enum, u8_offset_encoded from default order by enum desc, u8_offset_encoded limit 5;",
+        3,
+        &[
+            vec!["cc".into(), 343.into()],
+            vec!["cc".into(), 511.into()],
+            vec!["bb".into(), 257.into()],
+            vec!["bb".into(), 275.into()],
+            vec!["bb".into(), 500.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_column_aliases() {
+    let _ = env_logger::try_init();
+    let ruba = Ruba::memory_only();
+    let _ = block_on(ruba.load_csv("test_data/edge_cases.csv", "default", 3, vec![]));
+    let query = "select enum as kind, count(1) as n from default having n > 2 order by n desc;";
+    let result = block_on(ruba.run_query(query)).unwrap().0.unwrap();
+    assert_eq!(result.colnames, vec!["kind".to_string(), "n".to_string()]);
+    assert_eq!(result.rows, vec![
+        vec![Value::from("aa"), 5.into()],
+        vec!["bb".into(), 3.into()],
+    ]);
+}
+
+#[test]
+fn test_group_by_negative_values() {
+    test_query_ec_batched(
+        "select non_dense_ints - 2, count(1) from default;",
+        3,
+        &[
+            vec![(-2).into(), 2.into()],
+            vec![(-1).into(), 2.into()],
+            vec![0.into(), 3.into()],
+            vec![1.into(), 2.into()],
+            vec![2.into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_group_by_three_columns() {
+    test_query_ec_batched(
+        "select enum, non_dense_ints / 2, u8_offset_encoded / 100, count(1) from default;",
+        3,
+        &[
+            vec!["aa".into(), 0.into(), 2.into(), 1.into()],
+            vec!["aa".into(), 0.into(), 4.into(), 1.into()],
+            vec!["aa".into(), 0.into(), 5.into(), 1.into()],
+            vec!["aa".into(), 1.into(), 2.into(), 2.into()],
+            vec!["bb".into(), 0.into(), 2.into(), 1.into()],
+            vec!["bb".into(), 1.into(), 5.into(), 1.into()],
+            vec!["bb".into(), 2.into(), 2.into(), 1.into()],
+            vec!["cc".into(), 1.into(), 3.into(), 1.into()],
+            vec!["cc".into(), 1.into(), 5.into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_comparison_operators() {
+    test_query_ec(
+        "select u8_offset_encoded from default where ((u8_offset_encoded >= 275) AND (u8_offset_encoded <= 432)) OR ((non_dense_ints <> 2) AND (600 < u8_offset_encoded));",
+        &[
+            vec![275.into()],
+            vec![343.into()],
+            vec![432.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_not_between() {
+    test_query_ec(
+        "select enum, count(1) from default where not (non_dense_ints between 1 and 3);",
+        &[
+            vec!["aa".into(), 2.into()],
+            vec!["bb".into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_in_list_dictionary_encoded() {
+    test_query_ec(
+        "select enum, count(1) from default where enum in (\"aa\", \"cc\", \"zz\");",
+        &[
+            vec!["aa".into(), 5.into()],
+            vec!["cc".into(), 2.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_not_in_list_integer() {
+    test_query_ec(
+        "select non_dense_ints, count(1) from default where non_dense_ints not in (0, 2, -1);",
+        &[
+            vec![1.into(), 2.into()],
+            vec![3.into(), 2.into()],
+            vec![4.into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_column_column_comparison() {
+    test_query_ec(
+        "select u8_offset_encoded, non_dense_ints from default where (u8_offset_encoded / 100) < non_dense_ints;",
+        &[
+            vec![259.into(), 3.into()],
+            vec![275.into(), 4.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_string_column_comparison() {
+    test_query(
+        "select first_name, last_name from default where (first_name > last_name) AND (num >= 2) AND (tld = \"name\");",
+        &[
+            vec!["Donna".into(), "Cook".into()],
+            vec!["Judith".into(), "Alvarez".into()],
+            vec!["Pamela".into(), "Kelley".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_operator_precedence() {
+    test_query_ec(
+        "select u8_offset_encoded - non_dense_ints - 1 from default where non_dense_ints = 0 or non_dense_ints = 4 and u8_offset_encoded > 300;",
+        &[
+            vec![255.into()],
+            vec![499.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_aggregates_skip_nulls() {
+    test_query_batched(
+        "select tld, count(num), count(1), sum(num) from default where (tld is null) or (tld = \"name\");",
+        13,
+        &[
+            vec!["name".into(), 17.into(), 17.into(), 26.into()],
+            vec![Value::Null, 0.into(), 8.into(), Value::Null],
+        ],
+    )
+}
+
+#[test]
+fn test_aggregates_of_groups_without_values_are_null() {
+    test_query_file(
+        "test_data/nulls.csv",
+        3,
+        &[],
+        "select grp, sum(value), min(value), max(value), avg(value), count(value) from default where id < 4;",
+        &[
+            vec!["a".into(), Value::Null, Value::Null, Value::Null, Value::Null, 0.into()],
+            vec!["b".into(), Value::Null, Value::Null, Value::Null, Value::Null, 0.into()],
+        ],
+    )
+}
+
+// The first batch of `value` and `name` contains only nulls
+#[test]
+fn test_all_null_batch_aggregates() {
+    test_query_file(
+        "test_data/nulls.csv",
+        3,
+        &[],
+        "select grp, sum(value), min(value), max(value), count(value), arg_max(id, value) from default;",
+        &[
+            vec!["a".into(), 5.into(), 5.into(), 5.into(), 1.into(), 4.into()],
+            vec!["b".into(), 12.into(), 2.into(), 7.into(), 3.into(), 6.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_all_null_batch_group_by() {
+    test_query_file(
+        "test_data/nulls.csv",
+        3,
+        &[],
+        "select value, count(1) from default;",
+        &[
+            vec![2.into(), 1.into()],
+            vec![3.into(), 1.into()],
+            vec![5.into(), 1.into()],
+            vec![7.into(), 1.into()],
+            vec![Value::Null, 4.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_all_null_batch_order_by() {
+    test_query_file(
+        "test_data/nulls.csv",
+        3,
+        &[],
+        "select id, name from default order by name;",
+        &[
+            vec![4.into(), "x".into()],
+            vec![8.into(), "x".into()],
+            vec![5.into(), "y".into()],
+            vec![7.into(), "z".into()],
+            vec![1.into(), Value::Null],
+            vec![2.into(), Value::Null],
+            vec![3.into(), Value::Null],
+            vec![6.into(), Value::Null],
+        ],
+    )
+}
+
+#[test]
+fn test_select_is_null() {
+    test_query_file(
+        "test_data/nulls.csv",
+        3,
+        &[],
+        "select id, value is null, value is not null from default where id < 6 order by id;",
+        &[
+            vec![1.into(), 1.into(), 0.into()],
+            vec![2.into(), 1.into(), 0.into()],
+            vec![3.into(), 1.into(), 0.into()],
+            vec![4.into(), 0.into(), 1.into()],
+            vec![5.into(), 0.into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_all_null_batch_not_equals() {
+    test_query_file(
+        "test_data/nulls.csv",
+        3,
+        &[],
+        "select id from default where value <> 5;",
+        &[vec![5.into()], vec![6.into()], vec![8.into()]],
+    )
+}
+
+// The first batch of `value` contains only whole numbers
+#[test]
+fn test_float_column_with_whole_number_batch_aggregates() {
+    test_query_file(
+        "test_data/floats.csv",
+        3,
+        &[],
+        "select grp, sum(value), min(value), max(value) from default;",
+        &[
+            vec!["a".into(), 5.5.into(), 1.0.into(), 3.0.into()],
+            vec!["b".into(), 8.5.into(), 2.0.into(), 4.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_float_column_with_whole_number_batch_order_by() {
+    test_query_file(
+        "test_data/floats.csv",
+        3,
+        &[],
+        "select id, value from default order by value desc limit 3;",
+        &[
+            vec![6.into(), 4.0.into()],
+            vec![3.into(), 3.0.into()],
+            vec![5.into(), 2.5.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_float_column_with_whole_number_batch_group_by() {
+    test_query_file(
+        "test_data/floats.csv",
+        3,
+        &[],
+        "select value, count(1) from default where value > 2;",
+        &[
+            vec![2.5.into(), 1.into()],
+            vec![3.0.into(), 1.into()],
+            vec![4.0.into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_not_excludes_nulls() {
+    test_query(
+        "select num, count(1) from default where not (num < 2);",
+        &[
+            vec![2.into(), 24.into()],
+            vec![3.into(), 11.into()],
+            vec![4.into(), 5.into()],
+            vec![5.into(), 2.into()],
+            vec![8.into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_order_by_nullable_desc() {
+    test_query_batched(
+        "select num from default order by num desc limit 3;",
+        13,
+        &[
+            vec![Value::Null],
+            vec![Value::Null],
+            vec![Value::Null],
+        ],
+    )
+}
+
+#[test]
+fn test_float_aggregates() {
+    test_query_ec_batched(
+        "select enum, sum(float), avg(float), min(float), max(float) from default;",
+        3,
+        &[
+            vec!["aa".into(), 13.5.into(), 2.7.into(), (-3.5).into(), 10.5.into()],
+            vec!["bb".into(), 2.125.into(), (2.125 / 3.0).into(), 0.0.into(), 2.0.into()],
+            vec!["cc".into(), 1.25.into(), 0.625.into(), (-0.25).into(), 1.5.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_float_arithmetic_and_filter() {
+    test_query_ec(
+        "select float * 2 + non_dense_ints from default where float > 1;",
+        &[
+            vec![4.5.into()],
+            vec![5.0.into()],
+            vec![21.0.into()],
+            vec![10.5.into()],
+            vec![5.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_group_by_float() {
+    test_query_ec_batched(
+        "select float, count(1) from default where non_dense_ints = 2;",
+        3,
+        &[
+            vec![(-0.25).into(), 1.into()],
+            vec![1.25.into(), 1.into()],
+            vec![1.5.into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_decimal_aggregates() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("float", extractor::Extractor::Decimal(2))],
+        "select enum, sum(float), avg(float), min(float), max(float) from default;",
+        &[
+            vec!["aa".into(), 13.5.into(), 2.7.into(), (-3.5).into(), 10.5.into()],
+            vec!["bb".into(), 2.13.into(), 0.71.into(), 0.0.into(), 2.0.into()],
+            vec!["cc".into(), 1.25.into(), 0.625.into(), (-0.25).into(), 1.5.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_decimal_arithmetic_and_filter() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("float", extractor::Extractor::Decimal(2))],
+        "select float, float * 2 - 1 from default where float > 1.25 and float <= 10;",
+        &[
+            vec![2.0.into(), 3.0.into()],
+            vec![4.75.into(), 8.5.into()],
+            vec![1.5.into(), 2.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_decimal_rounding() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("float", extractor::Extractor::Decimal(2))],
+        "select float from default where float > 0.125 and float < 1;",
+        &[
+            vec![0.5.into()],
+            vec![0.13.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_time_bucket() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("time", extractor::Extractor::Timestamp)],
+        "select time_bucket(time, 3600), count(1) from default where time >= '2018-03-04' and time < '2018-03-05';",
+        &[
+            vec!["2018-03-04 05:00:00".into(), 3.into()],
+            vec!["2018-03-04 06:00:00".into(), 2.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_date_trunc_and_weekday() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("time", extractor::Extractor::Timestamp)],
+        "select date_trunc('month', time), count(1) from default;",
+        &[
+            vec!["2017-12-01 00:00:00".into(), 1.into()],
+            vec!["2018-03-01 00:00:00".into(), 8.into()],
+            vec!["2018-04-01 00:00:00".into(), 1.into()],
+        ],
+    );
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("time", extractor::Extractor::Timestamp)],
+        "select weekday(time), count(1) from default;",
+        &[
+            vec![1.into(), 1.into()],
+            vec![2.into(), 1.into()],
+            vec![7.into(), 8.into()],
+        ],
+    );
+}
+
+#[test]
+fn test_relative_time() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("time", extractor::Extractor::Timestamp)],
+        "select enum, count(1) from default where time > now() - interval '30000 days';",
+        &[
+            vec!["aa".into(), 5.into()],
+            vec!["bb".into(), 3.into()],
+            vec!["cc".into(), 2.into()],
+        ],
+    );
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("time", extractor::Extractor::Timestamp)],
+        "select time - interval '1 hour' from default where time = '2018-03-05 23:30:00';",
+        &[vec!["2018-03-05 22:30:00".into()]],
+    );
+}
+
+#[test]
+fn test_run_length_aggregation() {
+    test_query_file(
+        "test_data/runs.csv",
+        200,
+        &[],
+        "select status, sum(level), sum(value), count(0) from default;",
+        &[
+            vec![1.into(), 1500.into(), 444.into(), 150.into()],
+            vec![2.into(), 1700.into(), 453.into(), 150.into()],
+            vec![3.into(), 2000.into(), 300.into(), 100.into()],
+        ],
+    );
+}
+
+#[test]
+fn test_run_length_filter() {
+    test_query_file(
+        "test_data/runs.csv",
+        200,
+        &[],
+        "select status, count(0) from default where level >= 20;",
+        &[
+            vec![2.into(), 20.into()],
+            vec![3.into(), 100.into()],
+        ],
+    );
+    test_query_file(
+        "test_data/runs.csv",
+        200,
+        &[],
+        "select status, sum(level) from default where level <> 10 and status < 3;",
+        &[vec![2.into(), 400.into()]],
+    );
+}
+
+#[test]
+fn test_delta_encoded_column() {
+    test_query_file(
+        "test_data/runs.csv",
+        200,
+        &[],
+        "select status, min(time), max(time) from default;",
+        &[
+            vec![1.into(), 1520000016.into(), 1520002267.into()],
+            vec![2.into(), 1520002281.into(), 1520004532.into()],
+            vec![3.into(), 1520004548.into(), 1520006043.into()],
+        ],
+    );
+    test_query_file(
+        "test_data/runs.csv",
+        200,
+        &[],
+        "select time from default order by time desc limit 2;",
+        &[vec![1520006043.into()], vec![1520006028.into()]],
+    );
+}
+
+#[test]
+fn test_bit_packed_column() {
+    test_query_file(
+        "test_data/runs.csv",
+        200,
+        &[],
+        "select status, sum(distance), min(distance), max(distance) from default;",
+        &[
+            vec![1.into(), 172285.into(), 1005.into(), 1300.into()],
+            vec![2.into(), 174642.into(), 1003.into(), 1299.into()],
+            vec![3.into(), 114878.into(), 1010.into(), 1297.into()],
+        ],
+    );
+    test_query_file(
+        "test_data/runs.csv",
+        200,
+        &[],
+        "select status, count(0) from default where distance < 1010;",
+        &[
+            vec![1.into(), 10.into()],
+            vec![2.into(), 3.into()],
+        ],
+    );
+    test_query_file(
+        "test_data/runs.csv",
+        200,
+        &[],
+        "select distance, count(0) from default where distance >= 1296;",
+        &[
+            vec![1296.into(), 2.into()],
+            vec![1297.into(), 2.into()],
+            vec![1298.into(), 1.into()],
+            vec![1299.into(), 2.into()],
+            vec![1300.into(), 1.into()],
+        ],
+    );
+}
+
+#[test]
+fn test_group_by_shared_dictionary() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("enum", extractor::Extractor::SharedDictionary)],
+        "select enum, count(0) from default;",
+        &[
+            vec!["aa".into(), 5.into()],
+            vec!["bb".into(), 3.into()],
+            vec!["cc".into(), 2.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_group_by_two_columns_shared_dictionary() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("enum", extractor::Extractor::SharedDictionary)],
+        "select enum, non_dense_ints, count(0) from default;",
+        &[
+            vec!["aa".into(), 0.into(), 2.into()],
+            vec!["aa".into(), 1.into(), 1.into()],
+            vec!["aa".into(), 2.into(), 1.into()],
+            vec!["aa".into(), 3.into(), 1.into()],
+            vec!["bb".into(), 1.into(), 1.into()],
+            vec!["bb".into(), 3.into(), 1.into()],
+            vec!["bb".into(), 4.into(), 1.into()],
+            vec!["cc".into(), 2.into(), 2.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_filter_shared_dictionary() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("enum", extractor::Extractor::SharedDictionary)],
+        "select u8_offset_encoded from default where enum = 'cc';",
+        &[
+            vec![343.into()],
+            vec![511.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_count_distinct_shared_dictionary() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("enum", extractor::Extractor::SharedDictionary)],
+        "select non_dense_ints, count(distinct enum) from default;",
+        &[
+            vec![0.into(), 1.into()],
+            vec![1.into(), 2.into()],
+            vec![2.into(), 2.into()],
+            vec![3.into(), 2.into()],
+            vec![4.into(), 1.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_count_distinct_without_shared_dictionary_error() {
+    let ruba = Ruba::memory_only();
+    let _ = block_on(ruba.load_csv("test_data/edge_cases.csv", "default", 3, vec![]));
+    let query = "select non_dense_ints, count(distinct enum) from default;";
+    match block_on(ruba.run_query(query)).unwrap().0.map(|output| output.rows) {
+        Err(QueryError::NotImplemented(_)) => {}
+        result => panic!("{}: expected NotImplemented, got {:?}", query, result),
+    }
+}
+
+#[test]
+fn test_count_distinct_integers() {
+    test_query_ec_batched(
+        "select count(distinct non_dense_ints), count(distinct u8_offset_encoded) from default;",
+        3,
+        &[vec![5.into(), 9.into()]],
+    )
+}
+
+#[test]
+fn test_approx_count_distinct() {
+    test_query_ec_batched(
+        "select enum, approx_count_distinct(non_dense_ints), approx_count_distinct(u8_offset_encoded) from default;",
+        3,
+        &[
+            vec!["aa".into(), 4.into(), 5.into()],
+            vec!["bb".into(), 3.into(), 3.into()],
+            vec!["cc".into(), 1.into(), 2.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_percentiles() {
+    test_query_ec_batched(
+        "select enum, median(float), percentiles(non_dense_ints, 0, 1), percentile(u8_offset_encoded, 0.99) from default;",
+        3,
+        &[
+            vec!["aa".into(), 1.25.into(), 0.0.into(), 3.0.into(), 500.0.into()],
+            vec!["bb".into(), 0.125.into(), 1.0.into(), 4.0.into(), 500.0.into()],
+            vec!["cc".into(), 0.625.into(), 2.0.into(), 2.0.into(), 511.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_percentiles_of_different_expressions() {
+    test_query_ec_batched(
+        "select percentiles(non_dense_ints, 0.5, 1) filter (where enum = 'bb'), percentile(non_dense_ints, 0) from default;",
+        3,
+        &[vec![3.0.into(), 4.0.into(), 0.0.into()]],
+    )
+}
+
+#[test]
+fn test_statistical_aggregates() {
+    test_query_ec_batched(
+        "select non_dense_ints, variance(float), stddev(float), covar(float, u8_offset_encoded), corr(u8_offset_encoded, float) \
+         from default where non_dense_ints <> 2;",
+        3,
+        &[
+            vec![0.into(), 50.0.into(), 50f64.sqrt().into(), 1220.0.into(), 1.0.into()],
+            vec![1.into(), 3.78125.into(), 3.78125f64.sqrt().into(), 240.625.into(), 1.0.into()],
+            vec![3.into(), 6.125.into(), 6.125f64.sqrt().into(), 421.75.into(), 1.0.into()],
+            vec![4.into(), Value::Null, Value::Null, Value::Null, Value::Null],
+        ],
+    )
+}
+
+#[test]
+fn test_arg_min_max_first_last() {
+    test_query_file(
+        "test_data/edge_cases.csv",
+        3,
+        &[("time", extractor::Extractor::Timestamp)],
+        "select non_dense_ints, arg_max(enum, float), arg_min(u8_offset_encoded, float), first(enum, time), last(float, time) \
+         from default;",
+        &[
+            vec![0.into(), "aa".into(), 256.into(), "aa".into(), 10.5.into()],
+            vec![1.into(), "aa".into(), 257.into(), "aa".into(), 2.0.into()],
+            vec![2.into(), "cc".into(), 343.into(), "cc".into(), 1.5.into()],
+            vec![3.into(), "bb".into(), 259.into(), "aa".into(), 0.0.into()],
+            vec![4.into(), "bb".into(), 275.into(), "bb".into(), 0.125.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_sort_by_high_cardinality_string() {
+    test_query(
+        "select hash, last_name from default order by hash limit 2;",
+        &[
+            vec!["001afb298cbd02141733745f6a2cda1e723366d6ef0417ad1b27c8c1eb89edc8".into(), "Taylor".into()],
+            vec!["02e694ab100a6a346d1bca36bdc9ea4c7f8b38a54106d34ca6b5025ad723ef4e".into(), "Tucker".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_string_range_filter() {
+    test_query_ec(
+        "select u8_offset_encoded from default where enum > 'aa';",
+        &[vec![257.into()], vec![275.into()], vec![343.into()], vec![511.into()], vec![500.into()]],
+    );
+    test_query_ec_batched(
+        "select u8_offset_encoded from default where enum <= 'bb';",
+        3,
+        &[vec![256.into()], vec![258.into()], vec![259.into()], vec![257.into()], vec![275.into()],
+            vec![500.into()], vec![432.into()], vec![500.into()]],
+    );
+}
+
+#[test]
+fn test_string_between() {
+    test_query_ec(
+        "select u8_offset_encoded from default where enum between 'b' and 'bz';",
+        &[vec![257.into()], vec![275.into()], vec![500.into()]],
+    )
+}
+
+#[test]
+fn test_like_prefix() {
+    test_query_ec(
+        "select u8_offset_encoded from default where enum like 'c%';",
+        &[vec![343.into()], vec![511.into()]],
+    );
+    test_query_ec_batched(
+        "select u8_offset_encoded from default where enum like 'c%';",
+        3,
+        &[vec![343.into()], vec![511.into()]],
+    );
+}
+
+#[test]
+fn test_like_wildcards() {
+    test_query_ec(
+        "select u8_offset_encoded from default where enum like '_c';",
+        &[vec![343.into()], vec![511.into()]],
+    );
+    test_query_ec_batched(
+        "select u8_offset_encoded from default where enum not like '%a%';",
+        3,
+        &[vec![257.into()], vec![275.into()], vec![343.into()], vec![511.into()], vec![500.into()]],
+    );
+}
+
+#[test]
+fn test_ilike() {
+    test_query_ec(
+        "select u8_offset_encoded from default where enum ilike 'B%';",
+        &[vec![257.into()], vec![275.into()], vec![500.into()]],
+    )
+}
+
+#[test]
+fn test_upper_lower() {
+    test_query_ec(
+        "select upper(enum), count(0) from default;",
+        &[
+            vec!["AA".into(), 5.into()],
+            vec!["BB".into(), 3.into()],
+            vec!["CC".into(), 2.into()],
+        ],
+    );
+    test_query_ec_batched(
+        "select u8_offset_encoded, lower(upper(enum)) from default where upper(enum) = 'CC';",
+        3,
+        &[vec![343.into(), "cc".into()], vec![511.into(), "cc".into()]],
+    );
+}
+
+#[test]
+fn test_lower_compressed_string() {
+    test_query(
+        "select first_name from default where lower(opaque_json) like '%\"firstname\":\"christina\"%';",
+        &[vec!["Christina".into()], vec!["Christina".into()]],
+    );
+}
+
+#[test]
+fn test_select_lower_compressed_string() {
+    let guid = "9952a9e6-2e0c-4c25-9084-6a04e8c6a233";
+    let json = format!("{{\"firstname\":\"christina\",\"lastname\":\"reyes\",\"guid\":\"{}\"}}", guid);
+    test_query(
+        "select lower(opaque_json) from default where num = 5 limit 1;",
+        &[vec![Value::Str(format!("[{},{},{}]", json, json, json))]],
+    )
+}
+
+#[test]
+fn test_aggregate_filter() {
+    test_query_ec_batched(
+        "select enum, count(1) filter (where float > 1.0), sum(u8_offset_encoded) filter (where non_dense_ints = 0), count(1) \
+         from default;",
+        3,
+        &[
+            vec!["aa".into(), 3.into(), 756.into(), 5.into()],
+            vec!["bb".into(), 1.into(), Value::Null, 3.into()],
+            vec!["cc".into(), 1.into(), Value::Null, 2.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_aggregate_filter_without_rows() {
+    test_query_ec_batched(
+        "select enum, min(u8_offset_encoded) filter (where float > 1000.0), avg(u8_offset_encoded) filter (where float > 1000.0), \
+         count(1) filter (where float > 1000.0) from default;",
+        3,
+        &[
+            vec!["aa".into(), Value::Null, Value::Null, 0.into()],
+            vec!["bb".into(), Value::Null, Value::Null, 0.into()],
+            vec!["cc".into(), Value::Null, Value::Null, 0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_aggregate_filter_without_rows_no_group_by() {
+    test_query_ec_batched(
+        "select min(u8_offset_encoded) filter (where float > 1000.0), max(float) filter (where float > 1000.0), \
+         avg(u8_offset_encoded) filter (where float > 1000.0), count(1) filter (where float > 1000.0) from default;",
+        3,
+        &[vec![Value::Null, Value::Null, Value::Null, 0.into()]],
+    )
+}